@@ -1,6 +1,9 @@
 use std::fs;
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Entry type in the file browser
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -19,6 +22,13 @@ pub struct Entry {
     pub entry_type: EntryType,
     pub size: Option<u64>,
     pub extension: Option<String>,
+    pub modified: Option<SystemTime>,
+    pub permissions: Option<String>,
+    pub link_target: Option<PathBuf>,
+    /// Whether a symlink resolves to a directory, checked once when listing
+    pub target_is_dir: bool,
+    /// Nesting level in tree view (0 for entries of `current_dir`)
+    pub depth: usize,
 }
 
 impl Entry {
@@ -30,9 +40,21 @@ impl Entry {
         self.entry_type == EntryType::File
     }
 
+    pub fn is_symlink(&self) -> bool {
+        self.entry_type == EntryType::Symlink
+    }
+
+    /// A directory, or a symlink to one: sorted, entered and expanded like a directory
+    pub fn is_dir_like(&self) -> bool {
+        self.is_directory() || self.target_is_dir
+    }
+
     pub fn display_name(&self) -> String {
         if self.is_directory() {
             format!("{}/", self.name)
+        } else if let Some(target) = &self.link_target {
+            let slash = if self.target_is_dir { "/" } else { "" };
+            format!("{}{} -> {}", self.name, slash, target.display())
         } else {
             self.name.clone()
         }
@@ -54,28 +76,147 @@ impl Entry {
             None => String::new(),
         }
     }
+
+    /// Format the modification time as `YYYY-MM-DD HH:MM` (UTC)
+    pub fn modified_string(&self) -> String {
+        match self.modified.and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+            Some(since_epoch) => format_timestamp(since_epoch.as_secs()),
+            None => String::new(),
+        }
+    }
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DD HH:MM` (UTC)
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (hour, minute) = (rem / 3600, (rem % 3600) / 60);
+
+    // Civil date from days since epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute)
+}
+
+/// Format Unix permission bits like `rwxr-xr-x`
+pub fn format_mode(mode: u32) -> String {
+    let flags = ['r', 'w', 'x'];
+    (0..9)
+        .map(|i| {
+            if mode & (0o400 >> i) != 0 {
+                flags[i % 3]
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Describe an entry's permissions from its metadata
+#[cfg(unix)]
+fn permissions_string(meta: &fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    format_mode(meta.permissions().mode())
+}
+
+/// Describe an entry's attributes from its metadata (read-only/hidden)
+#[cfg(windows)]
+fn permissions_string(meta: &fs::Metadata) -> String {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+    let attrs = meta.file_attributes();
+    format!(
+        "{}{}",
+        if attrs & FILE_ATTRIBUTE_READONLY != 0 { "ro" } else { "rw" },
+        if attrs & FILE_ATTRIBUTE_HIDDEN != 0 { " hidden" } else { "" }
+    )
 }
 
 impl PartialOrd for Entry {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
 impl Ord for Entry {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> Ordering {
         // Directories first, then files
-        match (&self.entry_type, &other.entry_type) {
-            (EntryType::Directory, EntryType::Directory) => {
-                self.name.to_lowercase().cmp(&other.name.to_lowercase())
-            }
-            (EntryType::Directory, _) => std::cmp::Ordering::Less,
-            (_, EntryType::Directory) => std::cmp::Ordering::Greater,
+        match (self.is_dir_like(), other.is_dir_like()) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
             _ => self.name.to_lowercase().cmp(&other.name.to_lowercase()),
         }
     }
 }
 
+/// Sort order for the directory listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortMode {
+    Name,
+    Size,
+    Modified,
+    Extension,
+}
+
+impl SortMode {
+    /// Cycle to the next sort mode
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Name => SortMode::Size,
+            SortMode::Size => SortMode::Modified,
+            SortMode::Modified => SortMode::Extension,
+            SortMode::Extension => SortMode::Name,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortMode::Name => "name",
+            SortMode::Size => "size",
+            SortMode::Modified => "modified",
+            SortMode::Extension => "extension",
+        }
+    }
+}
+
+/// Compare two entries using the given sort mode
+fn compare_entries(a: &Entry, b: &Entry, mode: SortMode) -> Ordering {
+    let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+    match mode {
+        SortMode::Name => by_name(),
+        SortMode::Size => a.size.cmp(&b.size).then_with(by_name),
+        SortMode::Modified => a.modified.cmp(&b.modified).then_with(by_name),
+        SortMode::Extension => a.extension.cmp(&b.extension).then_with(by_name),
+    }
+}
+
+/// Sort a flattened tree listing, keeping each entry's descendants directly below it
+fn sort_tree(entries: Vec<Entry>, cmp: &dyn Fn(&Entry, &Entry) -> Ordering) -> Vec<Entry> {
+    let mut groups: Vec<(Entry, Vec<Entry>)> = Vec::new();
+    for entry in entries {
+        match groups.last_mut() {
+            Some((head, children)) if entry.depth > head.depth => children.push(entry),
+            _ => groups.push((entry, Vec::new())),
+        }
+    }
+
+    groups.sort_by(|a, b| cmp(&a.0, &b.0));
+    groups
+        .into_iter()
+        .flat_map(|(head, children)| std::iter::once(head).chain(sort_tree(children, cmp)))
+        .collect()
+}
+
 /// Directory browser
 pub struct Browser {
     pub current_dir: PathBuf,
@@ -83,6 +224,12 @@ pub struct Browser {
     pub selected: usize,
     pub scroll_offset: usize,
     pub show_hidden: bool,
+    pub sort_mode: SortMode,
+    pub sort_reverse: bool,
+    pub dirs_first: bool,
+    /// Expand directories inline instead of navigating into them
+    pub tree_view: bool,
+    pub expanded: HashSet<PathBuf>,
 }
 
 impl Browser {
@@ -101,6 +248,11 @@ impl Browser {
             selected: 0,
             scroll_offset: 0,
             show_hidden: false,
+            sort_mode: SortMode::Name,
+            sort_reverse: false,
+            dirs_first: true,
+            tree_view: false,
+            expanded: HashSet::new(),
         };
 
         browser.refresh()?;
@@ -119,11 +271,45 @@ impl Browser {
                 entry_type: EntryType::Directory,
                 size: None,
                 extension: None,
+                modified: None,
+                permissions: None,
+                link_target: None,
+                target_is_dir: false,
+                depth: 0,
             });
         }
 
-        // Read directory entries
-        for entry_result in fs::read_dir(&self.current_dir)? {
+        let mut listing = self.read_entries(&self.current_dir, 0)?;
+        listing.reverse();
+
+        // Walk the listing depth-first, splicing in children of expanded directories
+        while let Some(entry) = listing.pop() {
+            if self.tree_view
+                && self.expanded.contains(&entry.path)
+                && entry.is_dir_like()
+                && let Ok(children) = self.read_entries(&entry.path, entry.depth + 1)
+            {
+                listing.extend(children.into_iter().rev());
+            }
+            self.entries.push(entry);
+        }
+
+        // Sort entries
+        self.sort_entries();
+
+        // Reset selection if needed
+        if self.selected >= self.entries.len() && !self.entries.is_empty() {
+            self.selected = self.entries.len() - 1;
+        }
+
+        Ok(())
+    }
+
+    /// Read the entries of a single directory at the given tree depth
+    fn read_entries(&self, dir: &Path, depth: usize) -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+
+        for entry_result in fs::read_dir(dir)? {
             let entry = entry_result?;
             let name = entry.file_name().to_string_lossy().to_string();
 
@@ -132,16 +318,25 @@ impl Browser {
                 continue;
             }
 
-            let metadata = entry.metadata();
             let path = entry.path();
+            let link_meta = fs::symlink_metadata(&path);
+            let is_link = link_meta.as_ref().is_ok_and(|m| m.file_type().is_symlink());
 
-            let entry_type = if let Ok(ref meta) = metadata {
+            // For links, report the target's details when it resolves
+            let link_target = if is_link { fs::read_link(&path).ok() } else { None };
+            let metadata = if is_link {
+                fs::metadata(&path).or(link_meta)
+            } else {
+                link_meta
+            };
+
+            let entry_type = if is_link {
+                EntryType::Symlink
+            } else if let Ok(ref meta) = metadata {
                 if meta.is_dir() {
                     EntryType::Directory
                 } else if meta.is_file() {
                     EntryType::File
-                } else if meta.is_symlink() {
-                    EntryType::Symlink
                 } else {
                     EntryType::Unknown
                 }
@@ -149,29 +344,29 @@ impl Browser {
                 EntryType::Unknown
             };
 
+            let target_is_dir = is_link && metadata.as_ref().is_ok_and(|m| m.is_dir());
+            let modified = metadata.as_ref().ok().and_then(|m| m.modified().ok());
+            let permissions = metadata.as_ref().ok().map(permissions_string);
             let size = metadata.ok().map(|m| m.len());
             let extension = path
                 .extension()
                 .map(|e| e.to_string_lossy().to_string().to_lowercase());
 
-            self.entries.push(Entry {
+            entries.push(Entry {
                 name,
                 path,
                 entry_type,
                 size,
                 extension,
+                modified,
+                permissions,
+                link_target,
+                target_is_dir,
+                depth,
             });
         }
 
-        // Sort entries
-        self.entries.sort();
-
-        // Reset selection if needed
-        if self.selected >= self.entries.len() && !self.entries.is_empty() {
-            self.selected = self.entries.len() - 1;
-        }
-
-        Ok(())
+        Ok(entries)
     }
 
     /// Navigate into a directory or return the selected file path
@@ -182,7 +377,25 @@ impl Browser {
 
         let entry = &self.entries[self.selected];
 
-        if entry.is_directory() {
+        if entry.is_symlink() && !entry.path.exists() {
+            let target = entry
+                .link_target
+                .as_ref()
+                .map(|t| t.display().to_string())
+                .unwrap_or_default();
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Broken symlink: {} -> {}", entry.name, target),
+            ));
+        }
+
+        // Symlinks to directories are entered like directories
+        let is_dir = entry.is_dir_like();
+
+        if is_dir && self.tree_view && entry.name != ".." {
+            self.toggle_expand()?;
+            Ok(None)
+        } else if is_dir {
             self.current_dir = entry.path.clone();
             self.selected = 0;
             self.scroll_offset = 0;
@@ -208,6 +421,63 @@ impl Browser {
         Ok(())
     }
 
+    /// Expand or collapse the selected directory in tree view
+    pub fn toggle_expand(&mut self) -> io::Result<()> {
+        let Some(entry) = self.selected_entry() else {
+            return Ok(());
+        };
+        let path = entry.path.clone();
+
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path.clone());
+        }
+        self.refresh_keeping(&path)
+    }
+
+    /// Collapse the selected directory, select its parent, or go up a level
+    pub fn collapse_or_go_up(&mut self) -> io::Result<()> {
+        if !self.tree_view {
+            return self.go_up();
+        }
+
+        let Some(entry) = self.selected_entry() else {
+            return self.go_up();
+        };
+
+        if self.expanded.contains(&entry.path) {
+            let path = entry.path.clone();
+            self.expanded.remove(&path);
+            self.refresh_keeping(&path)
+        } else if entry.depth > 0 {
+            // Jump to the enclosing directory's row
+            let depth = entry.depth;
+            if let Some(pos) = self.entries[..self.selected].iter().rposition(|e| e.depth < depth) {
+                self.selected = pos;
+            }
+            Ok(())
+        } else {
+            self.go_up()
+        }
+    }
+
+    /// Switch between flat navigation and tree view
+    pub fn toggle_tree_view(&mut self) -> io::Result<()> {
+        self.tree_view = !self.tree_view;
+        match self.selected_entry().map(|e| e.path.clone()) {
+            Some(path) => self.refresh_keeping(&path),
+            None => self.refresh(),
+        }
+    }
+
+    /// Refresh the listing and reselect the entry with the given path
+    fn refresh_keeping(&mut self, path: &Path) -> io::Result<()> {
+        self.refresh()?;
+        if let Some(pos) = self.entries.iter().position(|e| e.path == path) {
+            self.selected = pos;
+        }
+        Ok(())
+    }
+
     /// Move selection up
     pub fn move_up(&mut self) {
         if self.selected > 0 {
@@ -253,6 +523,64 @@ impl Browser {
         self.refresh()
     }
 
+    /// Sort entries according to the current sort settings, keeping `..` on top
+    pub fn sort_entries(&mut self) {
+        let start = usize::from(self.entries.first().is_some_and(|e| e.name == ".."));
+        let (mode, reverse, dirs_first) = (self.sort_mode, self.sort_reverse, self.dirs_first);
+
+        let listing = self.entries.split_off(start);
+        self.entries.extend(sort_tree(listing, &|a, b| {
+            if dirs_first && a.is_dir_like() != b.is_dir_like() {
+                return if a.is_dir_like() {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                };
+            }
+            let ord = compare_entries(a, b, mode);
+            if reverse { ord.reverse() } else { ord }
+        }));
+    }
+
+    /// Cycle to the next sort mode
+    pub fn cycle_sort(&mut self) {
+        self.sort_mode = self.sort_mode.next();
+        self.resort();
+    }
+
+    /// Toggle reverse sort order
+    pub fn toggle_sort_reverse(&mut self) {
+        self.sort_reverse = !self.sort_reverse;
+        self.resort();
+    }
+
+    /// Toggle listing directories before files
+    pub fn toggle_dirs_first(&mut self) {
+        self.dirs_first = !self.dirs_first;
+        self.resort();
+    }
+
+    /// Re-sort the listing while keeping the same entry selected
+    fn resort(&mut self) {
+        let selected_path = self.selected_entry().map(|e| e.path.clone());
+        self.sort_entries();
+        if let Some(path) = selected_path
+            && let Some(pos) = self.entries.iter().position(|e| e.path == path)
+        {
+            self.selected = pos;
+        }
+    }
+
+    /// Describe the current sort settings for the status line
+    pub fn sort_description(&self) -> String {
+        format!(
+            "sort: {}{}{}",
+            self.sort_mode.label(),
+            if self.sort_reverse { " (rev)" } else { "" },
+            if self.dirs_first { ", dirs first" } else { "" }
+        )
+    }
+
     /// Get the currently selected entry
     pub fn selected_entry(&self) -> Option<&Entry> {
         self.entries.get(self.selected)
@@ -290,6 +618,11 @@ mod tests {
             entry_type: EntryType::Directory,
             size: None,
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert!(entry.is_directory());
         assert!(!entry.is_file());
@@ -303,6 +636,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(1024),
             extension: Some("txt".to_string()),
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert!(entry.is_file());
         assert!(!entry.is_directory());
@@ -316,6 +654,11 @@ mod tests {
             entry_type: EntryType::Directory,
             size: None,
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert_eq!(entry.display_name(), "folder/");
     }
@@ -328,6 +671,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(100),
             extension: Some("txt".to_string()),
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert_eq!(entry.display_name(), "file.txt");
     }
@@ -340,6 +688,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(500),
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert_eq!(entry.size_string(), "500 B");
     }
@@ -352,6 +705,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(2048),
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert_eq!(entry.size_string(), "2.0 KB");
     }
@@ -364,6 +722,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(5 * 1024 * 1024),
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert_eq!(entry.size_string(), "5.0 MB");
     }
@@ -376,6 +739,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(2 * 1024 * 1024 * 1024),
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert_eq!(entry.size_string(), "2.0 GB");
     }
@@ -388,10 +756,47 @@ mod tests {
             entry_type: EntryType::Directory,
             size: None,
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         assert_eq!(entry.size_string(), "");
     }
 
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13");
+    }
+
+    #[test]
+    fn test_format_mode() {
+        assert_eq!(format_mode(0o755), "rwxr-xr-x");
+        assert_eq!(format_mode(0o644), "rw-r--r--");
+        assert_eq!(format_mode(0o100600), "rw-------");
+        assert_eq!(format_mode(0), "---------");
+    }
+
+    #[test]
+    fn test_entry_modified_string_none() {
+        let entry = Entry {
+            name: "..".to_string(),
+            path: PathBuf::from("/"),
+            entry_type: EntryType::Directory,
+            size: None,
+            extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
+        };
+        assert_eq!(entry.modified_string(), "");
+    }
+
     #[test]
     fn test_entry_ordering_directories_first() {
         let dir = Entry {
@@ -400,6 +805,11 @@ mod tests {
             entry_type: EntryType::Directory,
             size: None,
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         let file = Entry {
             name: "aaa.txt".to_string(),
@@ -407,6 +817,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(100),
             extension: Some("txt".to_string()),
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
 
         assert!(dir < file); // Directories come first regardless of name
@@ -420,6 +835,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(100),
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         let entry_b = Entry {
             name: "banana".to_string(),
@@ -427,6 +847,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(100),
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
 
         assert!(entry_a < entry_b);
@@ -440,6 +865,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(100),
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
         let entry_lower = Entry {
             name: "banana".to_string(),
@@ -447,6 +877,11 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(100),
             extension: None,
+            modified: None,
+            permissions: None,
+            link_target: None,
+            target_is_dir: false,
+            depth: 0,
         };
 
         assert!(entry_upper < entry_lower);
@@ -605,6 +1040,180 @@ mod tests {
         fs::remove_dir_all(&temp_dir).ok();
     }
 
+    #[test]
+    fn test_sort_mode_cycle() {
+        assert_eq!(SortMode::Name.next(), SortMode::Size);
+        assert_eq!(SortMode::Size.next(), SortMode::Modified);
+        assert_eq!(SortMode::Modified.next(), SortMode::Extension);
+        assert_eq!(SortMode::Extension.next(), SortMode::Name);
+    }
+
+    #[test]
+    fn test_browser_sort_modes() {
+        let temp_dir = std::env::temp_dir().join("zedit_browser_sort_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        fs::create_dir(temp_dir.join("zdir")).unwrap();
+        fs::write(temp_dir.join("a.txt"), "three").unwrap();
+        fs::write(temp_dir.join("b.rs"), "1").unwrap();
+        fs::write(temp_dir.join("c.md"), "twenty-two bytes long!").unwrap();
+
+        let mut browser = Browser::new(&temp_dir).unwrap();
+        let names = |b: &Browser| -> Vec<String> {
+            b.entries.iter().filter(|e| e.name != "..").map(|e| e.name.clone()).collect()
+        };
+
+        // Default: directories first, then by name
+        assert_eq!(names(&browser), ["zdir", "a.txt", "b.rs", "c.md"]);
+        assert_eq!(browser.entries[0].name, "..");
+
+        browser.cycle_sort();
+        assert_eq!(browser.sort_mode, SortMode::Size);
+        assert_eq!(names(&browser), ["zdir", "b.rs", "a.txt", "c.md"]);
+
+        browser.toggle_sort_reverse();
+        assert_eq!(names(&browser), ["zdir", "c.md", "a.txt", "b.rs"]);
+        assert_eq!(browser.entries[0].name, "..");
+
+        browser.sort_mode = SortMode::Extension;
+        browser.sort_reverse = false;
+        browser.toggle_dirs_first();
+        assert!(!browser.dirs_first);
+        assert_eq!(names(&browser), ["zdir", "c.md", "b.rs", "a.txt"]);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_browser_sort_keeps_selection() {
+        let temp_dir = std::env::temp_dir().join("zedit_browser_sort_select_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        fs::write(temp_dir.join("a.txt"), "longer content").unwrap();
+        fs::write(temp_dir.join("b.txt"), "x").unwrap();
+
+        let mut browser = Browser::new(&temp_dir).unwrap();
+        browser.selected = browser.entries.iter().position(|e| e.name == "a.txt").unwrap();
+
+        browser.cycle_sort();
+        assert_eq!(browser.selected_entry().unwrap().name, "a.txt");
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_entry_display_name_symlink() {
+        let entry = Entry {
+            name: "link".to_string(),
+            path: PathBuf::from("/link"),
+            entry_type: EntryType::Symlink,
+            size: None,
+            extension: None,
+            modified: None,
+            permissions: None,
+            link_target: Some(PathBuf::from("target/file")),
+            target_is_dir: false,
+            depth: 0,
+        };
+        assert!(entry.is_symlink());
+        assert_eq!(entry.display_name(), "link -> target/file");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_browser_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = std::env::temp_dir().join("zedit_browser_symlink_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("real_dir")).unwrap();
+        fs::write(temp_dir.join("real_dir").join("inner.txt"), "x").unwrap();
+        symlink(temp_dir.join("real_dir"), temp_dir.join("dir_link")).unwrap();
+        symlink(temp_dir.join("missing"), temp_dir.join("broken_link")).unwrap();
+        fs::write(temp_dir.join("a.txt"), "x").unwrap();
+
+        let mut browser = Browser::new(&temp_dir).unwrap();
+
+        let dir_link = browser.entries.iter().find(|e| e.name == "dir_link").unwrap();
+        assert_eq!(dir_link.entry_type, EntryType::Symlink);
+        assert_eq!(dir_link.link_target, Some(temp_dir.join("real_dir")));
+        assert!(dir_link.is_dir_like());
+        assert_eq!(dir_link.display_name(), format!("dir_link/ -> {}", temp_dir.join("real_dir").display()));
+
+        let broken = browser.entries.iter().find(|e| e.name == "broken_link").unwrap();
+        assert_eq!(broken.entry_type, EntryType::Symlink);
+        assert!(!broken.is_dir_like());
+
+        // A link to a directory is listed with the directories
+        let names: Vec<&str> = browser.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["..", "dir_link", "real_dir", "a.txt", "broken_link"]);
+
+        // and expands in tree view
+        browser.tree_view = true;
+        browser.selected = 1;
+        browser.enter().unwrap();
+        assert_eq!(browser.entries[2].name, "inner.txt");
+        assert_eq!(browser.entries[2].depth, 1);
+        browser.tree_view = false;
+
+        // Entering a broken link reports an error instead of navigating
+        browser.selected = browser.entries.iter().position(|e| e.name == "broken_link").unwrap();
+        let err = browser.enter().unwrap_err();
+        assert!(err.to_string().contains("Broken symlink"));
+
+        // Entering a link to a directory navigates into it
+        browser.selected = browser.entries.iter().position(|e| e.name == "dir_link").unwrap();
+        assert_eq!(browser.enter().unwrap(), None);
+        assert!(browser.current_dir.ends_with("dir_link"));
+        assert!(browser.entries.iter().any(|e| e.name == "inner.txt"));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_browser_tree_view() {
+        let temp_dir = std::env::temp_dir().join("zedit_browser_tree_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src").join("nested")).unwrap();
+        fs::write(temp_dir.join("src").join("main.rs"), "fn main() {}").unwrap();
+        fs::write(temp_dir.join("README.md"), "# readme").unwrap();
+
+        let mut browser = Browser::new(&temp_dir).unwrap();
+        browser.toggle_tree_view().unwrap();
+        assert!(browser.tree_view);
+
+        // Expanding a directory inlines its children, indented one level
+        browser.selected = browser.entries.iter().position(|e| e.name == "src").unwrap();
+        assert_eq!(browser.enter().unwrap(), None);
+        assert_eq!(browser.current_dir, temp_dir.canonicalize().unwrap());
+
+        let names: Vec<(&str, usize)> = browser
+            .entries
+            .iter()
+            .skip(1)
+            .map(|e| (e.name.as_str(), e.depth))
+            .collect();
+        assert_eq!(names, [("src", 0), ("nested", 1), ("main.rs", 1), ("README.md", 0)]);
+
+        // Sorting keeps children under their parent
+        browser.toggle_sort_reverse();
+        let names: Vec<&str> = browser.entries.iter().skip(1).map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["src", "nested", "main.rs", "README.md"]);
+        browser.toggle_sort_reverse();
+
+        // From a child, collapsing first selects the parent, then collapses it
+        browser.selected = browser.entries.iter().position(|e| e.name == "main.rs").unwrap();
+        browser.collapse_or_go_up().unwrap();
+        assert_eq!(browser.selected_entry().unwrap().name, "src");
+        browser.collapse_or_go_up().unwrap();
+        assert!(!browser.entries.iter().any(|e| e.name == "main.rs"));
+        assert_eq!(browser.selected_entry().unwrap().name, "src");
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
     #[test]
     fn test_entry_types() {
         assert_eq!(EntryType::Directory, EntryType::Directory);
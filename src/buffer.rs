@@ -1,9 +1,15 @@
+use std::collections::HashMap;
 use std::fs;
-use std::io::{self, BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::regex::Regex;
+use crate::textobject::Pos;
+use crate::undo::UndoHistory;
 
 /// A single line in the buffer
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Line {
     pub chars: Vec<char>,
 }
@@ -57,6 +63,140 @@ impl Line {
     pub fn append(&mut self, other: &Line) {
         self.chars.extend(other.chars.iter().cloned());
     }
+
+    /// Screen column where the char at `col` starts, with tabs expanded to `tab_stop`.
+    /// Columns past the end count one cell each.
+    pub fn visual_col(&self, col: usize, tab_stop: usize) -> usize {
+        let mut visual = 0;
+        for &c in self.chars.iter().take(col) {
+            visual += display_width(c, visual, tab_stop);
+        }
+        visual + col.saturating_sub(self.chars.len())
+    }
+}
+
+/// East Asian wide and fullwidth ranges: CJK ideographs, kana, Hangul, fullwidth forms
+/// and emoji, which terminals draw two cells wide
+const WIDE_RANGES: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xA960, 0xA97F),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE10, 0xFE19),
+    (0xFE30, 0xFE6F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+/// Whether a char takes two cells on screen
+fn is_wide(c: char) -> bool {
+    let c = c as u32;
+    WIDE_RANGES
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                std::cmp::Ordering::Less
+            } else if start > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Cells a char takes on screen when it starts at visual column `col`. A tab reaches
+/// the next multiple of `tab_stop`, and wide chars take two.
+pub fn display_width(c: char, col: usize, tab_stop: usize) -> usize {
+    if c == '\t' {
+        tab_stop - col % tab_stop
+    } else if is_wide(c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Replace `path` by writing a temporary file beside it and renaming it into place, so
+/// a failed write leaves the original untouched. The original's permissions are kept.
+fn write_atomically(path: &Path, write: impl FnOnce(&mut fs::File) -> io::Result<()>) -> io::Result<()> {
+    // Replace the file a symlink points to, not the link itself
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let original = fs::metadata(&target).ok();
+    if original.as_ref().is_some_and(|m| m.permissions().readonly()) {
+        // Renaming would succeed regardless, so refuse like a direct write would
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "File is read-only"));
+    }
+
+    let name = target
+        .file_name()
+        .ok_or_else(|| io::Error::other("Invalid file path"))?
+        .to_string_lossy();
+    let temp = target.with_file_name(format!(".{}.{}.zedit.tmp", name, process::id()));
+
+    let result = (|| {
+        let mut file = fs::File::create(&temp)?;
+        write(&mut file)?;
+        file.sync_all()?;
+        if let Some(metadata) = &original {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        fs::rename(&temp, &target)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Options for `Buffer::sort_lines`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SortOptions {
+    pub reverse: bool,
+    pub unique: bool,
+    pub ignore_case: bool,
+}
+
+/// Line terminator written between lines when saving
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+
+    /// Vim's `fileformat` name
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "unix",
+            LineEnding::Crlf => "dos",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unix" => Some(LineEnding::Lf),
+            "dos" => Some(LineEnding::Crlf),
+            _ => None,
+        }
+    }
 }
 
 /// Text buffer containing all lines
@@ -65,6 +205,21 @@ pub struct Buffer {
     pub path: Option<PathBuf>,
     pub modified: bool,
     pub readonly: bool,
+    /// Line ending used when saving; the one most lines had when loaded
+    pub line_ending: LineEnding,
+    /// End the file with a line ending, as it did when loaded
+    pub final_newline: bool,
+    /// Always end the file with a line ending when saving (`:set fixeol`)
+    pub fix_eol: bool,
+    /// Leave trailing spaces and tabs out of the saved file (`:set trimtrailing`)
+    pub trim_trailing: bool,
+    /// Positions set with `m<letter>`, kept on their lines as lines above change
+    pub marks: HashMap<char, Pos>,
+    history: UndoHistory,
+    /// Bumped by every edit, so undo only compares the lines when something changed
+    edits: u64,
+    /// Value of `edits` at the last undo commit
+    committed_edits: Option<u64>,
 }
 
 impl Buffer {
@@ -74,58 +229,218 @@ impl Buffer {
             path: None,
             modified: false,
             readonly: false,
+            line_ending: LineEnding::Lf,
+            final_newline: true,
+            fix_eol: false,
+            trim_trailing: false,
+            marks: HashMap::new(),
+            history: UndoHistory::new(),
+            edits: 0,
+            committed_edits: None,
         }
     }
 
     pub fn from_file(path: &PathBuf) -> io::Result<Self> {
         let file = fs::File::open(path)?;
-        let reader = BufReader::new(file);
-        let mut lines = Vec::new();
+        let readonly = fs::metadata(path)
+            .map(|m| m.permissions().readonly())
+            .unwrap_or(false);
+
+        Ok(Buffer {
+            path: Some(path.clone()),
+            readonly,
+            ..Buffer::from_reader(BufReader::new(file))?
+        })
+    }
 
-        for line_result in reader.lines() {
-            let line = line_result?;
-            lines.push(Line::from_str(&line));
+    /// Read a buffer with no path, such as piped input
+    pub fn from_reader(mut reader: impl BufRead) -> io::Result<Self> {
+        let mut lines = Vec::new();
+        let mut terminated = 0;
+        let mut crlf = 0;
+        let mut final_newline = false;
+
+        let mut text = String::new();
+        while reader.read_line(&mut text)? > 0 {
+            let mut line = text.as_str();
+            final_newline = line.ends_with('\n');
+            if let Some(rest) = line.strip_suffix('\n') {
+                terminated += 1;
+                line = rest;
+                if let Some(rest) = line.strip_suffix('\r') {
+                    crlf += 1;
+                    line = rest;
+                }
+            }
+            lines.push(Line::from_str(line));
+            text.clear();
         }
+        let line_ending = if crlf * 2 > terminated {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        };
 
         if lines.is_empty() {
             lines.push(Line::new());
         }
 
-        let readonly = fs::metadata(path)
-            .map(|m| m.permissions().readonly())
-            .unwrap_or(false);
-
         Ok(Buffer {
             lines,
-            path: Some(path.clone()),
+            path: None,
             modified: false,
-            readonly,
+            readonly: false,
+            line_ending,
+            final_newline,
+            fix_eol: false,
+            trim_trailing: false,
+            marks: HashMap::new(),
+            history: UndoHistory::new(),
+            edits: 0,
+            committed_edits: None,
         })
     }
 
     pub fn save(&mut self) -> io::Result<()> {
         if let Some(path) = &self.path {
-            let mut file = fs::File::create(path)?;
-            for (i, line) in self.lines.iter().enumerate() {
-                if i > 0 {
-                    writeln!(file)?;
-                }
-                write!(file, "{}", line.to_string())?;
-            }
-            // Add final newline
-            writeln!(file)?;
+            write_atomically(path, |file| self.write_lines(BufWriter::new(file), self.trim_trailing))?;
             self.modified = false;
+            self.history.mark_saved(&self.lines);
+            // The file now holds everything the swap file did
+            self.remove_swap();
             Ok(())
         } else {
             Err(io::Error::new(io::ErrorKind::Other, "No file path set"))
         }
     }
 
+    /// Write the lines to `out`; with `trim`, trailing spaces and tabs are left out of
+    /// what is written but stay in the buffer, where they may be mid-edit
+    fn write_lines(&self, mut out: impl Write, trim: bool) -> io::Result<()> {
+        let newline = self.line_ending.as_str();
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                write!(out, "{}", newline)?;
+            }
+            let text = line.to_string();
+            write!(out, "{}", if trim { text.trim_end_matches([' ', '\t']) } else { &text })?;
+        }
+        if self.final_newline || self.fix_eol {
+            write!(out, "{}", newline)?;
+        }
+        out.flush()
+    }
+
+    /// Sidecar file holding unsaved contents for crash recovery (`.name.zedit.swp`
+    /// next to the file)
+    pub fn swap_path(&self) -> Option<PathBuf> {
+        let path = self.path.as_ref()?;
+        let name = path.file_name()?.to_string_lossy();
+        Some(path.with_file_name(format!(".{}.zedit.swp", name)))
+    }
+
+    /// Write the current contents to the swap file
+    pub fn write_swap(&self) -> io::Result<()> {
+        let Some(swap) = self.swap_path() else {
+            return Ok(());
+        };
+        self.write_lines(BufWriter::new(fs::File::create(swap)?), false)
+    }
+
+    /// Delete the swap file if there is one
+    pub fn remove_swap(&self) {
+        if let Some(swap) = self.swap_path() {
+            let _ = fs::remove_file(swap);
+        }
+    }
+
+    /// Replace the contents with those of the swap file, leaving the buffer modified
+    pub fn recover_swap(&mut self) -> io::Result<()> {
+        let swap = self
+            .swap_path()
+            .ok_or_else(|| io::Error::other("No file path set"))?;
+        let recovered = Buffer::from_file(&swap)?;
+        self.lines = recovered.lines;
+        self.mark_changed();
+        Ok(())
+    }
+
     pub fn save_as(&mut self, path: PathBuf) -> io::Result<()> {
         self.path = Some(path);
         self.save()
     }
 
+    /// Save even if the file is read-only, creating missing parent directories
+    pub fn force_save(&mut self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Err(io::Error::other("No file path set"));
+        };
+
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        if let Ok(metadata) = fs::metadata(path) {
+            let mut permissions = metadata.permissions();
+            if permissions.readonly() {
+                // Only the owner gets write access; group and others keep their mode
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    permissions.set_mode(permissions.mode() | 0o200);
+                }
+                #[cfg(not(unix))]
+                permissions.set_readonly(false);
+                fs::set_permissions(path, permissions)?;
+            }
+        }
+
+        self.save()?;
+        self.readonly = false;
+        Ok(())
+    }
+
+    /// Flag the lines as edited; code that changes `lines` directly must call this
+    /// for undo to see the change
+    pub fn mark_changed(&mut self) {
+        self.modified = true;
+        self.edits += 1;
+    }
+
+    /// End the current undo step: edits since the last call are undone together
+    pub fn commit_undo(&mut self, cursor_before: Pos, cursor_after: Pos) {
+        // Most keys are motions; don't compare the whole buffer for them
+        if self.committed_edits == Some(self.edits) {
+            return;
+        }
+        self.committed_edits = Some(self.edits);
+        if let Some((start, removed, inserted)) = self.history.commit(&self.lines, cursor_before, cursor_after) {
+            // Marks on deleted lines go away; those below move with their lines
+            self.marks.retain(|_, (row, _)| *row < start + inserted || *row >= start + removed);
+            for (row, _) in self.marks.values_mut() {
+                if *row >= start + removed {
+                    *row = *row + inserted - removed;
+                }
+            }
+        }
+    }
+
+    /// Undo the last step, returning where to put the cursor
+    pub fn undo(&mut self) -> Option<Pos> {
+        let cursor = self.history.undo(&mut self.lines)?;
+        self.modified = !self.history.at_save_point();
+        Some(cursor)
+    }
+
+    /// Redo the last undone step, returning where to put the cursor
+    pub fn redo(&mut self) -> Option<Pos> {
+        let cursor = self.history.redo(&mut self.lines)?;
+        self.modified = !self.history.at_save_point();
+        Some(cursor)
+    }
+
     pub fn line_count(&self) -> usize {
         self.lines.len()
     }
@@ -142,7 +457,7 @@ impl Buffer {
     pub fn insert_char(&mut self, row: usize, col: usize, c: char) {
         if row < self.lines.len() {
             self.lines[row].insert(col, c);
-            self.modified = true;
+            self.mark_changed();
         }
     }
 
@@ -151,7 +466,7 @@ impl Buffer {
         if row < self.lines.len() {
             let result = self.lines[row].delete(col);
             if result.is_some() {
-                self.modified = true;
+                self.mark_changed();
             }
             result
         } else {
@@ -159,12 +474,30 @@ impl Buffer {
         }
     }
 
+    /// Delete up to `count` characters starting at the given position, stopping at the
+    /// end of the line. Returns the deleted characters.
+    pub fn delete_chars(&mut self, row: usize, col: usize, count: usize) -> Vec<char> {
+        let Some(line) = self.lines.get_mut(row) else {
+            return Vec::new();
+        };
+        if col >= line.len() {
+            return Vec::new();
+        }
+
+        let end = col.saturating_add(count).min(line.len());
+        let deleted: Vec<char> = line.chars.drain(col..end).collect();
+        if !deleted.is_empty() {
+            self.mark_changed();
+        }
+        deleted
+    }
+
     /// Insert a new line (split current line at position)
     pub fn insert_newline(&mut self, row: usize, col: usize) {
         if row < self.lines.len() {
             let new_line = self.lines[row].split_off(col);
             self.lines.insert(row + 1, new_line);
-            self.modified = true;
+            self.mark_changed();
         }
     }
 
@@ -173,15 +506,239 @@ impl Buffer {
         if row > 0 && row < self.lines.len() {
             let line = self.lines.remove(row);
             self.lines[row - 1].append(&line);
-            self.modified = true;
+            self.mark_changed();
         }
     }
 
+    /// Remove up to `count` whole lines starting at `row`, returning them. A buffer
+    /// always keeps at least one (possibly empty) line.
+    pub fn delete_lines(&mut self, row: usize, count: usize) -> Vec<Line> {
+        if row >= self.lines.len() || count == 0 {
+            return Vec::new();
+        }
+
+        let end = row.saturating_add(count).min(self.lines.len());
+        let removed: Vec<Line> = self.lines.drain(row..end).collect();
+        if self.lines.is_empty() {
+            self.lines.push(Line::new());
+        }
+        self.mark_changed();
+        removed
+    }
+
+    /// Insert `lines` as text at the given position, joining the first and last with the
+    /// surrounding text. Returns the position just after the inserted text.
+    pub fn insert_text(&mut self, row: usize, col: usize, lines: &[Line]) -> (usize, usize) {
+        if row >= self.lines.len() || lines.is_empty() {
+            return (row, col);
+        }
+
+        let col = col.min(self.lines[row].len());
+        let tail = self.lines[row].split_off(col);
+        self.lines[row].append(&lines[0]);
+        for (i, line) in lines[1..].iter().enumerate() {
+            self.lines.insert(row + 1 + i, line.clone());
+        }
+
+        let end_row = row + lines.len() - 1;
+        let end_col = if lines.len() == 1 {
+            col + lines[0].len()
+        } else {
+            lines[lines.len() - 1].len()
+        };
+        self.lines[end_row].append(&tail);
+        self.mark_changed();
+        (end_row, end_col)
+    }
+
     /// Insert an empty line at the given position
     pub fn insert_empty_line(&mut self, row: usize) {
         if row <= self.lines.len() {
             self.lines.insert(row, Line::new());
-            self.modified = true;
+            self.mark_changed();
+        }
+    }
+
+    /// Copy the text from `start` (inclusive) to `end` (exclusive), as (row, col) positions
+    pub fn text_in_range(&self, start: (usize, usize), end: (usize, usize)) -> Vec<Line> {
+        let slice = |row: usize, from: usize, to: usize| {
+            let chars = self.lines.get(row).map_or(&[][..], |l| &l.chars[..]);
+            let to = to.min(chars.len());
+            Line {
+                chars: chars[from.min(to)..to].to_vec(),
+            }
+        };
+
+        if start.0 == end.0 {
+            return vec![slice(start.0, start.1, end.1)];
+        }
+
+        let mut lines = vec![slice(start.0, start.1, usize::MAX)];
+        for row in start.0 + 1..end.0 {
+            if let Some(line) = self.lines.get(row) {
+                lines.push(line.clone());
+            }
+        }
+        lines.push(slice(end.0, 0, end.1));
+        lines
+    }
+
+    /// Delete the text from `start` (inclusive) to `end` (exclusive), returning it
+    pub fn delete_range(&mut self, start: (usize, usize), end: (usize, usize)) -> Vec<Line> {
+        if start >= end || start.0 >= self.lines.len() {
+            return vec![Line::new()];
+        }
+        let end = if end.0 >= self.lines.len() {
+            let last = self.lines.len() - 1;
+            (last, self.lines[last].len())
+        } else {
+            end
+        };
+
+        let removed = self.text_in_range(start, end);
+        let tail = self.lines[end.0].split_off(end.1);
+        self.lines[start.0].chars.truncate(start.1);
+        self.lines[start.0].append(&tail);
+        if end.0 > start.0 {
+            self.lines.drain(start.0 + 1..=end.0);
+        }
+        self.mark_changed();
+        removed
+    }
+
+    /// Sort lines in `start..end`, returning the number of lines removed as duplicates
+    pub fn sort_lines(&mut self, start: usize, end: usize, options: SortOptions) -> usize {
+        let end = end.min(self.lines.len());
+        if start >= end {
+            return 0;
+        }
+
+        let key = |line: &Line| {
+            let text = line.to_string();
+            if options.ignore_case {
+                text.to_lowercase()
+            } else {
+                text
+            }
+        };
+
+        let mut sorted: Vec<Line> = self.lines[start..end].to_vec();
+        sorted.sort_by_cached_key(|line| key(line));
+        if options.reverse {
+            sorted.reverse();
+        }
+        let before = sorted.len();
+        if options.unique {
+            sorted.dedup_by(|a, b| key(a) == key(b));
+        }
+        let removed = before - sorted.len();
+
+        self.lines.splice(start..end, sorted);
+        self.mark_changed();
+        removed
+    }
+
+    /// Delete every line containing `pattern` (or not containing it, if `invert`),
+    /// returning the number of lines removed
+    pub fn delete_lines_matching(&mut self, pattern: &str, invert: bool) -> usize {
+        let mut removed = 0;
+        for row in (0..self.lines.len()).rev() {
+            if self.lines[row].to_string().contains(pattern) != invert {
+                self.lines.remove(row);
+                removed += 1;
+            }
+        }
+
+        if self.lines.is_empty() {
+            self.lines.push(Line::new());
+        }
+        if removed > 0 {
+            self.mark_changed();
+        }
+        removed
+    }
+
+    /// Start positions (row, char column) of non-overlapping, non-empty matches
+    pub fn find_all(&self, pattern: &Regex) -> Vec<(usize, usize)> {
+        (0..self.lines.len())
+            .flat_map(|row| {
+                self.find_in_line(row, pattern)
+                    .into_iter()
+                    .map(move |(col, _)| (row, col))
+            })
+            .collect()
+    }
+
+    /// Start and end char columns of non-overlapping, non-empty matches on one line
+    pub fn find_in_line(&self, row: usize, pattern: &Regex) -> Vec<(usize, usize)> {
+        let Some(line) = self.lines.get(row) else {
+            return Vec::new();
+        };
+        pattern.find_iter(&line.chars)
+    }
+
+    /// Number of non-overlapping, non-empty matches in the buffer
+    pub fn count_matches(&self, pattern: &Regex) -> usize {
+        self.find_all(pattern).len()
+    }
+
+    /// Replace matches of `pattern` on every line, all of them if `global` or else the
+    /// first on each line, returning the number of replacements
+    pub fn replace_all(&mut self, pattern: &Regex, replacement: &str, global: bool) -> usize {
+        (0..self.lines.len())
+            .map(|row| self.replace_in_line(row, pattern, replacement, global))
+            .sum()
+    }
+
+    /// Replace the first match of `pattern` on a line, or every match if `global`,
+    /// returning the number of replacements
+    pub fn replace_in_line(&mut self, row: usize, pattern: &Regex, replacement: &str, global: bool) -> usize {
+        let Some(line) = self.lines.get_mut(row) else {
+            return 0;
+        };
+        let mut count = 0;
+        let mut from = 0;
+        while let Some(m) = pattern.find_at(&line.chars, from) {
+            let text = pattern.expand(replacement, &line.chars, &m);
+            from = m.start + text.len() + usize::from(m.start == m.end);
+            line.chars.splice(m.start..m.end, text);
+            count += 1;
+            if !global {
+                break;
+            }
+        }
+
+        if count > 0 {
+            self.mark_changed();
+        }
+        count
+    }
+
+    /// Shift `count` lines from `row` one `width` to the right, or to the left when
+    /// `outdent` is set. A tab in the indentation counts as `width` columns, and lines
+    /// indented with tabs keep using them. Empty lines are left alone.
+    pub fn shift_lines(&mut self, row: usize, count: usize, width: usize, outdent: bool) {
+        let end = (row + count).min(self.lines.len());
+        let mut shifted = false;
+        for line in &mut self.lines[row.min(end)..end] {
+            let indent_len = line.chars.iter().take_while(|c| **c == ' ' || **c == '\t').count();
+            if line.is_empty() || (outdent && indent_len == 0) {
+                continue;
+            }
+            let indent = &line.chars[..indent_len];
+            let columns: usize = indent.iter().map(|c| if *c == '\t' { width } else { 1 }).sum();
+            let columns = if outdent { columns.saturating_sub(width) } else { columns + width };
+            let new_indent: Vec<char> = if indent.contains(&'\t') {
+                let tabs = std::iter::repeat_n('\t', columns / width);
+                tabs.chain(std::iter::repeat_n(' ', columns % width)).collect()
+            } else {
+                vec![' '; columns]
+            };
+            line.chars.splice(..indent_len, new_indent);
+            shifted = true;
+        }
+        if shifted {
+            self.mark_changed();
         }
     }
 
@@ -207,6 +764,113 @@ mod tests {
     use super::*;
     use std::io::Write;
 
+    #[test]
+    fn test_swap_file_round_trip() {
+        let temp_dir = std::env::temp_dir().join("zedit_swap_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("notes.txt");
+        fs::write(&path, "saved\n").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        let swap = buffer.swap_path().unwrap();
+        assert_eq!(swap, temp_dir.join(".notes.txt.zedit.swp"));
+
+        buffer.lines = vec![Line::from_str("unsaved"), Line::from_str("work")];
+        buffer.write_swap().unwrap();
+        assert!(swap.exists());
+
+        let mut reopened = Buffer::from_file(&path).unwrap();
+        reopened.recover_swap().unwrap();
+        assert_eq!(buffer_lines(&reopened), ["unsaved", "work"]);
+        assert!(reopened.modified);
+
+        // Saving makes the swap file redundant
+        reopened.save().unwrap();
+        assert!(!swap.exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "unsaved\nwork\n");
+
+        assert!(Buffer::new().swap_path().is_none());
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_line_endings_preserved() {
+        let temp_dir = std::env::temp_dir().join("zedit_line_ending_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("windows.txt");
+        fs::write(&path, "one\r\ntwo\r\nthree\n").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        assert_eq!(buffer.line_ending, LineEnding::Crlf);
+        assert_eq!(buffer_lines(&buffer), ["one", "two", "three"]);
+        buffer.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\r\ntwo\r\nthree\r\n");
+
+        buffer.line_ending = LineEnding::Lf;
+        buffer.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\nthree\n");
+        assert_eq!(Buffer::from_file(&path).unwrap().line_ending, LineEnding::Lf);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_missing_final_newline_round_trips() {
+        let temp_dir = std::env::temp_dir().join("zedit_final_newline_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("no_eol.txt");
+        fs::write(&path, "first\nlast").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        assert!(!buffer.final_newline);
+        buffer.save().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first\nlast");
+
+        buffer.fix_eol = true;
+        buffer.save().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first\nlast\n");
+        assert!(Buffer::from_file(&path).unwrap().final_newline);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_trim_trailing_on_save() {
+        let temp_dir = std::env::temp_dir().join("zedit_trim_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("spaces.txt");
+        fs::write(&path, "a  \n\t \n  b\t\n").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        buffer.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a  \n\t \n  b\t\n");
+
+        buffer.trim_trailing = true;
+        buffer.modified = true;
+        buffer.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\n\n  b\n");
+        assert_eq!(buffer_lines(&buffer), ["a  ", "\t ", "  b\t"]);
+        assert!(!buffer.modified);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let buffer = Buffer::from_reader(io::Cursor::new("a.rs:1: x\r\nb.rs:2: y\r\n")).unwrap();
+        assert_eq!(buffer_lines(&buffer), ["a.rs:1: x", "b.rs:2: y"]);
+        assert_eq!(buffer.line_ending, LineEnding::Crlf);
+        assert!(buffer.path.is_none());
+        assert!(!buffer.modified);
+
+        let buffer = Buffer::from_reader(io::Cursor::new("")).unwrap();
+        assert_eq!(buffer_lines(&buffer), [""]);
+    }
+
     // Line tests
     #[test]
     fn test_line_new() {
@@ -350,6 +1014,28 @@ mod tests {
         assert!(buffer.modified);
     }
 
+    #[test]
+    fn test_buffer_delete_chars() {
+        let mut buffer = buffer_from(&["hello"]);
+        assert_eq!(buffer.delete_chars(0, 1, 3), ['e', 'l', 'l']);
+        assert_eq!(buffer_lines(&buffer), ["ho"]);
+        assert!(buffer.modified);
+
+        // Stops at the end of the line
+        assert_eq!(buffer.delete_chars(0, 1, 10), ['o']);
+        assert_eq!(buffer_lines(&buffer), ["h"]);
+    }
+
+    #[test]
+    fn test_buffer_delete_chars_empty_and_past_end() {
+        let mut buffer = buffer_from(&["", "ab"]);
+        assert!(buffer.delete_chars(0, 0, 1).is_empty());
+        assert!(buffer.delete_chars(1, 2, 1).is_empty());
+        assert!(buffer.delete_chars(5, 0, 1).is_empty());
+        assert!(!buffer.modified);
+        assert_eq!(buffer_lines(&buffer), ["", "ab"]);
+    }
+
     #[test]
     fn test_buffer_insert_newline() {
         let mut buffer = Buffer::new();
@@ -385,6 +1071,43 @@ mod tests {
         assert_eq!(buffer.line_count(), 2);
     }
 
+    #[test]
+    fn test_buffer_delete_lines() {
+        let mut buffer = buffer_from(&["a", "b", "c", "d"]);
+        let removed = buffer.delete_lines(1, 2);
+        assert_eq!(removed.iter().map(|l| l.to_string()).collect::<Vec<_>>(), ["b", "c"]);
+        assert_eq!(buffer_lines(&buffer), ["a", "d"]);
+        assert!(buffer.modified);
+
+        // Count past the end stops at the last line
+        assert_eq!(buffer.delete_lines(1, 5).len(), 1);
+        assert_eq!(buffer_lines(&buffer), ["a"]);
+    }
+
+    #[test]
+    fn test_buffer_delete_lines_single_line() {
+        let mut buffer = buffer_from(&["only"]);
+        let removed = buffer.delete_lines(0, 1);
+        assert_eq!(removed[0].to_string(), "only");
+        assert_eq!(buffer.line_count(), 1);
+        assert!(buffer.lines[0].is_empty());
+
+        assert!(buffer.delete_lines(3, 1).is_empty());
+    }
+
+    #[test]
+    fn test_buffer_insert_text() {
+        let mut buffer = buffer_from(&["hello world"]);
+        let end = buffer.insert_text(0, 5, &[Line::from_str(",")]);
+        assert_eq!(end, (0, 6));
+        assert_eq!(buffer_lines(&buffer), ["hello, world"]);
+
+        let end = buffer.insert_text(0, 6, &[Line::from_str(" big"), Line::from_str("new")]);
+        assert_eq!(end, (1, 3));
+        assert_eq!(buffer_lines(&buffer), ["hello, big", "new world"]);
+        assert!(buffer.modified);
+    }
+
     #[test]
     fn test_buffer_insert_empty_line() {
         let mut buffer = Buffer::new();
@@ -395,6 +1118,113 @@ mod tests {
         assert!(buffer.modified);
     }
 
+    fn buffer_from(lines: &[&str]) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.lines = lines.iter().map(|l| Line::from_str(l)).collect();
+        buffer
+    }
+
+    fn buffer_lines(buffer: &Buffer) -> Vec<String> {
+        buffer.lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_buffer_sort_lines() {
+        let mut buffer = buffer_from(&["pear", "Apple", "banana", "apple"]);
+        let removed = buffer.sort_lines(0, 4, SortOptions::default());
+
+        assert_eq!(removed, 0);
+        assert_eq!(buffer_lines(&buffer), ["Apple", "apple", "banana", "pear"]);
+        assert!(buffer.modified);
+    }
+
+    #[test]
+    fn test_buffer_sort_lines_options() {
+        let mut buffer = buffer_from(&["pear", "Apple", "banana", "apple"]);
+        let options = SortOptions { reverse: true, ..Default::default() };
+        buffer.sort_lines(0, 4, options);
+        assert_eq!(buffer_lines(&buffer), ["pear", "banana", "apple", "Apple"]);
+
+        let mut buffer = buffer_from(&["b", "a", "b", "B"]);
+        let options = SortOptions { unique: true, ..Default::default() };
+        assert_eq!(buffer.sort_lines(0, 4, options), 1);
+        assert_eq!(buffer_lines(&buffer), ["B", "a", "b"]);
+
+        let mut buffer = buffer_from(&["b", "a", "B"]);
+        let options = SortOptions { unique: true, ignore_case: true, ..Default::default() };
+        assert_eq!(buffer.sort_lines(0, 3, options), 1);
+        assert_eq!(buffer_lines(&buffer), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_buffer_sort_lines_range() {
+        let mut buffer = buffer_from(&["z", "c", "b", "a"]);
+        buffer.sort_lines(1, 3, SortOptions::default());
+        assert_eq!(buffer_lines(&buffer), ["z", "b", "c", "a"]);
+
+        assert_eq!(buffer.sort_lines(3, 10, SortOptions::default()), 0);
+        assert_eq!(buffer.sort_lines(5, 10, SortOptions::default()), 0);
+    }
+
+    #[test]
+    fn test_buffer_text_in_range() {
+        let buffer = buffer_from(&["hello world", "middle", "last line"]);
+
+        let text = buffer.text_in_range((0, 6), (0, 11));
+        assert_eq!(text.len(), 1);
+        assert_eq!(text[0].to_string(), "world");
+
+        let text = buffer.text_in_range((0, 6), (2, 4));
+        let text: Vec<String> = text.iter().map(|l| l.to_string()).collect();
+        assert_eq!(text, ["world", "middle", "last"]);
+    }
+
+    #[test]
+    fn test_buffer_delete_range() {
+        let mut buffer = buffer_from(&["hello world"]);
+        let removed = buffer.delete_range((0, 5), (0, 11));
+        assert_eq!(removed[0].to_string(), " world");
+        assert_eq!(buffer_lines(&buffer), ["hello"]);
+        assert!(buffer.modified);
+
+        let mut buffer = buffer_from(&["fn f() {", "    x();", "}"]);
+        let removed = buffer.delete_range((0, 8), (2, 0));
+        assert_eq!(removed.len(), 3);
+        assert_eq!(buffer_lines(&buffer), ["fn f() {}"]);
+    }
+
+    #[test]
+    fn test_buffer_delete_range_empty() {
+        let mut buffer = buffer_from(&["abc"]);
+        buffer.delete_range((0, 1), (0, 1));
+        assert_eq!(buffer_lines(&buffer), ["abc"]);
+        assert!(!buffer.modified);
+    }
+
+    #[test]
+    fn test_buffer_delete_lines_matching() {
+        let mut buffer = buffer_from(&["INFO start", "DEBUG x", "INFO stop", "DEBUG y"]);
+        assert_eq!(buffer.delete_lines_matching("DEBUG", false), 2);
+        assert_eq!(buffer_lines(&buffer), ["INFO start", "INFO stop"]);
+        assert!(buffer.modified);
+
+        let mut buffer = buffer_from(&["INFO start", "DEBUG x", "INFO stop"]);
+        assert_eq!(buffer.delete_lines_matching("INFO", true), 1);
+        assert_eq!(buffer_lines(&buffer), ["INFO start", "INFO stop"]);
+    }
+
+    #[test]
+    fn test_buffer_delete_lines_matching_all_or_none() {
+        let mut buffer = buffer_from(&["a", "a"]);
+        assert_eq!(buffer.delete_lines_matching("a", false), 2);
+        assert_eq!(buffer.line_count(), 1);
+        assert!(buffer.lines[0].is_empty());
+
+        let mut buffer = buffer_from(&["a", "b"]);
+        assert_eq!(buffer.delete_lines_matching("z", false), 0);
+        assert!(!buffer.modified);
+    }
+
     #[test]
     fn test_buffer_filename() {
         let mut buffer = Buffer::new();
@@ -442,6 +1272,197 @@ mod tests {
         std::fs::remove_file(test_file).ok();
     }
 
+    #[test]
+    fn test_buffer_force_save_creates_parent_dirs() {
+        let temp_dir = std::env::temp_dir().join("zedit_force_save_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.join("new").join("file.txt");
+
+        let mut buffer = buffer_from(&["content"]);
+        buffer.path = Some(path.clone());
+        assert!(buffer.save().is_err());
+
+        buffer.force_save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "content\n");
+        assert!(!buffer.modified);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_buffer_force_save_read_only() {
+        let temp_dir = std::env::temp_dir().join("zedit_force_save_ro_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("ro.txt");
+        fs::write(&path, "old\n").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o444)).unwrap();
+        }
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        assert!(buffer.readonly);
+        buffer.lines = vec![Line::from_str("new")];
+        buffer.force_save().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert!(!buffer.readonly);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o644);
+        }
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_buffer_save_replaces_atomically() {
+        let temp_dir = std::env::temp_dir().join("zedit_atomic_save_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("script.sh");
+        fs::write(&path, "old\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+        }
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        buffer.lines = vec![Line::from_str("new")];
+        buffer.save().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o751);
+        }
+        // Only the saved file is left behind
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_find_and_count_matches() {
+        let buffer = buffer_from(&["café foo", "foofoo", "FOO"]);
+        assert_eq!(buffer.find_all(&Regex::literal("foo", false)), [(0, 5), (1, 0), (1, 3)]);
+        assert_eq!(buffer.count_matches(&Regex::literal("foo", false)), 3);
+        assert_eq!(buffer.find_all(&Regex::literal("foo", true)).len(), 4);
+        assert_eq!(buffer.count_matches(&Regex::literal("aa", false)), 0);
+        assert_eq!(buffer.count_matches(&Regex::literal("", false)), 0);
+
+        // Matches don't overlap
+        let buffer = buffer_from(&["aaaa"]);
+        assert_eq!(buffer.count_matches(&Regex::literal("aa", false)), 2);
+    }
+
+    #[test]
+    fn test_replace_all() {
+        let mut buffer = buffer_from(&["a.b.c", "no dots", "Dot.DOT"]);
+        assert_eq!(buffer.replace_all(&Regex::literal(".", false), "::", true), 3);
+        assert_eq!(buffer_lines(&buffer), ["a::b::c", "no dots", "Dot::DOT"]);
+        assert!(buffer.modified);
+
+        // Replacement text containing the pattern is not rescanned
+        assert_eq!(buffer.replace_all(&Regex::literal("dot", true), "dotdot", true), 3);
+        assert_eq!(buffer_lines(&buffer), ["a::b::c", "no dotdots", "dotdot::dotdot"]);
+
+        let mut buffer = buffer_from(&["same"]);
+        assert_eq!(buffer.replace_all(&Regex::literal("x", false), "y", true), 0);
+        assert!(!buffer.modified);
+    }
+
+    #[test]
+    fn test_visual_col() {
+        let line = Line::from_str("\ta\tbc");
+        assert_eq!(line.visual_col(0, 4), 0);
+        assert_eq!(line.visual_col(1, 4), 4);
+        assert_eq!(line.visual_col(3, 4), 8);
+        assert_eq!(line.visual_col(3, 8), 16);
+        assert_eq!(line.visual_col(6, 4), 11);
+        assert_eq!(Line::from_str("ab\t").visual_col(3, 4), 4);
+
+        // Wide chars take two cells, and push tabs along with them
+        let line = Line::from_str("世界\tx é");
+        assert_eq!(line.visual_col(1, 4), 2);
+        assert_eq!(line.visual_col(3, 4), 8);
+        assert_eq!(line.visual_col(6, 4), 11);
+        assert!(!is_wide('a') && !is_wide('é') && is_wide('한') && is_wide('Ａ') && is_wide('🦀'));
+    }
+
+    #[test]
+    fn test_marks_follow_lines() {
+        let mut buffer = Buffer::new();
+        buffer.lines = ["a", "b", "c", "d"].iter().map(|l| Line::from_str(l)).collect();
+        buffer.commit_undo((0, 0), (0, 0));
+        buffer.marks.insert('a', (2, 0));
+        buffer.marks.insert('b', (3, 0));
+
+        buffer.insert_empty_line(0);
+        buffer.commit_undo((0, 0), (0, 0));
+        assert_eq!(buffer.marks.get(&'a'), Some(&(3, 0)));
+        buffer.delete_lines(3, 1);
+        buffer.commit_undo((0, 0), (0, 0));
+        assert_eq!(buffer.marks.get(&'a'), None);
+        assert_eq!(buffer.marks.get(&'b'), Some(&(3, 0)));
+    }
+
+    #[test]
+    fn test_commit_undo_only_after_edits() {
+        let mut buffer = buffer_from(&["a"]);
+        buffer.commit_undo((0, 0), (0, 0));
+
+        // Lines changed without `mark_changed` aren't looked at
+        buffer.lines[0] = Line::from_str("b");
+        buffer.commit_undo((0, 0), (0, 0));
+        assert_eq!(buffer.undo(), None);
+
+        buffer.mark_changed();
+        buffer.commit_undo((0, 0), (0, 1));
+        assert_eq!(buffer.undo(), Some((0, 0)));
+        assert_eq!(buffer_lines(&buffer), ["a"]);
+    }
+
+    #[test]
+    fn test_shift_lines() {
+        let mut buffer = Buffer::new();
+        buffer.lines = ["a", "", "  b", "\tc", "\t  d"].iter().map(|l| Line::from_str(l)).collect();
+        buffer.shift_lines(0, 5, 4, false);
+        let text: Vec<String> = buffer.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(text, ["    a", "", "      b", "\t\tc", "\t\t  d"]);
+
+        buffer.shift_lines(2, 10, 4, true);
+        buffer.shift_lines(2, 10, 4, true);
+        let text: Vec<String> = buffer.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(text, ["    a", "", "b", "c", "  d"]);
+    }
+
+    #[test]
+    fn test_replace_in_line() {
+        let mut buffer = buffer_from(&["aXa", "a"]);
+        assert_eq!(buffer.replace_in_line(0, &Regex::literal("a", false), "b", false), 1);
+        assert_eq!(buffer_lines(&buffer), ["bXa", "a"]);
+        assert_eq!(buffer.replace_in_line(0, &Regex::literal("x", true), "", true), 1);
+        assert_eq!(buffer_lines(&buffer), ["ba", "a"]);
+        assert_eq!(buffer.replace_in_line(5, &Regex::literal("a", false), "b", true), 0);
+        // Patterns can capture text for the replacement, and empty matches advance
+        let mut buffer = buffer_from(&["key = value", "ab"]);
+        let pattern = Regex::new("(\\w+) = (\\w+)", false).unwrap();
+        assert_eq!(buffer.replace_in_line(0, &pattern, "\\2: \\1", false), 1);
+        assert_eq!(buffer.replace_in_line(1, &Regex::new("x*", false).unwrap(), "-", true), 3);
+        assert_eq!(buffer_lines(&buffer), ["value: key", "-a-b-"]);
+    }
+
     #[test]
     fn test_buffer_load_empty_file() {
         let temp_dir = std::env::temp_dir();
@@ -0,0 +1,167 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Commands whose argument is a file path
+const FILE_COMMANDS: &[&str] = &["cd", "e", "edit", "view", "w", "write", "w!", "write!"];
+
+/// Option names completed after `:set`
+const SET_OPTIONS: &[&str] = &[
+    "autoindent", "autopairs", "clipboard=unnamed", "colorcolumn=", "cursorline", "expandtab", "fileformat=",
+    "filetype=", "ff=", "fixeol", "ft=", "list", "noautoindent", "noautopairs", "nocursorline", "noexpandtab", "nofixeol",
+    "nolist", "nonumber", "norainbow", "noreadonly", "noregex", "norelativenumber", "noswapfile",
+    "notrimtrailing", "number", "rainbow", "readonly", "regex", "relativenumber", "shiftwidth=", "swapfile",
+    "tabstop=", "trimtrailing",
+];
+
+/// Expand a leading `~` to the home directory
+pub fn expand_tilde(path: &str) -> PathBuf {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Complete the last word of a command line, returning full replacement lines
+pub fn complete(line: &str) -> Vec<String> {
+    let Some((head, partial)) = line.rsplit_once(' ') else {
+        return Vec::new();
+    };
+
+    let command = head.split_whitespace().next().unwrap_or("");
+    let candidates = if FILE_COMMANDS.contains(&command) {
+        complete_path(partial)
+    } else if command == "set" {
+        SET_OPTIONS
+            .iter()
+            .filter(|opt| opt.starts_with(partial))
+            .map(|opt| opt.to_string())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    candidates
+        .into_iter()
+        .map(|c| format!("{} {}", head, c))
+        .collect()
+}
+
+/// Complete a partial path, keeping the directory part as typed. Directories end in `/`.
+fn complete_path(partial: &str) -> Vec<String> {
+    let split = partial.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    let (dir, prefix) = partial.split_at(split);
+    let listing_dir = if dir.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand_tilde(dir)
+    };
+
+    let Ok(read_dir) = fs::read_dir(listing_dir) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = read_dir
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// Longest common prefix of all candidates
+pub fn common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+
+    let mut len = first.len();
+    for candidate in &candidates[1..] {
+        len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8())
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_set_options() {
+        assert_eq!(complete("set li"), ["set list"]);
+        assert_eq!(
+            complete("set no"),
+            [
+                "set noautoindent",
+                "set noautopairs",
+                "set nocursorline",
+                "set noexpandtab",
+                "set nofixeol",
+                "set nolist",
+                "set nonumber",
+                "set norainbow",
+                "set noreadonly",
+                "set noregex",
+                "set norelativenumber",
+                "set noswapfile",
+                "set notrimtrailing"
+            ]
+        );
+        assert!(complete("set zz").is_empty());
+        assert!(complete("sort").is_empty());
+    }
+
+    #[test]
+    fn test_complete_paths() {
+        let temp_dir = env::temp_dir().join("zedit_completion_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src")).unwrap();
+        fs::write(temp_dir.join("main.rs"), "").unwrap();
+        fs::write(temp_dir.join("makefile"), "").unwrap();
+        fs::write(temp_dir.join(".hidden"), "").unwrap();
+
+        let base = format!("{}/", temp_dir.display());
+        assert_eq!(
+            complete(&format!("e {}ma", base)),
+            [format!("e {}main.rs", base), format!("e {}makefile", base)]
+        );
+        assert_eq!(complete(&format!("w {}s", base)), [format!("w {}src/", base)]);
+        assert_eq!(complete(&format!("e {}", base)).len(), 3);
+        assert_eq!(complete(&format!("e {}.h", base)), [format!("e {}.hidden", base)]);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let candidates = vec!["e main.rs".to_string(), "e makefile".to_string()];
+        assert_eq!(common_prefix(&candidates), "e ma");
+        assert_eq!(common_prefix(&["only".to_string()]), "only");
+        assert_eq!(common_prefix(&[]), "");
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        assert_eq!(expand_tilde("src/main.rs"), PathBuf::from("src/main.rs"));
+        assert_eq!(expand_tilde("~user/x"), PathBuf::from("~user/x"));
+        if let Some(home) = env::var_os("HOME") {
+            assert_eq!(expand_tilde("~/notes.txt"), PathBuf::from(home).join("notes.txt"));
+        }
+    }
+}
@@ -1,9 +1,27 @@
 use crate::browser::Browser;
-use crate::buffer::Buffer;
-use crate::syntax::{HighlightState, Highlighter};
+use crate::buffer::{display_width, Buffer, Line, LineEnding, SortOptions};
+use crate::completion::{self, expand_tilde};
+use crate::finder::Finder;
+use crate::regex::Regex;
+use crate::state::PositionStore;
+use crate::syntax::{colorize_brackets, find_language, HighlightState, Highlighter};
 use crate::terminal::{ansi, Key, Size, Terminal};
+use crate::textobject::{self, Pos};
+use crate::theme::{Theme, BUILTIN_THEMES};
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Write the swap file after this many keys...
+const SWAP_KEYSTROKES: usize = 200;
+/// ...or once input has been idle this long
+const SWAP_IDLE: Duration = Duration::from_secs(4);
+/// Oldest jumps are dropped beyond this many
+const MAX_JUMPS: usize = 100;
+/// Narrowest name column the browser keeps before dropping its other columns
+const BROWSER_MIN_NAME: usize = 16;
 
 /// Editor mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,23 +31,373 @@ pub enum Mode {
     Command,
     Search,
     Browser,
+    Finder,
+    /// Waiting for an answer to a `Confirm` prompt
+    Confirm,
+    /// Selecting characters from `visual_anchor` to the cursor (`v`)
+    Visual,
+    /// Selecting whole lines from `visual_anchor` to the cursor (`V`)
+    VisualLine,
+}
+
+/// An action that runs once the user confirms it
+#[derive(Debug, Clone)]
+enum ConfirmAction {
+    /// Save the buffer over an existing file
+    Overwrite(PathBuf),
+    /// Quit with unsaved changes: save first, discard, or cancel
+    Quit,
+    /// Load the contents of a swap file left by an earlier session
+    Recover,
+}
+
+impl ConfirmAction {
+    /// Answers shown after the prompt
+    fn choices(&self) -> &'static str {
+        match self {
+            ConfirmAction::Overwrite(_) | ConfirmAction::Recover => "(y/n)",
+            ConfirmAction::Quit => "(y)es / (n)o / (c)ancel",
+        }
+    }
+}
+
+/// Yanked or deleted text
+#[derive(Default)]
+struct Register {
+    lines: Vec<Line>,
+    /// Whole lines (from `dd`) rather than a run of characters
+    linewise: bool,
+}
+
+/// Previously entered command or search lines, recalled with Up/Down
+#[derive(Default)]
+struct History {
+    entries: Vec<String>,
+    /// Entry being shown while browsing; `None` when editing a fresh line
+    index: Option<usize>,
+    /// The line typed before browsing started, restored after the newest entry
+    draft: String,
+}
+
+impl History {
+    /// Record an executed line, skipping empty lines and immediate repeats
+    fn push(&mut self, line: &str) {
+        self.index = None;
+        if !line.is_empty() && self.entries.last().map(String::as_str) != Some(line) {
+            self.entries.push(line.to_string());
+        }
+    }
+
+    /// Step to an older entry, returning the line to show
+    fn older(&mut self, current: &str) -> Option<String> {
+        let idx = match self.index {
+            _ if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(i) => i.saturating_sub(1),
+        };
+        self.index = Some(idx);
+        Some(self.entries[idx].clone())
+    }
+
+    /// Step to a newer entry, returning the line to show
+    fn newer(&mut self) -> Option<String> {
+        let idx = self.index?;
+        if idx + 1 < self.entries.len() {
+            self.index = Some(idx + 1);
+            Some(self.entries[idx + 1].clone())
+        } else {
+            self.index = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+}
+
+/// A pending yes/no question shown on the message line
+struct Confirm {
+    prompt: String,
+    action: ConfirmAction,
+    /// Mode to return to once answered
+    return_mode: Mode,
+}
+
+/// An open buffer and its view state, kept while it is not displayed
+struct BufferSlot {
+    buffer: Buffer,
+    highlighter: Highlighter,
+    cursor_row: usize,
+    cursor_col: usize,
+    scroll_row: usize,
+    scroll_col: usize,
+}
+
+impl BufferSlot {
+    fn new() -> Self {
+        BufferSlot {
+            buffer: Buffer::new(),
+            highlighter: Highlighter::new(None),
+            cursor_row: 0,
+            cursor_col: 0,
+            scroll_row: 0,
+            scroll_col: 0,
+        }
+    }
+}
+
+/// State from before a search was typed, restored if it is cancelled
+struct SearchOrigin {
+    cursor: Pos,
+    scroll_row: usize,
+    pattern: String,
+    highlight: bool,
+}
+
+/// A viewport onto one of the open buffers
+#[derive(Debug, Clone, Copy, Default)]
+struct Window {
+    /// Index into `Editor::buffers`
+    buffer: usize,
+    cursor_row: usize,
+    cursor_col: usize,
+    scroll_row: usize,
+    scroll_col: usize,
+}
+
+/// Pending-key marker for `Ctrl+w` window commands
+const WINDOW_PREFIX: char = '\u{17}';
+
+/// Parse `g/pat/cmd`, `g!/pat/cmd` or `v/pat/cmd` into (invert, pattern, cmd)
+fn parse_global_command(cmd: &str) -> Option<(bool, &str, &str)> {
+    let (invert, rest) = if let Some(rest) = cmd.strip_prefix("g!") {
+        (true, rest)
+    } else if let Some(rest) = cmd.strip_prefix('v') {
+        (true, rest)
+    } else {
+        (false, cmd.strip_prefix('g')?)
+    };
+
+    let rest = rest.strip_prefix('/')?;
+    let (pattern, action) = rest.split_once('/')?;
+    Some((invert, pattern, action.trim()))
+}
+
+/// Normal-mode keys that change the buffer or start changing it
+fn edits_buffer(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::Char('i' | 'I' | 'a' | 'A' | 'o' | 'O' | 'x' | 'p' | 'P' | 'r' | 'J' | '~' | 'u' | '.')
+            | Key::Char('d' | 'c' | 'D' | 'C' | 'S' | '>' | '<')
+            | Key::Ctrl('r')
+            | Key::Paste(_)
+    )
+}
+
+/// Whether the keys of a complete normal-mode command make a change that `.` repeats
+fn is_repeatable_change(keys: &[Key]) -> bool {
+    keys.iter()
+        .find(|key| !matches!(key, Key::Char('0'..='9')))
+        .is_some_and(|key| edits_buffer(key) && !matches!(key, Key::Char('u' | '.') | Key::Ctrl('r')))
+}
+
+/// Normal-mode keys that a count repeats, as in `5j` or `3p`
+fn repeats_with_count(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::Char(
+            'h' | 'j' | 'k' | 'l' | 'w' | 'W' | 'b' | 'B' | 'e' | 'E' | 'n' | 'N' | ';' | ',' | 'p' | 'P' | 'u'
+        )
+            | Key::Left
+            | Key::Right
+            | Key::Up
+            | Key::Down
+            | Key::CtrlLeft
+            | Key::CtrlRight
+            | Key::PageUp
+            | Key::PageDown
+            | Key::Ctrl('d' | 'u' | 'e' | 'y' | 'r' | 'o')
+            | Key::Tab
+    )
+}
+
+/// A parsed `:s` command
+struct Substitute<'a> {
+    /// `:%s` over the whole buffer rather than `:s` on the current line
+    whole_buffer: bool,
+    pattern: &'a str,
+    replacement: &'a str,
+    flags: &'a str,
+}
+
+/// Parse `s/pattern/replacement/flags` or `%s/...`; the trailing `/` is optional
+fn parse_substitute_command(cmd: &str) -> Option<Substitute<'_>> {
+    let (whole_buffer, rest) = match cmd.strip_prefix('%') {
+        Some(rest) => (true, rest),
+        None => (false, cmd),
+    };
+    let mut parts = rest.strip_prefix("s/")?.splitn(3, '/');
+    Some(Substitute {
+        whole_buffer,
+        pattern: parts.next()?,
+        replacement: parts.next().unwrap_or(""),
+        flags: parts.next().unwrap_or(""),
+    })
+}
+
+/// Parse the `sort` word of `:sort`, `:sort!` or `:{start},{end}sort` into its line range
+/// (0-based, end exclusive; `None` for the whole buffer) and whether it sorts in reverse
+fn parse_sort_command(word: &str) -> Option<(Option<(usize, usize)>, bool)> {
+    let reverse = word.ends_with('!');
+    let range = word.trim_end_matches('!').strip_suffix("sort")?;
+    if range.is_empty() || range == "%" {
+        return Some((None, reverse));
+    }
+    let (start, end) = range.split_once(',')?;
+    let (start, end) = (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?);
+    if start == 0 || end == 0 {
+        return None;
+    }
+    Some((Some((start.min(end) - 1, start.max(end))), reverse))
+}
+
+/// Vim-style view position: `All`, `Top`, `Bot`, or a percentage
+fn scroll_position(scroll_row: usize, content_height: usize, line_count: usize) -> String {
+    if line_count <= content_height {
+        "All".to_string()
+    } else if scroll_row == 0 {
+        "Top".to_string()
+    } else if scroll_row + content_height >= line_count {
+        "Bot".to_string()
+    } else {
+        format!("{}%", scroll_row * 100 / (line_count - content_height))
+    }
+}
+
+/// Highlighter for a buffer, from its file name, extension or `#!` line
+fn highlighter_for(buffer: &Buffer) -> Highlighter {
+    let first_line = buffer.line(0).map(|line| line.to_string());
+    Highlighter::for_file(buffer.filename().as_deref(), buffer.extension().as_deref(), first_line.as_deref())
+}
+
+/// Message shown when a file can't be opened
+fn open_error(path: &Path, e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => format!("Permission denied: {}", path.display()),
+        _ => format!("Could not open {}: {}", path.display(), e),
+    }
 }
 
 /// Editor state
 pub struct Editor {
-    terminal: Terminal,
+    /// `None` for a headless editor driven by `feed_keys`
+    terminal: Option<Terminal>,
     buffer: Buffer,
     highlighter: Highlighter,
     cursor_row: usize,
     cursor_col: usize,
     scroll_row: usize,
+    /// First screen column shown, counting tabs at their expanded width
     scroll_col: usize,
     mode: Mode,
     command_buffer: String,
     search_buffer: String,
     search_direction: i8,
+    /// Highlight matches of the last search; cleared by `:noh` until the next search
+    highlight_search: bool,
+    /// Treat search and `:s` patterns as regular expressions (`:set regex`)
+    use_regex: bool,
+    /// The search compiled for highlighting, with the text and `use_regex` it was built from
+    search_cache: RefCell<(String, bool, Result<Regex, String>)>,
+    /// Set while typing a search, whose matches are previewed as it changes
+    search_origin: Option<SearchOrigin>,
+    command_history: History,
+    search_history: History,
+    /// Candidates being cycled by repeated Tab presses in command mode
+    completions: Vec<String>,
+    completion_index: usize,
     message: Option<String>,
     browser: Option<Browser>,
+    finder: Option<Finder>,
+    confirm: Option<Confirm>,
+    /// Open buffers; the slot at `current_buffer` is a placeholder for the active buffer
+    buffers: Vec<BufferSlot>,
+    current_buffer: usize,
+    /// Windows stacked top to bottom; the one at `current_window` has focus and its view
+    /// lives in the editor's own cursor and scroll fields
+    windows: Vec<Window>,
+    current_window: usize,
+    /// Highlight trailing whitespace and indentation tabs (`:set list`)
+    show_whitespace: bool,
+    /// Draw the line number gutter (`:set number`)
+    show_line_numbers: bool,
+    /// Number lines by distance from the cursor (`:set relativenumber`)
+    relative_numbers: bool,
+    /// Color brackets by nesting depth (`:set rainbow`)
+    rainbow_brackets: bool,
+    /// 1-based column marked on every line, or 0 for none (`:set colorcolumn=N`)
+    color_column: usize,
+    /// Give the cursor line a background (`:set cursorline`)
+    cursor_line: bool,
+    /// Colors for syntax and the status line (`:colorscheme`)
+    theme: Theme,
+    /// Whether the terminal shows 24-bit color, checked once at startup
+    truecolor: bool,
+    /// Auto-close brackets and quotes in insert mode (`:set autopairs`)
+    auto_pairs: bool,
+    /// Start new lines with the indentation of the line they came from (`:set autoindent`)
+    auto_indent: bool,
+    /// Columns that `>>` and `<<` shift by (`:set shiftwidth=N`)
+    shift_width: usize,
+    /// Width of a tab on screen, and of the Tab key with `expandtab` (`:set tabstop=N`)
+    tab_stop: usize,
+    /// Insert spaces instead of a tab character for the Tab key (`:set expandtab`)
+    expand_tab: bool,
+    /// Count typed before a normal-mode command, as in `3j` or `2dd`
+    count: Option<usize>,
+    /// Keys of an unfinished normal-mode command such as `d` or `ci`
+    pending: String,
+    /// Text most recently yanked or deleted
+    register: Register,
+    /// Also copy yanks to the system clipboard via OSC 52 (`:set clipboard=unnamed`)
+    clipboard: bool,
+    /// Cursor position before the last jump, returned to by two backticks
+    previous_jump: Option<Pos>,
+    /// Register and keys of the macro being recorded with `q<letter>`
+    macro_recording: Option<(char, Vec<Key>)>,
+    /// Recorded macros by register, replayed with `@<letter>`
+    macros: HashMap<char, Vec<Key>>,
+    /// Register of the last macro replayed, for `@@`
+    last_macro: Option<char>,
+    /// Registers of the macros currently being replayed, innermost last
+    running_macros: Vec<char>,
+    /// Positions before jumps, oldest first, stepped through by `Ctrl+o` and `Ctrl+i`
+    jump_list: Vec<Pos>,
+    /// Where `Ctrl+o` and `Ctrl+i` are in `jump_list`; its length when not stepping
+    jump_index: usize,
+    /// Where insert mode was last left, for `gi`
+    last_insert: Option<Pos>,
+    /// Kind (`f`, `F`, `t` or `T`) and character of the last find, repeated by `;` and `,`
+    last_find: Option<(char, char)>,
+    /// Keys of the normal-mode command being typed, including any inserted text
+    change_keys: Vec<Key>,
+    /// Keys of the last complete change, replayed by `.`
+    last_change: Vec<Key>,
+    /// Replaying `last_change`, which must not record over itself
+    repeating: bool,
+    /// Fixed end of the visual selection; the cursor is the other end
+    visual_anchor: Pos,
+    /// Mode, anchor and cursor of the last visual selection, for `gv`
+    last_visual: Option<(Mode, Pos, Pos)>,
+    /// Cursor position at the start of the current undo step
+    undo_cursor: Pos,
+    /// Remembered cursor positions; `None` when running headless
+    positions: Option<PositionStore>,
+    /// Periodically save unsaved changes to a swap file (`:set swapfile`)
+    swap_file: bool,
+    /// Keys handled since the swap file was last written
+    keys_since_swap: usize,
+    last_input: Instant,
     quit: bool,
     size: Size,
 }
@@ -38,8 +406,28 @@ impl Editor {
     pub fn new() -> io::Result<Self> {
         let terminal = Terminal::new()?;
         let size = Terminal::size()?;
+        let mut editor = Editor::with_terminal(Some(terminal), size);
+        editor.positions = PositionStore::default_location();
+        if Terminal::supports_truecolor() {
+            editor.truecolor = true;
+            editor.theme = Theme::named("dark", true).unwrap_or_else(Theme::dark);
+        }
+        match Theme::load_user() {
+            Ok(Some(theme)) => editor.theme = theme,
+            Ok(None) => {}
+            Err(e) => editor.message = Some(format!("Error in theme file: {}", e)),
+        }
+        Ok(editor)
+    }
+
+    /// Create an editor with a fixed size and no terminal, for driving with `feed_keys`
+    #[cfg(test)]
+    pub fn new_headless(size: Size) -> Self {
+        Editor::with_terminal(None, size)
+    }
 
-        Ok(Editor {
+    fn with_terminal(terminal: Option<Terminal>, size: Size) -> Self {
+        Editor {
             terminal,
             buffer: Buffer::new(),
             highlighter: Highlighter::new(None),
@@ -51,11 +439,96 @@ impl Editor {
             command_buffer: String::new(),
             search_buffer: String::new(),
             search_direction: 1,
+            highlight_search: false,
+            use_regex: false,
+            search_cache: RefCell::new((String::new(), false, Ok(Regex::literal("", false)))),
+            search_origin: None,
+            command_history: History::default(),
+            search_history: History::default(),
+            completions: Vec::new(),
+            completion_index: 0,
             message: None,
             browser: None,
+            finder: None,
+            confirm: None,
+            buffers: vec![BufferSlot::new()],
+            current_buffer: 0,
+            windows: vec![Window::default()],
+            current_window: 0,
+            show_whitespace: false,
+            show_line_numbers: true,
+            relative_numbers: false,
+            rainbow_brackets: false,
+            color_column: 0,
+            cursor_line: false,
+            theme: Theme::dark(),
+            truecolor: false,
+            auto_pairs: true,
+            auto_indent: true,
+            shift_width: 4,
+            tab_stop: 4,
+            expand_tab: true,
+            count: None,
+            pending: String::new(),
+            register: Register::default(),
+            clipboard: false,
+            previous_jump: None,
+            macro_recording: None,
+            macros: HashMap::new(),
+            last_macro: None,
+            running_macros: Vec::new(),
+            jump_list: Vec::new(),
+            jump_index: 0,
+            last_insert: None,
+            last_find: None,
+            change_keys: Vec::new(),
+            last_change: Vec::new(),
+            repeating: false,
+            visual_anchor: (0, 0),
+            last_visual: None,
+            undo_cursor: (0, 0),
+            positions: None,
+            swap_file: true,
+            keys_since_swap: 0,
+            last_input: Instant::now(),
             quit: false,
             size,
-        })
+        }
+    }
+
+    /// Handle a sequence of keys as if typed, without drawing
+    #[cfg(test)]
+    pub fn feed_keys(&mut self, keys: impl IntoIterator<Item = Key>) -> io::Result<()> {
+        for key in keys {
+            self.handle_key(key)?;
+        }
+        Ok(())
+    }
+
+    /// Buffer contents joined with newlines
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        self.buffer
+            .lines
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[cfg(test)]
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_col)
+    }
+
+    #[cfg(test)]
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    #[cfg(test)]
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
     }
 
     /// Open a file or directory
@@ -65,16 +538,406 @@ impl Editor {
             self.mode = Mode::Browser;
         } else {
             self.buffer = Buffer::from_file(path)?;
-            self.highlighter = Highlighter::new(self.buffer.extension().as_deref());
+            self.highlighter = highlighter_for(&self.buffer);
             self.cursor_row = 0;
             self.cursor_col = 0;
             self.scroll_row = 0;
             self.scroll_col = 0;
             self.mode = Mode::Normal;
+            self.restore_position();
+            self.check_swap();
+        }
+        Ok(())
+    }
+
+    /// Open a path, reporting failures on the message line instead of returning them.
+    /// A missing file starts an empty buffer that will be saved to `path`.
+    pub fn open_or_report(&mut self, path: &PathBuf) {
+        match self.open(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !path.is_dir() => {
+                self.buffer = Buffer::new();
+                self.buffer.path = Some(path.clone());
+                self.highlighter = highlighter_for(&self.buffer);
+                self.cursor_row = 0;
+                self.cursor_col = 0;
+                self.mode = Mode::Normal;
+                self.message = Some(format!("\"{}\" [New]", path.display()));
+                self.check_swap();
+            }
+            Err(e) => self.message = Some(open_error(path, &e)),
+        }
+    }
+
+    /// Offer to recover the current file from a swap file left by a crashed session
+    fn check_swap(&mut self) {
+        if !self.swap_file {
+            return;
+        }
+        if let Some(swap) = self.buffer.swap_path().filter(|p| p.exists()) {
+            self.ask(
+                format!("Found swap file {}. Recover?", swap.display()),
+                ConfirmAction::Recover,
+            );
+        }
+    }
+
+    /// Write the swap file once enough keys or idle time have passed since the last write
+    fn update_swap(&mut self) {
+        if !self.swap_file || !self.buffer.modified || self.keys_since_swap == 0 {
+            return;
+        }
+        if self.keys_since_swap >= SWAP_KEYSTROKES || self.last_input.elapsed() >= SWAP_IDLE {
+            if let Err(e) = self.buffer.write_swap() {
+                self.message = Some(format!("Could not write swap file: {}", e));
+            }
+            self.keys_since_swap = 0;
+        }
+    }
+
+    /// Delete the swap files of all open buffers
+    fn remove_swaps(&self) {
+        for (_, buffer) in self.open_buffers() {
+            buffer.remove_swap();
+        }
+    }
+
+    /// Show a buffer that has no file, such as piped input
+    pub fn open_unnamed(&mut self, buffer: Buffer) {
+        self.highlighter = highlighter_for(&buffer);
+        self.buffer = buffer;
+        self.cursor_row = 0;
+        self.cursor_col = 0;
+        self.scroll_row = 0;
+        self.scroll_col = 0;
+    }
+
+    /// Put the cursor on a 1-based line and column, clamped to the buffer
+    pub fn go_to(&mut self, line: usize, col: usize) {
+        self.cursor_row = line.saturating_sub(1);
+        self.cursor_col = col.saturating_sub(1);
+        self.clamp_cursor();
+    }
+
+    /// Run a command given on the command line with `+cmd` or `-c cmd`. `/pat` searches;
+    /// anything else runs as a `:` command, with or without the leading colon.
+    pub fn run_startup_command(&mut self, cmd: &str) {
+        let cmd = cmd.strip_prefix(':').unwrap_or(cmd);
+        if let Some(pattern) = cmd.strip_prefix('/') {
+            self.search_buffer = pattern.to_string();
+            self.search_history.push(pattern);
+            self.perform_search();
+        } else if let Err(e) = self.execute_command(cmd) {
+            self.message = Some(format!("Error: {}", e));
+        }
+        self.clamp_cursor();
+    }
+
+    /// Change the working directory for `:cd`, which relative `:e` paths, the browser
+    /// and the finder start from
+    fn change_directory(&mut self, dir: &Path) {
+        // Pin open buffers to their files before relative paths change meaning
+        let others = self.buffers.iter_mut().map(|slot| &mut slot.buffer);
+        for buffer in std::iter::once(&mut self.buffer).chain(others) {
+            if let Some(path) = &buffer.path
+                && let Ok(absolute) = std::path::absolute(path)
+            {
+                buffer.path = Some(absolute);
+            }
+        }
+
+        self.message = Some(match std::env::set_current_dir(dir) {
+            Ok(()) => std::env::current_dir()
+                .map(|cwd| cwd.display().to_string())
+                .unwrap_or_default(),
+            Err(e) => format!("Cannot change directory to {}: {}", dir.display(), e),
+        });
+    }
+
+    /// Re-read the current file from disk, discarding unsaved changes (`:e!`)
+    fn reload(&mut self) {
+        let Some(path) = self.buffer.path.clone() else {
+            self.message = Some("No file name".to_string());
+            return;
+        };
+        match Buffer::from_file(&path) {
+            Ok(mut buffer) => {
+                buffer.readonly |= self.buffer.readonly;
+                buffer.fix_eol = self.buffer.fix_eol;
+                self.buffer.remove_swap();
+                self.buffer = buffer;
+                self.highlighter = highlighter_for(&self.buffer);
+                self.cursor_row = 0;
+                self.cursor_col = 0;
+                self.scroll_row = 0;
+                self.scroll_col = 0;
+                self.message = Some(format!("\"{}\" reloaded", path.display()));
+            }
+            Err(e) => self.message = Some(open_error(&path, &e)),
+        }
+    }
+
+    /// Open a file for viewing only; edits are refused until `:set noreadonly`
+    pub fn open_readonly(&mut self, path: &PathBuf) {
+        self.open_or_report(path);
+        if self.buffer.path.as_ref() == Some(path) {
+            self.buffer.readonly = true;
+        }
+    }
+
+    /// Move the cursor to where the current file was last left, clamped to the buffer
+    fn restore_position(&mut self) {
+        let (Some(positions), Some(path)) = (&self.positions, &self.buffer.path) else {
+            return;
+        };
+        if let Some((row, col)) = positions.get(path) {
+            self.cursor_row = row;
+            self.cursor_col = col;
+            self.clamp_cursor();
+        }
+    }
+
+    /// Remember the cursor position of the current file and any stashed buffers
+    fn remember_positions(&self) {
+        let Some(positions) = &self.positions else {
+            return;
+        };
+        for (idx, buffer) in self.open_buffers() {
+            if let Some(path) = &buffer.path {
+                let (row, col) = self.slot_cursor(idx);
+                // Best effort: a missing state directory shouldn't block editing
+                let _ = positions.set(path, row, col);
+            }
+        }
+    }
+
+    /// Open a file into its own buffer slot, keeping the current buffer open
+    pub fn open_in_new_buffer(&mut self, path: &PathBuf) -> io::Result<()> {
+        let existing = self.open_buffers().find(|(_, buffer)| buffer.path.as_ref() == Some(path)).map(|(i, _)| i);
+
+        let mut new_file = false;
+        if let Some(idx) = existing {
+            self.switch_buffer(idx);
+        } else {
+            let buffer = match Buffer::from_file(path) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound && !path.is_dir() => {
+                    new_file = true;
+                    let mut buffer = Buffer::new();
+                    buffer.path = Some(path.clone());
+                    buffer
+                }
+                result => result?,
+            };
+            // Reuse an untouched scratch buffer
+            if self.buffer.path.is_some() || self.buffer.modified {
+                self.stash_current_buffer();
+                self.buffers.push(BufferSlot::new());
+                self.current_buffer = self.buffers.len() - 1;
+            }
+            self.highlighter = highlighter_for(&buffer);
+            self.buffer = buffer;
+            self.cursor_row = 0;
+            self.cursor_col = 0;
+            self.scroll_row = 0;
+            self.scroll_col = 0;
+            self.restore_position();
+            self.check_swap();
         }
+
+        self.mode = Mode::Normal;
+        self.message = Some(format!(
+            "\"{}\"{} opened in buffer {}",
+            self.buffer.filename().unwrap_or_default(),
+            if new_file { " [New]" } else { "" },
+            self.current_buffer + 1
+        ));
         Ok(())
     }
 
+    /// Switch to the next or previous buffer slot, wrapping around (`:bn`, `:bp`)
+    fn cycle_buffer(&mut self, forward: bool) {
+        let count = self.buffers.len();
+        let idx = if forward {
+            (self.current_buffer + 1) % count
+        } else {
+            (self.current_buffer + count - 1) % count
+        };
+        self.switch_buffer(idx);
+        self.show_buffer_info();
+    }
+
+    /// Show the active buffer's name and position among the open buffers
+    fn show_buffer_info(&mut self) {
+        self.message = Some(format!(
+            "\"{}\" buffer {} of {}",
+            self.buffer.filename().unwrap_or_else(|| "[No Name]".to_string()),
+            self.current_buffer + 1,
+            self.buffers.len()
+        ));
+    }
+
+    /// One-line listing of the open buffers for `:ls`; `%` marks the active one and
+    /// `[+]` unsaved changes
+    fn buffer_list(&self) -> String {
+        let entries = self.open_buffers().map(|(i, buffer)| {
+            let name = buffer.path.as_ref().map_or("[No Name]".to_string(), |p| p.display().to_string());
+            format!(
+                "{}{} \"{}\"{}",
+                i + 1,
+                if i == self.current_buffer { "%" } else { "" },
+                name,
+                if buffer.modified { " [+]" } else { "" }
+            )
+        });
+        entries.collect::<Vec<_>>().join("  ")
+    }
+
+    /// Every open buffer with its slot index; the active slot's buffer lives in `self.buffer`
+    /// until it is stashed, so this is how to reach all of them
+    fn open_buffers(&self) -> impl Iterator<Item = (usize, &Buffer)> {
+        self.buffers.iter().enumerate().map(|(i, slot)| {
+            let buffer = if i == self.current_buffer { &self.buffer } else { &slot.buffer };
+            (i, buffer)
+        })
+    }
+
+    /// Mutable access to every open buffer, the active one first
+    fn open_buffers_mut(&mut self) -> impl Iterator<Item = &mut Buffer> {
+        let current = self.current_buffer;
+        let stashed = self
+            .buffers
+            .iter_mut()
+            .enumerate()
+            .filter(move |(i, _)| *i != current)
+            .map(|(_, slot)| &mut slot.buffer);
+        std::iter::once(&mut self.buffer).chain(stashed)
+    }
+
+    /// Cursor position of the buffer in slot `idx`
+    fn slot_cursor(&self, idx: usize) -> (usize, usize) {
+        if idx == self.current_buffer {
+            (self.cursor_row, self.cursor_col)
+        } else {
+            (self.buffers[idx].cursor_row, self.buffers[idx].cursor_col)
+        }
+    }
+
+    /// Make the buffer in slot `idx` the active one
+    fn switch_buffer(&mut self, idx: usize) {
+        if idx == self.current_buffer || idx >= self.buffers.len() {
+            return;
+        }
+        self.stash_current_buffer();
+        self.current_buffer = idx;
+        self.restore_buffer(idx);
+    }
+
+    /// Move the active buffer and its view state into its slot
+    fn stash_current_buffer(&mut self) {
+        self.buffers[self.current_buffer] = BufferSlot {
+            buffer: std::mem::replace(&mut self.buffer, Buffer::new()),
+            highlighter: std::mem::replace(&mut self.highlighter, Highlighter::new(None)),
+            cursor_row: self.cursor_row,
+            cursor_col: self.cursor_col,
+            scroll_row: self.scroll_row,
+            scroll_col: self.scroll_col,
+        };
+    }
+
+    /// Take the buffer out of slot `idx` and make it active
+    fn restore_buffer(&mut self, idx: usize) {
+        let slot = std::mem::replace(&mut self.buffers[idx], BufferSlot::new());
+        self.buffer = slot.buffer;
+        self.highlighter = slot.highlighter;
+        self.cursor_row = slot.cursor_row;
+        self.cursor_col = slot.cursor_col;
+        self.scroll_row = slot.scroll_row;
+        self.scroll_col = slot.scroll_col;
+    }
+
+    /// The focused window's buffer and view
+    fn current_view(&self) -> Window {
+        Window {
+            buffer: self.current_buffer,
+            cursor_row: self.cursor_row,
+            cursor_col: self.cursor_col,
+            scroll_row: self.scroll_row,
+            scroll_col: self.scroll_col,
+        }
+    }
+
+    /// Show a window's buffer and view in the editor's fields
+    fn load_window(&mut self, window: Window) {
+        self.switch_buffer(window.buffer);
+        self.cursor_row = window.cursor_row;
+        self.cursor_col = window.cursor_col;
+        self.scroll_row = window.scroll_row;
+        self.scroll_col = window.scroll_col;
+        self.clamp_cursor();
+    }
+
+    /// Move focus to window `idx`
+    fn focus_window(&mut self, idx: usize) {
+        if idx == self.current_window || idx >= self.windows.len() {
+            return;
+        }
+        self.windows[self.current_window] = self.current_view();
+        self.current_window = idx;
+        self.load_window(self.windows[idx]);
+    }
+
+    /// Top screen row and content height of each window; every window is followed by
+    /// its status line, and the last row is left for the command line
+    fn window_layout(&self) -> Vec<(usize, usize)> {
+        let total = (self.size.rows as usize).saturating_sub(1);
+        let count = self.windows.len();
+        let each = total / count;
+        (0..count)
+            .map(|i| {
+                let height = if i + 1 == count { total - each * i } else { each };
+                (each * i, height.saturating_sub(1))
+            })
+            .collect()
+    }
+
+    /// Split the focused window in two (`:sp`); the new window opens above and takes focus
+    fn split_window(&mut self) -> bool {
+        // Each window needs a line of text and its status line
+        if (self.size.rows as usize).saturating_sub(1) / (self.windows.len() + 1) < 2 {
+            self.message = Some("Not enough room".to_string());
+            return false;
+        }
+        self.windows[self.current_window] = self.current_view();
+        self.windows.insert(self.current_window, self.current_view());
+        true
+    }
+
+    /// Close windows other than the focused one, from the bottom up, until each
+    /// still has a line of text and its status line after the terminal shrinks
+    fn fit_windows(&mut self) {
+        let total = (self.size.rows as usize).saturating_sub(1);
+        while self.windows.len() > 1 && total / self.windows.len() < 2 {
+            let last = self.windows.len() - 1;
+            let idx = if self.current_window == last { last - 1 } else { last };
+            self.windows.remove(idx);
+            if idx < self.current_window {
+                self.current_window -= 1;
+            }
+        }
+    }
+
+    /// Close the focused window, moving focus to the one below it (or the new last one)
+    fn close_window(&mut self) {
+        self.windows.remove(self.current_window);
+        self.current_window = self.current_window.min(self.windows.len() - 1);
+        self.load_window(self.windows[self.current_window]);
+    }
+
+    /// Whether any open buffer has unsaved changes
+    fn has_unsaved_changes(&self) -> bool {
+        self.open_buffers().any(|(_, buffer)| buffer.modified)
+    }
+
     /// Main event loop
     pub fn run(&mut self) -> io::Result<()> {
         Terminal::hide_cursor();
@@ -83,15 +946,27 @@ impl Editor {
             self.size = Terminal::size()?;
             self.draw()?;
 
-            if let Some(key) = self.terminal.read_key()? {
-                self.handle_key(key)?;
+            let Some(terminal) = &self.terminal else {
+                return Err(io::Error::other("No terminal attached"));
+            };
+            if let Some(key) = terminal.read_key()? {
+                self.keys_since_swap += 1;
+                self.last_input = Instant::now();
+                if let Err(e) = self.handle_key(key) {
+                    // Keep the session alive; show the failure instead
+                    self.message = Some(format!("Error: {}", e));
+                }
             }
 
             if self.quit {
                 break;
             }
+            self.update_swap();
         }
 
+        self.remember_positions();
+        self.remove_swaps();
+
         Terminal::show_cursor();
         Terminal::clear_screen();
         Terminal::flush()?;
@@ -103,35 +978,237 @@ impl Editor {
     fn handle_key(&mut self, key: Key) -> io::Result<()> {
         self.message = None;
 
-        match self.mode {
-            Mode::Normal => self.handle_normal_key(key)?,
-            Mode::Insert => self.handle_insert_key(key)?,
-            Mode::Command => self.handle_command_key(key)?,
-            Mode::Search => self.handle_search_key(key)?,
-            Mode::Browser => self.handle_browser_key(key)?,
+        // Everything from here until insert mode is left is one undo step
+        if self.mode != Mode::Insert {
+            self.undo_cursor = (self.cursor_row, self.cursor_col);
+            self.buffer.commit_undo(self.undo_cursor, self.undo_cursor);
         }
 
-        Ok(())
+        // Keys replayed by `.` or a macro aren't part of the macro being recorded
+        if let Some((_, keys)) = &mut self.macro_recording
+            && !self.repeating
+            && self.running_macros.is_empty()
+        {
+            keys.push(key.clone());
+        }
+
+        let recording = matches!(self.mode, Mode::Normal | Mode::Insert) && !self.repeating;
+        if recording {
+            self.change_keys.push(key.clone());
+        }
+
+        let result = match self.mode {
+            Mode::Normal => self.handle_normal_key(key),
+            Mode::Insert => self.handle_insert_key(key),
+            Mode::Command => self.handle_command_key(key),
+            Mode::Search => self.handle_search_key(key),
+            Mode::Browser => self.handle_browser_key(key),
+            Mode::Finder => self.handle_finder_key(key),
+            Mode::Confirm => self.handle_confirm_key(key),
+            Mode::Visual | Mode::VisualLine => self.handle_visual_key(key),
+        };
+
+        if recording {
+            let complete = self.mode == Mode::Normal && self.pending.is_empty() && self.count.is_none();
+            if complete && is_repeatable_change(&self.change_keys) {
+                self.last_change = std::mem::take(&mut self.change_keys);
+            } else if complete || self.mode != Mode::Insert && self.mode != Mode::Normal {
+                self.change_keys.clear();
+            }
+        }
+
+        if self.mode != Mode::Insert {
+            let cursor = (self.cursor_row, self.cursor_col);
+            self.buffer.commit_undo(self.undo_cursor, cursor);
+        }
+        result
+    }
+
+    /// Replay the last change (`.`), with `count` replacing the count it was typed with
+    fn repeat_last_change(&mut self, count: Option<usize>) -> io::Result<()> {
+        let mut keys = self.last_change.clone();
+        if let Some(count) = count {
+            let digits = keys.iter().take_while(|key| matches!(key, Key::Char('0'..='9'))).count();
+            keys.splice(..digits, count.to_string().chars().map(Key::Char));
+        }
+
+        self.repeating = true;
+        let result = keys.into_iter().try_for_each(|key| self.handle_key(key));
+        self.repeating = false;
+        result
+    }
+
+    /// Replay the macro in `register` `count` times. A macro can run others but not
+    /// itself, which would never finish.
+    fn run_macro(&mut self, register: char, count: usize) -> io::Result<()> {
+        let Some(keys) = self.macros.get(&register).cloned() else {
+            self.message = Some(format!("Register is empty: {}", register));
+            return Ok(());
+        };
+        if self.running_macros.contains(&register) {
+            self.message = Some(format!("Recursive macro: @{}", register));
+            return Ok(());
+        }
+
+        self.last_macro = Some(register);
+        // The replayed keys record their own changes for `.`
+        self.change_keys.clear();
+        self.running_macros.push(register);
+        let result = (0..count).try_for_each(|_| keys.iter().try_for_each(|key| self.handle_key(key.clone())));
+        self.running_macros.pop();
+        result
     }
 
     /// Handle keys in normal mode
     fn handle_normal_key(&mut self, key: Key) -> io::Result<()> {
-        match key {
-            // Movement
-            Key::Char('h') | Key::Left => self.move_cursor_left(),
-            Key::Char('j') | Key::Down => self.move_cursor_down(),
-            Key::Char('k') | Key::Up => self.move_cursor_up(),
-            Key::Char('l') | Key::Right => self.move_cursor_right(),
-            Key::Char('0') | Key::Home => self.cursor_col = 0,
-            Key::Char('$') | Key::End => self.move_cursor_end_of_line(),
-            Key::Char('g') => self.cursor_row = 0,
-            Key::Char('G') => self.cursor_row = self.buffer.line_count().saturating_sub(1),
-            Key::Char('w') => self.move_word_forward(),
-            Key::Char('b') => self.move_word_backward(),
-            Key::PageUp | Key::Ctrl('u') => self.page_up(),
-            Key::PageDown | Key::Ctrl('d') => self.page_down(),
+        if !self.pending.is_empty() {
+            match key {
+                Key::Char(c) => {
+                    self.pending.push(c);
+                    self.handle_pending()?;
+                }
+                _ => {
+                    self.pending.clear();
+                    self.count = None;
+                }
+            }
+            self.clamp_cursor();
+            return Ok(());
+        }
 
-            // Mode switching
+        // Digits build a count for the next command; a leading 0 is still a motion
+        if let Key::Char(c @ '0'..='9') = key
+            && (c != '0' || self.count.is_some())
+        {
+            let digit = c.to_digit(10).unwrap() as usize;
+            self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            return Ok(());
+        }
+
+        if edits_buffer(&key) && self.refuse_if_readonly() {
+            self.count = None;
+            return Ok(());
+        }
+
+        if let Some(count) = self.count {
+            match key {
+                Key::Char('G') => {
+                    self.count = None;
+                    self.record_jump();
+                    self.cursor_row = count.clamp(1, self.buffer.line_count()) - 1;
+                    self.clamp_cursor();
+                    return Ok(());
+                }
+                Key::Char('x') => {
+                    self.count = None;
+                    self.delete_chars_under_cursor(count);
+                    return Ok(());
+                }
+                Key::Char('.') => {
+                    self.count = None;
+                    return self.repeat_last_change(Some(count));
+                }
+                Key::Char('J') => {
+                    self.count = None;
+                    self.join_lines(count);
+                    return Ok(());
+                }
+                Key::Char('~') => {
+                    self.count = None;
+                    self.toggle_case(count);
+                    return Ok(());
+                }
+                Key::Char('S') => {
+                    self.count = None;
+                    self.change_lines(count);
+                    return Ok(());
+                }
+                // Operators and prefixes use the count once the command is complete
+                Key::Char('d' | 'c' | 'y' | 'r' | '>' | '<' | '@' | 'f' | 'F' | 't' | 'T' | 'g' | 'z') => {}
+                _ => {
+                    self.count = None;
+                    if repeats_with_count(&key) {
+                        for _ in 0..count {
+                            self.handle_normal_key(key.clone())?;
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        match key {
+            // Movement
+            Key::Char('h') | Key::Left => self.move_cursor_left(),
+            Key::Char('j') | Key::Down => self.move_cursor_down(),
+            Key::Char('k') | Key::Up => self.move_cursor_up(),
+            Key::Char('l') | Key::Right => self.move_cursor_right(),
+            Key::Char('0') | Key::Home => self.cursor_col = 0,
+            Key::Char('$') | Key::End => self.move_cursor_end_of_line(),
+            Key::Char(c @ ('g' | 'm' | '`' | '\'' | 'f' | 'F' | 't' | 'T')) => self.pending.push(c),
+            Key::Char('q') if self.macro_recording.is_some() => {
+                if let Some((register, mut keys)) = self.macro_recording.take() {
+                    // Drop the q that stopped the recording
+                    keys.pop();
+                    self.macros.insert(register, keys);
+                }
+            }
+            Key::Char(c @ ('q' | '@')) => self.pending.push(c),
+            Key::Ctrl('o') => self.step_jump_list(true),
+            // Terminals send Ctrl+i as Tab
+            Key::Tab => self.step_jump_list(false),
+            Key::Char('%') => {
+                let cursor = (self.cursor_row, self.cursor_col);
+                if let Some(pos) = textobject::matching_bracket(&self.buffer, cursor) {
+                    self.record_jump();
+                    (self.cursor_row, self.cursor_col) = pos;
+                }
+            }
+            Key::Char(';') => {
+                if let Some((kind, target)) = self.last_find {
+                    self.find_char(kind, target, 1, true);
+                }
+            }
+            Key::Char(',') => {
+                if let Some((kind, target)) = self.last_find {
+                    // The same search in the other direction
+                    let reversed = if kind.is_lowercase() {
+                        kind.to_ascii_uppercase()
+                    } else {
+                        kind.to_ascii_lowercase()
+                    };
+                    self.find_char(reversed, target, 1, true);
+                }
+            }
+            Key::Char('G') => {
+                self.record_jump();
+                self.cursor_row = self.buffer.line_count().saturating_sub(1);
+            }
+            Key::Char(c @ ('H' | 'M' | 'L')) => {
+                self.record_jump();
+                // The view is only corrected on redraw, so after a mass delete in a macro
+                // or `.` it can still start past the end of the buffer
+                let top = self.scroll_row.min(self.buffer.line_count() - 1);
+                let visible = self.content_height().min(self.buffer.line_count() - top);
+                self.cursor_row = match c {
+                    'H' => top,
+                    'M' => top + visible.saturating_sub(1) / 2,
+                    _ => top + visible.saturating_sub(1),
+                };
+            }
+            Key::Char('w') | Key::CtrlRight => self.move_word_forward(false),
+            Key::Char('W') => self.move_word_forward(true),
+            Key::Char('b') | Key::CtrlLeft => self.move_word_backward(false),
+            Key::Char('B') => self.move_word_backward(true),
+            Key::Char('e') => self.move_word_end(false),
+            Key::Char('E') => self.move_word_end(true),
+            Key::Ctrl('e') => self.scroll_lines_down(),
+            Key::Ctrl('y') => self.scroll_lines_up(),
+            Key::Char('z') => self.pending.push('z'),
+            Key::PageUp | Key::Ctrl('u') => self.page_up(),
+            Key::PageDown | Key::Ctrl('d') => self.page_down(),
+
+            // Mode switching
             Key::Char('i') => self.mode = Mode::Insert,
             Key::Char('I') => {
                 self.cursor_col = 0;
@@ -146,55 +1223,70 @@ impl Editor {
                 self.move_cursor_right();
                 self.mode = Mode::Insert;
             }
-            Key::Char('o') => {
-                self.cursor_row = self.cursor_row.saturating_add(1).min(self.buffer.line_count());
-                self.buffer.insert_empty_line(self.cursor_row);
-                self.cursor_col = 0;
-                self.mode = Mode::Insert;
-            }
-            Key::Char('O') => {
-                self.buffer.insert_empty_line(self.cursor_row);
-                self.cursor_col = 0;
-                self.mode = Mode::Insert;
-            }
+            Key::Char('o') => self.open_line(true),
+            Key::Char('O') => self.open_line(false),
+            Key::Char('v') => self.start_visual(Mode::Visual),
+            Key::Char('V') => self.start_visual(Mode::VisualLine),
             Key::Char(':') => {
                 self.command_buffer.clear();
+                self.command_history.index = None;
                 self.mode = Mode::Command;
             }
-            Key::Char('/') => {
-                self.search_buffer.clear();
-                self.search_direction = 1;
+            Key::Char(c @ ('/' | '?')) => {
+                self.search_origin = Some(SearchOrigin {
+                    cursor: (self.cursor_row, self.cursor_col),
+                    scroll_row: self.scroll_row,
+                    pattern: std::mem::take(&mut self.search_buffer),
+                    highlight: self.highlight_search,
+                });
+                self.search_history.index = None;
+                self.search_direction = if c == '/' { 1 } else { -1 };
                 self.mode = Mode::Search;
             }
-            Key::Char('?') => {
-                self.search_buffer.clear();
-                self.search_direction = -1;
-                self.mode = Mode::Search;
+            Key::Char('n') => {
+                self.record_jump();
+                self.search_next();
+            }
+            Key::Char('N') => {
+                self.record_jump();
+                self.search_prev();
             }
-            Key::Char('n') => self.search_next(),
-            Key::Char('N') => self.search_prev(),
 
             // Editing
-            Key::Char('x') => {
-                if let Some(line) = self.buffer.line(self.cursor_row) {
-                    if self.cursor_col < line.len() {
-                        self.buffer.delete_char(self.cursor_row, self.cursor_col);
-                    }
-                }
+            Key::Char('x') => self.delete_chars_under_cursor(1),
+            Key::Char('J') => self.join_lines(2),
+            Key::Char('~') => self.toggle_case(1),
+            Key::Char('u') => match self.buffer.undo() {
+                Some(pos) => (self.cursor_row, self.cursor_col) = pos,
+                None => self.message = Some("Already at oldest change".to_string()),
+            },
+            Key::Ctrl('r') => match self.buffer.redo() {
+                Some(pos) => (self.cursor_row, self.cursor_col) = pos,
+                None => self.message = Some("Already at newest change".to_string()),
+            },
+            Key::Char('p') => self.paste(true),
+            Key::Char('P') => self.paste(false),
+            // A terminal paste goes in at the cursor, leaving it on the last pasted char
+            Key::Paste(text) => {
+                self.insert_pasted(&text);
+                self.move_cursor_left();
             }
-            Key::Char('d') => {
-                // Simple dd implementation - delete line
-                if self.buffer.line_count() > 1 {
-                    self.buffer.lines.remove(self.cursor_row);
-                    if self.cursor_row >= self.buffer.line_count() {
-                        self.cursor_row = self.buffer.line_count() - 1;
-                    }
-                    self.buffer.modified = true;
+            Key::Char('.') => self.repeat_last_change(None)?,
+            Key::Char(c @ ('d' | 'c' | 'y' | 'r' | '>' | '<')) => self.pending.push(c),
+            Key::Char('S') => self.change_lines(1),
+            Key::Char(c @ ('D' | 'C')) => {
+                // Same as d$ and c$, leaving the register alone at the end of the line
+                let op = c.to_ascii_lowercase();
+                let range = self.motion_range(op, '$', 1);
+                if range.start != range.end {
+                    self.apply_operator(op, range);
+                } else if op == 'c' {
+                    self.mode = Mode::Insert;
                 }
             }
 
             // File browser
-            Key::Char('e') => {
+            Key::Char('-') => {
                 let dir = self
                     .buffer
                     .path
@@ -206,13 +1298,14 @@ impl Editor {
                 self.mode = Mode::Browser;
             }
 
-            Key::Ctrl('q') => {
-                if self.buffer.modified {
-                    self.message = Some("Unsaved changes! Use :q! to force quit".to_string());
-                } else {
-                    self.quit = true;
-                }
+            Key::Ctrl('p') => {
+                let root = std::env::current_dir().unwrap_or_default();
+                self.finder = Some(Finder::new(&root));
+                self.mode = Mode::Finder;
             }
+
+            Key::Ctrl('w') => self.pending.push(WINDOW_PREFIX),
+            Key::Ctrl('q') => self.request_quit(),
             Key::Ctrl('s') => {
                 self.save_file()?;
             }
@@ -224,24 +1317,625 @@ impl Editor {
         Ok(())
     }
 
+    /// Open an empty line below (`o`) or above (`O`) the cursor and start inserting
+    fn open_line(&mut self, below: bool) {
+        let row = if below {
+            (self.cursor_row + 1).min(self.buffer.line_count())
+        } else {
+            self.cursor_row.min(self.buffer.line_count())
+        };
+        let indent = self.indent_of(self.cursor_row);
+        self.buffer.insert_empty_line(row);
+        self.cursor_row = row;
+        self.cursor_col = 0;
+        self.insert_indent(&indent);
+        self.mode = Mode::Insert;
+        self.update_scroll(self.content_height());
+    }
+
+    /// Leading whitespace of a line, or nothing with `:set noautoindent`
+    fn indent_of(&self, row: usize) -> Vec<char> {
+        if !self.auto_indent {
+            return Vec::new();
+        }
+        self.buffer
+            .line(row)
+            .map(|line| line.chars.iter().copied().take_while(|c| *c == ' ' || *c == '\t').collect())
+            .unwrap_or_default()
+    }
+
+    /// Insert indentation at the cursor and move past it
+    fn insert_indent(&mut self, indent: &[char]) {
+        for &c in indent {
+            self.buffer.insert_char(self.cursor_row, self.cursor_col, c);
+            self.cursor_col += 1;
+        }
+    }
+
+    /// Store yanked or deleted text, copying it to the system clipboard with
+    /// `:set clipboard=unnamed`
+    fn set_register(&mut self, lines: Vec<Line>, linewise: bool) {
+        if self.clipboard && self.terminal.is_some() {
+            let mut text = lines.iter().map(|l| l.to_string()).collect::<Vec<_>>().join("\n");
+            if linewise {
+                text.push('\n');
+            }
+            print!("{}", ansi::clipboard_copy(&text));
+        }
+        self.register = Register { lines, linewise };
+    }
+
+    /// Delete characters from the cursor (`x`) into the register; the cursor is
+    /// re-clamped so it steps left after deleting the last character
+    fn delete_chars_under_cursor(&mut self, count: usize) {
+        let deleted = self.buffer.delete_chars(self.cursor_row, self.cursor_col, count);
+        if !deleted.is_empty() {
+            self.set_register(vec![Line { chars: deleted }], false);
+        }
+        self.clamp_cursor();
+    }
+
+    /// Replace `count` characters from the cursor with `c` (`r`), leaving the cursor on
+    /// the last one. Nothing changes if the line is too short.
+    fn replace_chars(&mut self, c: char, count: usize) {
+        let line_len = self.buffer.line(self.cursor_row).map_or(0, |l| l.len());
+        if self.cursor_col + count > line_len {
+            return;
+        }
+        for col in self.cursor_col..self.cursor_col + count {
+            self.buffer.delete_char(self.cursor_row, col);
+            self.buffer.insert_char(self.cursor_row, col, c);
+        }
+        self.cursor_col += count - 1;
+    }
+
+    /// Join `count` lines from the cursor row into one (`J`, at least two lines). Leading
+    /// whitespace of each joined line becomes a single space, and the cursor is left
+    /// where the last join happened.
+    fn join_lines(&mut self, count: usize) {
+        for _ in 1..count.max(2) {
+            let row = self.cursor_row;
+            if row + 1 >= self.buffer.line_count() {
+                break;
+            }
+            let next = self.buffer.lines.remove(row + 1);
+            let indent = next.chars.iter().take_while(|c| c.is_whitespace()).count();
+            let tail = Line {
+                chars: next.chars[indent..].to_vec(),
+            };
+
+            let line = &mut self.buffer.lines[row];
+            self.cursor_col = line.len();
+            let ends_in_space = line.chars.last().is_none_or(|c| c.is_whitespace());
+            if !tail.is_empty() && !ends_in_space && tail.chars[0] != ')' {
+                line.chars.push(' ');
+            }
+            line.append(&tail);
+            self.buffer.mark_changed();
+        }
+    }
+
+    /// Flip the case of `count` characters from the cursor (`~`) and move past them
+    fn toggle_case(&mut self, count: usize) {
+        let Some(line) = self.buffer.line_mut(self.cursor_row) else {
+            return;
+        };
+        let end = (self.cursor_col + count).min(line.len());
+        if self.cursor_col >= end {
+            return;
+        }
+        for c in &mut line.chars[self.cursor_col..end] {
+            let flipped: Vec<char> = if c.is_lowercase() {
+                c.to_uppercase().collect()
+            } else {
+                c.to_lowercase().collect()
+            };
+            // Characters without a single-char counterpart, like 'ß', stay as they are
+            if let [single] = flipped[..] {
+                *c = single;
+            }
+        }
+        self.buffer.mark_changed();
+        self.cursor_col = end;
+        self.clamp_cursor();
+    }
+
+    /// Delete whole lines from the cursor row (`dd`) into the register
+    fn delete_lines_at_cursor(&mut self, count: usize) {
+        let lines = self.buffer.delete_lines(self.cursor_row, count);
+        if !lines.is_empty() {
+            self.set_register(lines, true);
+        }
+        self.cursor_row = self.cursor_row.min(self.buffer.line_count() - 1);
+        self.cursor_col = 0;
+    }
+
+    /// Indent (`>>`) or unindent (`<<`) `count` lines from the cursor row by the
+    /// shiftwidth, leaving the cursor on the first non-blank character
+    fn shift_lines(&mut self, count: usize, outdent: bool) {
+        self.buffer.shift_lines(self.cursor_row, count, self.shift_width, outdent);
+        let lines = count.min(self.buffer.line_count() - self.cursor_row);
+        if lines > 2 {
+            let direction = if outdent { "unindented" } else { "indented" };
+            self.message = Some(format!("{} lines {}", lines, direction));
+        }
+        self.cursor_col = self.first_non_blank(self.cursor_row);
+        self.clamp_cursor();
+    }
+
+    /// Replace `count` lines from the cursor row (`cc`, `S`) with one empty line, keeping
+    /// the indentation with autoindent, and start inserting there
+    fn change_lines(&mut self, count: usize) {
+        let row = self.cursor_row;
+        let mut lines = vec![self.buffer.lines[row].clone()];
+        lines.extend(self.buffer.delete_lines(row + 1, count - 1));
+        self.set_register(lines, true);
+
+        let indent = self.indent_of(row);
+        self.cursor_col = indent.len();
+        if let Some(line) = self.buffer.line_mut(row) {
+            line.chars = indent;
+        }
+        self.buffer.mark_changed();
+        self.mode = Mode::Insert;
+    }
+
+    /// Copy whole lines from the cursor row (`yy`) into the register
+    fn yank_lines_at_cursor(&mut self, count: usize) {
+        let end = (self.cursor_row + count).min(self.buffer.line_count());
+        let lines = self.buffer.lines[self.cursor_row..end].to_vec();
+        if lines.len() > 2 {
+            self.message = Some(format!("{} lines yanked", lines.len()));
+        }
+        self.set_register(lines, true);
+    }
+
+    /// Paste the register after (`p`) or before (`P`) the cursor. Whole lines go
+    /// below or above the current line.
+    fn paste(&mut self, after: bool) {
+        if self.register.lines.is_empty() {
+            return;
+        }
+
+        if self.register.linewise {
+            let row = if after { self.cursor_row + 1 } else { self.cursor_row };
+            for (i, line) in self.register.lines.iter().enumerate() {
+                self.buffer.lines.insert(row + i, line.clone());
+            }
+            self.buffer.mark_changed();
+            self.cursor_row = row;
+            self.cursor_col = 0;
+        } else {
+            let line_len = self.buffer.line(self.cursor_row).map_or(0, |l| l.len());
+            let col = if after && line_len > 0 { self.cursor_col + 1 } else { self.cursor_col };
+            let (row, col) = self.buffer.insert_text(self.cursor_row, col, &self.register.lines);
+            // Leave the cursor on the last pasted character
+            self.cursor_row = row;
+            self.cursor_col = col.saturating_sub(1);
+        }
+    }
+
+    /// Run a multi-key normal-mode command once enough keys have been typed
+    fn handle_pending(&mut self) -> io::Result<()> {
+        let keys: Vec<char> = self.pending.chars().collect();
+        let count = self.count.unwrap_or(1);
+        // Macros run once the keys that started them are cleared
+        let mut run_macro = None;
+        match keys.as_slice() {
+            ['d', 'd'] => self.delete_lines_at_cursor(count),
+            ['y', 'y'] => self.yank_lines_at_cursor(count),
+            ['c', 'c'] => self.change_lines(count),
+            ['r', c] => self.replace_chars(*c, count),
+            [op @ ('>' | '<'), c] if c == op => self.shift_lines(count, *op == '<'),
+            [kind @ ('f' | 'F' | 't' | 'T'), target] => {
+                self.last_find = Some((*kind, *target));
+                self.find_char(*kind, *target, count, false);
+            }
+            [op @ ('d' | 'c' | 'y'), motion @ ('w' | 'W' | 'e' | 'E' | 'b' | 'B' | '$' | '0')] => {
+                let range = self.motion_range(*op, *motion, count);
+                self.apply_operator(*op, range);
+            }
+            ['z', 'z'] => {
+                self.scroll_row = self.cursor_row.saturating_sub(self.content_height() / 2);
+            }
+            ['z', 't'] => self.scroll_row = self.cursor_row,
+            ['z', 'b'] => {
+                self.scroll_row = self.cursor_row.saturating_sub(self.content_height().saturating_sub(1));
+            }
+            ['z', _] => {}
+            ['g', 'g'] => {
+                self.record_jump();
+                self.cursor_row = self.count.map_or(0, |n| n.clamp(1, self.buffer.line_count()) - 1);
+            }
+            ['g', 'i'] if self.refuse_if_readonly() => {}
+            ['g', 'i'] => match self.last_insert {
+                Some(pos) => {
+                    (self.cursor_row, self.cursor_col) = pos;
+                    self.mode = Mode::Insert;
+                    self.clamp_cursor();
+                }
+                None => self.message = Some("No previous insert".to_string()),
+            },
+            ['g', 'v'] => match self.last_visual {
+                Some((mode, anchor, cursor)) => {
+                    (self.cursor_row, self.cursor_col) = cursor;
+                    self.clamp_cursor();
+                    self.visual_anchor = (anchor.0.min(self.buffer.line_count() - 1), anchor.1);
+                    self.mode = mode;
+                }
+                None => self.message = Some("No previous visual selection".to_string()),
+            },
+            [WINDOW_PREFIX, 'w'] => self.focus_window((self.current_window + 1) % self.windows.len()),
+            [WINDOW_PREFIX, 'j'] => self.focus_window(self.current_window + 1),
+            [WINDOW_PREFIX, 'k'] => self.focus_window(self.current_window.saturating_sub(1)),
+            ['q', register] if register.is_ascii_lowercase() => {
+                self.macro_recording = Some((*register, Vec::new()));
+            }
+            ['@', '@'] => match self.last_macro {
+                Some(register) => run_macro = Some(register),
+                None => self.message = Some("No previous macro".to_string()),
+            },
+            ['@', register] if register.is_ascii_lowercase() => run_macro = Some(*register),
+            ['m', mark] if mark.is_ascii_lowercase() => {
+                self.buffer.marks.insert(*mark, (self.cursor_row, self.cursor_col));
+            }
+            // A backtick goes to the exact position, a quote to the line's first non-blank
+            [kind @ ('`' | '\''), '`' | '\''] => match self.previous_jump {
+                Some(pos) => self.jump_to(pos, *kind == '`'),
+                None => self.message = Some("No previous jump".to_string()),
+            },
+            [kind @ ('`' | '\''), mark] if mark.is_ascii_lowercase() => match self.buffer.marks.get(mark) {
+                Some(&pos) => self.jump_to(pos, *kind == '`'),
+                None => self.message = Some(format!("Mark not set: {}", mark)),
+            },
+            // Wait for the text object
+            [_, 'i' | 'a'] => return Ok(()),
+            [op, scope @ ('i' | 'a'), object] => {
+                let cursor = (self.cursor_row, self.cursor_col);
+                match textobject::select(&self.buffer, cursor, *object, *scope == 'a') {
+                    Some(range) => self.apply_operator(*op, range),
+                    None => self.message = Some("No text object here".to_string()),
+                }
+            }
+            _ => {
+                self.message = Some(format!("Unknown command: {}", self.pending));
+            }
+        }
+        self.pending.clear();
+        self.count = None;
+        match run_macro {
+            Some(register) => self.run_macro(register, count),
+            None => Ok(()),
+        }
+    }
+
+    /// Move to the `count`th `target` on the cursor line: onto it for `f`/`F`, next to it
+    /// for `t`/`T`. The cursor stays put if there is no such character.
+    fn find_char(&mut self, kind: char, target: char, count: usize, repeat: bool) {
+        let Some(line) = self.buffer.line(self.cursor_row) else {
+            return;
+        };
+        let till = kind == 't' || kind == 'T';
+        // Repeating `t` must not stop before the character it is already next to
+        let skip = usize::from(repeat && till);
+        let col = self.cursor_col;
+        let found = if kind == 'f' || kind == 't' {
+            line.chars
+                .iter()
+                .enumerate()
+                .skip(col + 1 + skip)
+                .filter(|(_, c)| **c == target)
+                .nth(count - 1)
+        } else {
+            line.chars[..col.saturating_sub(skip).min(line.len())]
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(|(_, c)| **c == target)
+                .nth(count - 1)
+        };
+
+        if let Some((i, _)) = found {
+            self.cursor_col = match kind {
+                't' => i - 1,
+                'T' => i + 1,
+                _ => i,
+            };
+        }
+    }
+
+    /// Show a message and return true if the buffer must not be changed
+    fn refuse_if_readonly(&mut self) -> bool {
+        if self.buffer.readonly {
+            self.message = Some("Buffer is read-only (:set noreadonly to allow changes)".to_string());
+        }
+        self.buffer.readonly
+    }
+
+    /// Remember the cursor before a jump (`G`, `gg`, searches, marks, `:N`) to return to
+    /// later
+    fn record_jump(&mut self) {
+        let cursor = (self.cursor_row, self.cursor_col);
+        self.previous_jump = Some(cursor);
+        // Jumping after stepping back forgets the newer entries, as in a browser
+        self.jump_list.truncate(self.jump_index);
+        if self.jump_list.last() != Some(&cursor) {
+            self.jump_list.push(cursor);
+        }
+        if self.jump_list.len() > MAX_JUMPS {
+            self.jump_list.remove(0);
+        }
+        self.jump_index = self.jump_list.len();
+    }
+
+    /// Jump to a remembered position, or to its line's first non-blank unless `exact`
+    fn jump_to(&mut self, pos: Pos, exact: bool) {
+        self.record_jump();
+        self.cursor_row = pos.0.min(self.buffer.line_count() - 1);
+        self.cursor_col = if exact { pos.1 } else { self.first_non_blank(self.cursor_row) };
+        self.clamp_cursor();
+    }
+
+    /// Move to the previous (`Ctrl+o`) or next (`Ctrl+i`) position in the jump list
+    fn step_jump_list(&mut self, back: bool) {
+        let cursor = (self.cursor_row, self.cursor_col);
+        if back && self.jump_index == self.jump_list.len() {
+            // Remember where stepping started so Ctrl+i can come back to it
+            if self.jump_list.last() != Some(&cursor) {
+                self.jump_list.push(cursor);
+            }
+            self.jump_index = self.jump_list.len() - 1;
+        }
+        if back && self.jump_index > 0 {
+            self.jump_index -= 1;
+        } else if !back && self.jump_index + 1 < self.jump_list.len() {
+            self.jump_index += 1;
+        } else {
+            return;
+        }
+        let (row, col) = self.jump_list[self.jump_index];
+        self.cursor_row = row.min(self.buffer.line_count() - 1);
+        self.cursor_col = col;
+        self.clamp_cursor();
+    }
+
+    /// Column of the first non-whitespace char of a line
+    fn first_non_blank(&self, row: usize) -> usize {
+        self.buffer
+            .line(row)
+            .map_or(0, |line| line.chars.iter().take_while(|c| c.is_whitespace()).count())
+    }
+
+    /// Text covered by moving the cursor with `motion`, for `dw`, `c$`, `yb` and so on
+    fn motion_range(&mut self, op: char, motion: char, count: usize) -> textobject::TextRange {
+        let cursor = (self.cursor_row, self.cursor_col);
+        let line_len = self.buffer.line(cursor.0).map_or(0, |l| l.len());
+        // Like vim, `cw` on a word changes to the end of the word
+        let on_word = self.class_at(cursor, false) != 0;
+        let motion = match motion {
+            'w' if op == 'c' && on_word => 'e',
+            'W' if op == 'c' && on_word => 'E',
+            m => m,
+        };
+
+        let (start, end) = match motion {
+            '$' => (cursor, (cursor.0, line_len)),
+            '0' => ((cursor.0, 0), cursor),
+            _ => {
+                for _ in 0..count {
+                    match motion {
+                        'w' | 'W' => self.move_word_forward(motion == 'W'),
+                        'e' | 'E' => self.move_word_end(motion == 'E'),
+                        _ => self.move_word_backward(motion == 'B'),
+                    }
+                }
+                let target = (self.cursor_row, self.cursor_col);
+                (self.cursor_row, self.cursor_col) = cursor;
+                match motion {
+                    // Word ends are inclusive
+                    'e' | 'E' => (cursor, (target.0, target.1 + 1)),
+                    // A word motion that leaves the line stops at its end
+                    'w' | 'W' if target.0 > cursor.0 => (cursor, (cursor.0, line_len)),
+                    'w' | 'W' => (cursor, target),
+                    _ => (target, cursor),
+                }
+            }
+        };
+        textobject::TextRange { start, end }
+    }
+
+    /// Delete, change or yank a range of text
+    fn apply_operator(&mut self, op: char, range: textobject::TextRange) {
+        let lines = if op == 'y' {
+            self.buffer.text_in_range(range.start, range.end)
+        } else {
+            self.buffer.delete_range(range.start, range.end)
+        };
+        self.set_register(lines, false);
+        if op == 'c' {
+            self.mode = Mode::Insert;
+        }
+        (self.cursor_row, self.cursor_col) = range.start;
+    }
+
+    /// Start selecting from the cursor
+    fn start_visual(&mut self, mode: Mode) {
+        self.visual_anchor = (self.cursor_row, self.cursor_col);
+        self.mode = mode;
+    }
+
+    /// Leave visual mode, remembering the selection for `gv`
+    fn end_visual(&mut self) {
+        self.last_visual = Some((self.mode, self.visual_anchor, (self.cursor_row, self.cursor_col)));
+        self.mode = Mode::Normal;
+    }
+
+    /// Start and end of the visual selection in buffer order, both inclusive
+    fn visual_selection(&self) -> (Pos, Pos) {
+        let cursor = (self.cursor_row, self.cursor_col);
+        if self.visual_anchor <= cursor {
+            (self.visual_anchor, cursor)
+        } else {
+            (cursor, self.visual_anchor)
+        }
+    }
+
+    /// Selected char columns of `row` as a half-open range, if any are selected
+    fn selected_columns(&self, row: usize) -> Option<(usize, usize)> {
+        let (start, end) = self.visual_selection();
+        match self.mode {
+            Mode::VisualLine if (start.0..=end.0).contains(&row) => Some((0, usize::MAX)),
+            Mode::Visual if (start.0..=end.0).contains(&row) => {
+                let from = if row == start.0 { start.1 } else { 0 };
+                let to = if row == end.0 { end.1 + 1 } else { usize::MAX };
+                Some((from, to))
+            }
+            _ => None,
+        }
+    }
+
+    /// Char ranges of the highlighted search matches on `row`
+    fn search_matches(&self, row: usize) -> Vec<(usize, usize)> {
+        if !self.highlight_search || self.search_buffer.is_empty() {
+            return Vec::new();
+        }
+        match &*self.search_regex() {
+            Ok(pattern) => self.buffer.find_in_line(row, pattern),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// The compiled search, recompiled only when its text or `:set regex` has changed
+    fn search_regex(&self) -> Ref<'_, Result<Regex, String>> {
+        {
+            let mut cache = self.search_cache.borrow_mut();
+            if cache.0 != self.search_buffer || cache.1 != self.use_regex {
+                let pattern = self.compile_pattern(&self.search_buffer, false);
+                *cache = (self.search_buffer.clone(), self.use_regex, pattern);
+            }
+        }
+        Ref::map(self.search_cache.borrow(), |cache| &cache.2)
+    }
+
+    /// Handle keys in visual mode: motions extend the selection, operators act on it
+    fn handle_visual_key(&mut self, key: Key) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            // Only `gg` moves; other `g` commands would leave the selection
+            if key == Key::Char('g') {
+                return self.handle_normal_key(key);
+            }
+            self.pending.clear();
+            self.count = None;
+            return Ok(());
+        }
+
+        match key {
+            Key::Escape => self.end_visual(),
+            Key::Char('v') if self.mode == Mode::Visual => self.end_visual(),
+            Key::Char('V') if self.mode == Mode::VisualLine => self.end_visual(),
+            Key::Char('v') => self.mode = Mode::Visual,
+            Key::Char('V') => self.mode = Mode::VisualLine,
+            Key::Char(op @ ('y' | 'd' | 'x')) => {
+                self.count = None;
+                if op == 'y' || !self.refuse_if_readonly() {
+                    self.apply_visual_operator(op == 'y');
+                }
+            }
+            // Start a command on the selected lines, e.g. `:3,7sort`
+            Key::Char(':') => {
+                let (start, end) = self.visual_selection();
+                self.end_visual();
+                self.command_buffer = format!("{},{}", start.0 + 1, end.0 + 1);
+                self.command_history.index = None;
+                self.mode = Mode::Command;
+            }
+            Key::Char(
+                '0'..='9' | 'h' | 'j' | 'k' | 'l' | 'w' | 'W' | 'b' | 'B' | 'e' | 'E' | '$' | 'g'
+                | 'G' | 'H' | 'M' | 'L' | 'n' | 'N' | '%' | ';' | ',',
+            )
+            | Key::Left
+            | Key::Right
+            | Key::Up
+            | Key::Down
+            | Key::Home
+            | Key::End
+            | Key::CtrlLeft
+            | Key::CtrlRight
+            | Key::PageUp
+            | Key::PageDown
+            | Key::Ctrl('d' | 'u' | 'e' | 'y') => self.handle_normal_key(key)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Yank or delete the visual selection and return to normal mode
+    fn apply_visual_operator(&mut self, yank: bool) {
+        let (start, end) = self.visual_selection();
+        let linewise = self.mode == Mode::VisualLine;
+        self.end_visual();
+
+        if linewise {
+            let count = end.0 - start.0 + 1;
+            self.cursor_row = start.0;
+            if yank {
+                self.yank_lines_at_cursor(count);
+            } else {
+                self.delete_lines_at_cursor(count);
+            }
+        } else {
+            // The selection includes the character under its end, or the line break
+            // when it ends on an empty line
+            let end_len = self.buffer.line(end.0).map_or(0, |l| l.len());
+            let end = if end.1 < end_len {
+                (end.0, end.1 + 1)
+            } else {
+                (end.0 + 1, 0)
+            };
+            self.apply_operator(if yank { 'y' } else { 'd' }, textobject::TextRange { start, end });
+        }
+        self.clamp_cursor();
+    }
+
+    /// Insert pasted text at the cursor verbatim, bypassing auto-pairs and auto-indent
+    fn insert_pasted(&mut self, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                self.buffer.insert_newline(self.cursor_row, self.cursor_col);
+                self.cursor_row += 1;
+                self.cursor_col = 0;
+            } else {
+                self.buffer.insert_char(self.cursor_row, self.cursor_col, c);
+                self.cursor_col += 1;
+            }
+        }
+    }
+
     /// Handle keys in insert mode
     fn handle_insert_key(&mut self, key: Key) -> io::Result<()> {
         match key {
             Key::Escape => {
+                self.last_insert = Some((self.cursor_row, self.cursor_col));
                 self.mode = Mode::Normal;
                 self.move_cursor_left();
             }
-            Key::Char(c) => {
-                self.buffer.insert_char(self.cursor_row, self.cursor_col, c);
-                self.cursor_col += 1;
-            }
+            Key::Char(c) => self.insert_typed_char(c),
+            Key::Paste(text) => self.insert_pasted(&text),
             Key::Enter => {
+                // Only the indentation before the cursor carries over
+                let indent: Vec<char> = self.indent_of(self.cursor_row).into_iter().take(self.cursor_col).collect();
                 self.buffer.insert_newline(self.cursor_row, self.cursor_col);
                 self.cursor_row += 1;
                 self.cursor_col = 0;
+                self.insert_indent(&indent);
             }
             Key::Backspace => {
-                if self.cursor_col > 0 {
+                if self.auto_pairs && self.cursor_col > 0 && self.between_pair() {
+                    // Delete an empty auto-pair together
+                    self.cursor_col -= 1;
+                    self.buffer.delete_char(self.cursor_row, self.cursor_col);
+                    self.buffer.delete_char(self.cursor_row, self.cursor_col);
+                } else if self.cursor_col > 0 {
                     self.cursor_col -= 1;
                     self.buffer.delete_char(self.cursor_row, self.cursor_col);
                 } else if self.cursor_row > 0 {
@@ -265,19 +1959,24 @@ impl Editor {
                         if let Some(current) = self.buffer.line_mut(self.cursor_row) {
                             current.append(&next_line);
                         }
-                        self.buffer.modified = true;
+                        self.buffer.mark_changed();
                     }
                 }
             }
-            Key::Tab => {
-                // Insert 4 spaces for tab
-                for _ in 0..4 {
+            Key::Tab if self.expand_tab => {
+                for _ in 0..self.tab_stop {
                     self.buffer.insert_char(self.cursor_row, self.cursor_col, ' ');
                     self.cursor_col += 1;
                 }
             }
+            Key::Tab => {
+                self.buffer.insert_char(self.cursor_row, self.cursor_col, '\t');
+                self.cursor_col += 1;
+            }
             Key::Left => self.move_cursor_left(),
             Key::Right => self.move_cursor_right(),
+            Key::CtrlLeft => self.move_word_backward(false),
+            Key::CtrlRight => self.move_word_forward(false),
             Key::Up => self.move_cursor_up(),
             Key::Down => self.move_cursor_down(),
             Key::Home => self.cursor_col = 0,
@@ -290,20 +1989,198 @@ impl Editor {
         Ok(())
     }
 
-    /// Handle keys in command mode
-    fn handle_command_key(&mut self, key: Key) -> io::Result<()> {
-        match key {
-            Key::Escape => {
+    /// Insert a typed character, auto-closing brackets and quotes
+    fn insert_typed_char(&mut self, c: char) {
+        let line = self.buffer.line(self.cursor_row);
+        let prev = line.and_then(|l| self.cursor_col.checked_sub(1).and_then(|i| l.chars.get(i).copied()));
+        let next = line.and_then(|l| l.chars.get(self.cursor_col).copied());
+
+        if self.auto_pairs {
+            // Typing the closer that is already next just steps over it
+            if next == Some(c) && matches!(c, ')' | ']' | '}' | '"' | '\'') {
+                self.cursor_col += 1;
+                return;
+            }
+
+            let closer = match c {
+                '(' => Some(')'),
+                '[' => Some(']'),
+                '{' => Some('}'),
+                '"' | '\'' if !prev.is_some_and(|p| p.is_alphanumeric()) => Some(c),
+                _ => None,
+            };
+            // Single quotes start lifetimes and char literals in Rust
+            let rust_quote = c == '\''
+                && self.highlighter.language.is_some_and(|lang| lang.name == "Rust");
+            let next_allows = next.is_none_or(|n| n.is_whitespace() || ")]},;".contains(n));
+
+            if let Some(closer) = closer
+                && !rust_quote
+                && next_allows
+            {
+                self.buffer.insert_char(self.cursor_row, self.cursor_col, c);
+                self.buffer.insert_char(self.cursor_row, self.cursor_col + 1, closer);
+                self.cursor_col += 1;
+                return;
+            }
+        }
+
+        self.buffer.insert_char(self.cursor_row, self.cursor_col, c);
+        self.cursor_col += 1;
+    }
+
+    /// Whether the cursor sits between an empty bracket or quote pair
+    fn between_pair(&self) -> bool {
+        let Some(line) = self.buffer.line(self.cursor_row) else {
+            return false;
+        };
+        let prev = self.cursor_col.checked_sub(1).and_then(|i| line.chars.get(i));
+        let next = line.chars.get(self.cursor_col);
+        matches!(
+            (prev, next),
+            (Some('('), Some(')'))
+                | (Some('['), Some(']'))
+                | (Some('{'), Some('}'))
+                | (Some('"'), Some('"'))
+                | (Some('\''), Some('\''))
+        )
+    }
+
+    /// Ask a yes/no question before running `action`
+    fn ask(&mut self, prompt: String, action: ConfirmAction) {
+        self.confirm = Some(Confirm {
+            prompt,
+            action,
+            return_mode: self.mode,
+        });
+        self.mode = Mode::Confirm;
+    }
+
+    /// Handle the answer to a confirmation prompt
+    fn handle_confirm_key(&mut self, key: Key) -> io::Result<()> {
+        let Some(confirm) = self.confirm.take() else {
+            self.mode = Mode::Normal;
+            return Ok(());
+        };
+        self.mode = confirm.return_mode;
+
+        let answer = match key {
+            Key::Char(c) => c.to_ascii_lowercase(),
+            _ => 'c',
+        };
+
+        match (confirm.action, answer) {
+            (ConfirmAction::Overwrite(path), 'y') => {
+                self.buffer.save_as(path.clone())?;
+                self.message = Some(format!("Saved to {}", path.display()));
+            }
+            (ConfirmAction::Quit, 'y') => {
+                if self.save_all()? {
+                    self.quit = true;
+                }
+            }
+            (ConfirmAction::Quit, 'n') => self.quit = true,
+            (ConfirmAction::Recover, 'y') => {
+                self.buffer.recover_swap()?;
+                self.clamp_cursor();
+                self.message = Some("Recovered unsaved changes from the swap file".to_string());
+            }
+            (ConfirmAction::Recover, 'n') => {
+                self.buffer.remove_swap();
+                self.message = Some("Swap file discarded".to_string());
+            }
+            _ => self.message = Some("Cancelled".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Quit, asking first if any buffer has unsaved changes
+    fn request_quit(&mut self) {
+        if self.has_unsaved_changes() {
+            self.ask("Save changes?".to_string(), ConfirmAction::Quit);
+        } else {
+            self.quit = true;
+        }
+    }
+
+    /// Save every modified buffer, returning false if one has no filename
+    fn save_all(&mut self) -> io::Result<bool> {
+        let mut refused = None;
+        for buffer in self.open_buffers_mut().filter(|buffer| buffer.modified) {
+            if buffer.path.is_none() {
+                refused = Some("No filename. Use :w <filename>");
+                break;
+            }
+            if buffer.readonly {
+                refused = Some("Buffer is read-only. Use :w! to save anyway");
+                break;
+            }
+            buffer.save()?;
+        }
+        if let Some(reason) = refused {
+            self.message = Some(reason.to_string());
+            return Ok(false);
+        }
+        self.remember_positions();
+        Ok(true)
+    }
+
+    /// Complete the command line: fill a unique match or the common prefix, and
+    /// cycle through the candidates on further presses
+    fn complete_command(&mut self) {
+        if !self.completions.is_empty() {
+            self.completion_index = (self.completion_index + 1) % self.completions.len();
+            self.command_buffer = self.completions[self.completion_index].clone();
+            return;
+        }
+
+        let candidates = completion::complete(&self.command_buffer);
+        if candidates.len() == 1 {
+            self.command_buffer = candidates[0].clone();
+            return;
+        }
+
+        let prefix = completion::common_prefix(&candidates);
+        if prefix.len() > self.command_buffer.len() {
+            self.command_buffer = prefix;
+        } else if let Some(first) = candidates.first() {
+            self.command_buffer = first.clone();
+            self.completions = candidates;
+            self.completion_index = 0;
+        }
+    }
+
+    /// Handle keys in command mode
+    fn handle_command_key(&mut self, key: Key) -> io::Result<()> {
+        if key != Key::Tab {
+            self.completions.clear();
+        }
+
+        match key {
+            Key::Escape => {
                 self.mode = Mode::Normal;
             }
             Key::Enter => {
                 let cmd = self.command_buffer.clone();
+                self.command_history.push(&cmd);
                 self.mode = Mode::Normal;
                 self.execute_command(&cmd)?;
             }
+            Key::Tab => self.complete_command(),
+            Key::Up => {
+                if let Some(line) = self.command_history.older(&self.command_buffer) {
+                    self.command_buffer = line;
+                }
+            }
+            Key::Down => {
+                if let Some(line) = self.command_history.newer() {
+                    self.command_buffer = line;
+                }
+            }
             Key::Char(c) => {
                 self.command_buffer.push(c);
             }
+            Key::Paste(text) => self.command_buffer.extend(text.chars().filter(|&c| c != '\n')),
             Key::Backspace => {
                 self.command_buffer.pop();
                 if self.command_buffer.is_empty() {
@@ -318,20 +2195,43 @@ impl Editor {
     /// Handle keys in search mode
     fn handle_search_key(&mut self, key: Key) -> io::Result<()> {
         match key {
-            Key::Escape => {
-                self.mode = Mode::Normal;
-            }
+            Key::Escape => self.cancel_search(),
             Key::Enter => {
+                self.search_history.push(&self.search_buffer);
                 self.mode = Mode::Normal;
-                self.perform_search();
+                // Search again from where it started, so the jump is recorded from there
+                if let Some(origin) = self.search_origin.take() {
+                    (self.cursor_row, self.cursor_col) = origin.cursor;
+                }
+                self.record_jump();
+                self.search_in_direction();
+            }
+            Key::Up => {
+                if let Some(line) = self.search_history.older(&self.search_buffer) {
+                    self.search_buffer = line;
+                    self.preview_search();
+                }
+            }
+            Key::Down => {
+                if let Some(line) = self.search_history.newer() {
+                    self.search_buffer = line;
+                    self.preview_search();
+                }
             }
             Key::Char(c) => {
                 self.search_buffer.push(c);
+                self.preview_search();
+            }
+            Key::Paste(text) => {
+                self.search_buffer.extend(text.chars().filter(|&c| c != '\n'));
+                self.preview_search();
             }
             Key::Backspace => {
                 self.search_buffer.pop();
                 if self.search_buffer.is_empty() {
-                    self.mode = Mode::Normal;
+                    self.cancel_search();
+                } else {
+                    self.preview_search();
                 }
             }
             _ => {}
@@ -339,6 +2239,28 @@ impl Editor {
         Ok(())
     }
 
+    /// Move to the first match of the search typed so far, from where the search began
+    fn preview_search(&mut self) {
+        let Some(origin) = &self.search_origin else {
+            return;
+        };
+        (self.cursor_row, self.cursor_col) = origin.cursor;
+        self.scroll_row = origin.scroll_row;
+        self.search_in_direction();
+    }
+
+    /// Leave search mode, putting back the cursor, view and previous search
+    fn cancel_search(&mut self) {
+        if let Some(origin) = self.search_origin.take() {
+            (self.cursor_row, self.cursor_col) = origin.cursor;
+            self.scroll_row = origin.scroll_row;
+            self.search_buffer = origin.pattern;
+            self.highlight_search = origin.highlight;
+        }
+        self.message = None;
+        self.mode = Mode::Normal;
+    }
+
     /// Handle keys in browser mode
     fn handle_browser_key(&mut self, key: Key) -> io::Result<()> {
         if let Some(browser) = &mut self.browser {
@@ -353,21 +2275,49 @@ impl Editor {
                 Key::PageDown => browser.page_down(self.size.rows as usize - 4),
                 Key::Home | Key::Char('g') => browser.go_to_first(),
                 Key::End | Key::Char('G') => browser.go_to_last(),
-                Key::Left | Key::Char('h') | Key::Backspace => browser.go_up()?,
-                Key::Enter | Key::Right | Key::Char('l') => {
-                    if let Some(path) = browser.enter()? {
-                        self.buffer = Buffer::from_file(&path)?;
-                        self.highlighter = Highlighter::new(self.buffer.extension().as_deref());
-                        self.cursor_row = 0;
-                        self.cursor_col = 0;
-                        self.scroll_row = 0;
-                        self.scroll_col = 0;
-                        self.browser = None;
+                Key::Left | Key::Char('h') | Key::Backspace => browser.collapse_or_go_up()?,
+                Key::Enter | Key::Right | Key::Char('l') => match browser.enter() {
+                    Ok(Some(path)) => match self.open_in_new_buffer(&path) {
+                        Ok(()) => self.browser = None,
+                        Err(e) => self.message = Some(open_error(&path, &e)),
+                    },
+                    Ok(None) => {}
+                    Err(e) => self.message = Some(e.to_string()),
+                },
+                Key::Char('.') => browser.toggle_hidden()?,
+                Key::Char('r') => browser.refresh()?,
+                Key::Char('s') => browser.cycle_sort(),
+                Key::Char('S') => browser.toggle_sort_reverse(),
+                Key::Char('d') => browser.toggle_dirs_first(),
+                Key::Char('t') => browser.toggle_tree_view()?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle keys in the fuzzy file finder
+    fn handle_finder_key(&mut self, key: Key) -> io::Result<()> {
+        if let Some(finder) = &mut self.finder {
+            match key {
+                Key::Escape => {
+                    self.finder = None;
+                    self.mode = Mode::Normal;
+                }
+                Key::Enter => {
+                    if let Some(path) = finder.selected_path() {
+                        self.finder = None;
                         self.mode = Mode::Normal;
+                        if let Err(e) = self.open_in_new_buffer(&path) {
+                            self.message = Some(open_error(&path, &e));
+                        }
                     }
                 }
-                Key::Char('.') => browser.toggle_hidden()?,
-                Key::Char('r') => browser.refresh()?,
+                Key::Up | Key::Ctrl('p') | Key::Ctrl('k') => finder.move_up(),
+                Key::Down | Key::Ctrl('n') | Key::Ctrl('j') => finder.move_down(),
+                Key::Backspace => finder.pop_char(),
+                Key::Char(c) => finder.push_char(c),
+                Key::Paste(text) => finder.push_str(&text.replace('\n', "")),
                 _ => {}
             }
         }
@@ -376,57 +2326,345 @@ impl Editor {
 
     /// Execute a command
     fn execute_command(&mut self, cmd: &str) -> io::Result<()> {
+        if let Some((invert, pattern, action)) = parse_global_command(cmd.trim()) {
+            if self.refuse_if_readonly() {
+                // Nothing to do
+            } else if action != "d" {
+                self.message = Some(format!("Unsupported global command: {}", action));
+            } else if pattern.is_empty() {
+                self.message = Some("Empty pattern".to_string());
+            } else {
+                let removed = self.buffer.delete_lines_matching(pattern, invert);
+                self.message = Some(format!("{} lines removed", removed));
+                self.clamp_cursor();
+            }
+            return Ok(());
+        }
+
+        if let Some(sub) = parse_substitute_command(cmd.trim()) {
+            let global = sub.flags.contains('g');
+            let ignore_case = sub.flags.contains('i');
+            if self.refuse_if_readonly() {
+                // Nothing to do
+            } else if let Some(flag) = sub.flags.chars().find(|f| !matches!(f, 'g' | 'i')) {
+                self.message = Some(format!("Unknown substitute flag: {}", flag));
+            } else if sub.pattern.is_empty() {
+                self.message = Some("Empty pattern".to_string());
+            } else {
+                match self.compile_pattern(sub.pattern, ignore_case) {
+                    Ok(pattern) => {
+                        let count = if sub.whole_buffer {
+                            self.buffer.replace_all(&pattern, sub.replacement, global)
+                        } else {
+                            self.buffer.replace_in_line(self.cursor_row, &pattern, sub.replacement, global)
+                        };
+                        self.message = Some(match count {
+                            0 => format!("Pattern not found: {}", sub.pattern),
+                            1 => "1 replacement".to_string(),
+                            n => format!("{} replacements", n),
+                        });
+                        self.clamp_cursor();
+                    }
+                    Err(e) => self.message = Some(format!("Invalid pattern: {}", e)),
+                }
+            }
+            return Ok(());
+        }
+
         let parts: Vec<&str> = cmd.trim().split_whitespace().collect();
 
         match parts.as_slice() {
-            ["q"] | ["quit"] => {
-                if self.buffer.modified {
-                    self.message = Some("Unsaved changes! Use :q! to force quit".to_string());
-                } else {
-                    self.quit = true;
-                }
+            ["q"] | ["quit"] | ["q!"] | ["quit!"] | ["close"] if self.windows.len() > 1 => {
+                self.close_window();
             }
+            ["q"] | ["quit"] => self.request_quit(),
             ["q!"] | ["quit!"] => {
                 self.quit = true;
             }
+            ["sp"] | ["split"] => {
+                self.split_window();
+            }
+            ["sp", path] | ["split", path] => {
+                let path = expand_tilde(path);
+                if self.split_window()
+                    && let Err(e) = self.open_in_new_buffer(&path)
+                {
+                    self.message = Some(open_error(&path, &e));
+                }
+            }
+            ["only"] => {
+                self.windows = vec![self.current_view()];
+                self.current_window = 0;
+            }
             ["w"] | ["write"] => {
                 self.save_file()?;
             }
             ["w", path] | ["write", path] => {
-                self.buffer.save_as(PathBuf::from(path))?;
-                self.message = Some(format!("Saved to {}", path));
+                let path = expand_tilde(path);
+                if path.exists() && self.buffer.path.as_ref() != Some(&path) {
+                    self.ask(
+                        format!("\"{}\" exists. Overwrite?", path.display()),
+                        ConfirmAction::Overwrite(path),
+                    );
+                } else {
+                    self.buffer.save_as(path.clone())?;
+                    self.message = Some(format!("Saved to {}", path.display()));
+                }
+            }
+            ["w!"] | ["write!"] => {
+                self.force_save_file(None);
+            }
+            ["w!", path] | ["write!", path] => {
+                self.force_save_file(Some(expand_tilde(path)));
             }
             ["wq"] => {
+                // A refused save leaves this buffer modified, so it asks like any other
                 self.save_file()?;
-                self.quit = true;
+                self.request_quit();
             }
+            ["view", path] => self.open_readonly(&expand_tilde(path)),
             ["e", path] | ["edit", path] => {
-                let path = PathBuf::from(path);
+                let path = expand_tilde(path);
                 if path.is_dir() {
                     self.browser = Some(Browser::new(&path)?);
                     self.mode = Mode::Browser;
-                } else {
-                    self.open(&path)?;
+                } else if let Err(e) = self.open_in_new_buffer(&path) {
+                    self.message = Some(open_error(&path, &e));
                 }
             }
+            ["bn"] | ["bnext"] => self.cycle_buffer(true),
+            ["bp"] | ["bprev"] | ["bprevious"] => self.cycle_buffer(false),
+            ["b", n] | ["buffer", n] => match n.parse::<usize>() {
+                Ok(n) if (1..=self.buffers.len()).contains(&n) => {
+                    self.switch_buffer(n - 1);
+                    self.show_buffer_info();
+                }
+                _ => self.message = Some(format!("No buffer {}", n)),
+            },
+            ["ls"] | ["buffers"] => self.message = Some(self.buffer_list()),
+            ["e!"] | ["edit!"] => self.reload(),
             ["e"] | ["edit"] => {
-                let dir = self
-                    .buffer
-                    .path
-                    .as_ref()
-                    .and_then(|p| p.parent())
-                    .map(|p| p.to_path_buf())
-                    .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
-                self.browser = Some(Browser::new(&dir)?);
+                self.browser = Some(Browser::new(&std::env::current_dir()?)?);
                 self.mode = Mode::Browser;
             }
+            ["pwd"] => {
+                self.message = Some(std::env::current_dir()?.display().to_string());
+            }
+            ["cd"] => self.change_directory(&expand_tilde("~")),
+            ["cd", path] => self.change_directory(&expand_tilde(path)),
+            ["$"] => {
+                self.record_jump();
+                self.cursor_row = self.buffer.line_count() - 1;
+                self.cursor_col = 0;
+            }
             [num] if num.parse::<usize>().is_ok() => {
                 let line = num.parse::<usize>().unwrap();
+                self.record_jump();
                 self.cursor_row = line.saturating_sub(1).min(self.buffer.line_count() - 1);
                 self.cursor_col = 0;
             }
+            [pct] if pct.strip_suffix('%').is_some_and(|n| n.parse::<usize>().is_ok()) => {
+                let pct = pct.trim_end_matches('%').parse::<usize>().unwrap().min(100);
+                // Same rounding as vim: line = (pct * count + 99) / 100
+                let line = (pct * self.buffer.line_count()).div_ceil(100);
+                self.record_jump();
+                self.cursor_row = line.saturating_sub(1);
+                self.cursor_col = 0;
+            }
+            [sort, ..] if parse_sort_command(sort).is_some() && self.refuse_if_readonly() => {}
+            [sort, flags @ ..] if parse_sort_command(sort).is_some() => {
+                let (range, reverse) = parse_sort_command(sort).unwrap();
+                let mut options = SortOptions {
+                    reverse,
+                    ..Default::default()
+                };
+                for flag in flags.iter().flat_map(|f| f.chars()) {
+                    match flag {
+                        'u' => options.unique = true,
+                        'i' => options.ignore_case = true,
+                        _ => {
+                            self.message = Some(format!("Unknown sort flag: {}", flag));
+                            return Ok(());
+                        }
+                    }
+                }
+                let (start, end) = range.unwrap_or((0, self.buffer.line_count()));
+                let end = end.min(self.buffer.line_count());
+                if start >= end {
+                    self.message = Some("Invalid range".to_string());
+                    return Ok(());
+                }
+                let line_count = end - start;
+                let removed = self.buffer.sort_lines(start, end, options);
+                self.message = Some(if removed > 0 {
+                    format!("Sorted {} lines, removed {} duplicates", line_count, removed)
+                } else {
+                    format!("Sorted {} lines", line_count)
+                });
+                self.clamp_cursor();
+            }
+            ["noh"] | ["nohlsearch"] => self.highlight_search = false,
+            ["colorscheme"] | ["colo"] => {
+                self.message = Some(self.theme.name.clone());
+            }
+            ["colorscheme", "custom"] | ["colo", "custom"] => match Theme::load_user() {
+                Ok(Some(theme)) => self.theme = theme,
+                Ok(None) => self.message = Some("No theme file".to_string()),
+                Err(e) => self.message = Some(format!("Error in theme file: {}", e)),
+            },
+            ["colorscheme", name] | ["colo", name] => match Theme::named(name, self.truecolor) {
+                Some(theme) => self.theme = theme,
+                None => {
+                    self.message = Some(format!(
+                        "Unknown color scheme: {} (try {} or custom)",
+                        name,
+                        BUILTIN_THEMES.join(", ")
+                    ))
+                }
+            },
             ["set", "number"] | ["set", "nu"] => {
-                self.message = Some("Line numbers enabled".to_string());
+                self.show_line_numbers = true;
+            }
+            ["set", "nonumber"] | ["set", "nonu"] => {
+                self.show_line_numbers = false;
+            }
+            ["set", "relativenumber"] | ["set", "rnu"] => {
+                self.relative_numbers = true;
+            }
+            ["set", "norelativenumber"] | ["set", "nornu"] => {
+                self.relative_numbers = false;
+            }
+            ["set", "readonly"] | ["set", "ro"] => {
+                self.buffer.readonly = true;
+            }
+            ["set", "noreadonly"] | ["set", "noro"] => {
+                self.buffer.readonly = false;
+            }
+            ["set", "list"] => {
+                self.show_whitespace = true;
+            }
+            ["set", "nolist"] => {
+                self.show_whitespace = false;
+            }
+            ["set", "cursorline"] | ["set", "cul"] => {
+                self.cursor_line = true;
+            }
+            ["set", "nocursorline"] | ["set", "nocul"] => {
+                self.cursor_line = false;
+            }
+            ["set", "rainbow"] => {
+                self.rainbow_brackets = true;
+            }
+            ["set", "norainbow"] => {
+                self.rainbow_brackets = false;
+            }
+            ["set", "ft"] | ["set", "filetype"] => {
+                let name = self.highlighter.language.map_or("none", |lang| lang.name);
+                self.message = Some(format!("filetype={}", name));
+            }
+            ["set", opt] if opt.starts_with("ft=") || opt.starts_with("filetype=") => {
+                let (_, name) = opt.split_once('=').unwrap();
+                if name.is_empty() || name == "none" {
+                    self.highlighter.language = None;
+                } else if let Some(lang) = find_language(name) {
+                    self.highlighter.language = Some(lang);
+                } else {
+                    self.message = Some(format!("Unknown filetype: {}", name));
+                }
+            }
+            ["set", "ff"] | ["set", "fileformat"] => {
+                self.message = Some(format!("fileformat={}", self.buffer.line_ending.name()));
+            }
+            ["set", opt] if opt.starts_with("ff=") || opt.starts_with("fileformat=") => {
+                let (_, name) = opt.split_once('=').unwrap();
+                match LineEnding::from_name(name) {
+                    None => self.message = Some(format!("Unknown fileformat: {}", name)),
+                    Some(ending) if ending == self.buffer.line_ending => {}
+                    Some(_) if self.refuse_if_readonly() => {}
+                    Some(ending) => {
+                        self.buffer.line_ending = ending;
+                        self.buffer.modified = true;
+                    }
+                }
+            }
+            ["set", "fixeol"] | ["set", "fixendofline"] => {
+                self.buffer.fix_eol = true;
+            }
+            ["set", "nofixeol"] | ["set", "nofixendofline"] => {
+                self.buffer.fix_eol = false;
+            }
+            ["set", "trimtrailing"] => {
+                self.buffer.trim_trailing = true;
+            }
+            ["set", "notrimtrailing"] => {
+                self.buffer.trim_trailing = false;
+            }
+            ["set", "clipboard=unnamed"] | ["set", "clipboard=unnamedplus"] => {
+                self.clipboard = true;
+            }
+            ["set", "clipboard="] => {
+                self.clipboard = false;
+            }
+            ["set", "autoindent"] | ["set", "ai"] => {
+                self.auto_indent = true;
+            }
+            ["set", "noautoindent"] | ["set", "noai"] => {
+                self.auto_indent = false;
+            }
+            ["set", "sw"] | ["set", "shiftwidth"] => {
+                self.message = Some(format!("shiftwidth={}", self.shift_width));
+            }
+            ["set", opt] if opt.starts_with("sw=") || opt.starts_with("shiftwidth=") => {
+                let (_, value) = opt.split_once('=').unwrap();
+                match value.parse::<usize>() {
+                    Ok(width) if width > 0 => self.shift_width = width,
+                    _ => self.message = Some(format!("Invalid shiftwidth: {}", value)),
+                }
+            }
+            ["set", "ts"] | ["set", "tabstop"] => {
+                self.message = Some(format!("tabstop={}", self.tab_stop));
+            }
+            ["set", opt] if opt.starts_with("ts=") || opt.starts_with("tabstop=") => {
+                let (_, value) = opt.split_once('=').unwrap();
+                match value.parse::<usize>() {
+                    Ok(width) if width > 0 => self.tab_stop = width,
+                    _ => self.message = Some(format!("Invalid tabstop: {}", value)),
+                }
+            }
+            ["set", "cc"] | ["set", "colorcolumn"] => {
+                self.message = Some(format!("colorcolumn={}", self.color_column));
+            }
+            ["set", opt] if opt.starts_with("cc=") || opt.starts_with("colorcolumn=") => {
+                let (_, value) = opt.split_once('=').unwrap();
+                match value.parse::<usize>() {
+                    Ok(column) => self.color_column = column,
+                    Err(_) => self.message = Some(format!("Invalid colorcolumn: {}", value)),
+                }
+            }
+            ["set", "expandtab"] | ["set", "et"] => {
+                self.expand_tab = true;
+            }
+            ["set", "noexpandtab"] | ["set", "noet"] => {
+                self.expand_tab = false;
+            }
+            ["set", "regex"] => {
+                self.use_regex = true;
+            }
+            ["set", "noregex"] => {
+                self.use_regex = false;
+            }
+            ["set", "autopairs"] => {
+                self.auto_pairs = true;
+            }
+            ["set", "noautopairs"] => {
+                self.auto_pairs = false;
+            }
+            ["set", "swapfile"] => {
+                self.swap_file = true;
+            }
+            ["set", "noswapfile"] => {
+                self.swap_file = false;
+                self.buffer.remove_swap();
             }
             ["help"] | ["h"] => {
                 self.message = Some("Commands: :w :q :wq :e <file> :<num>".to_string());
@@ -441,90 +2679,167 @@ impl Editor {
 
     /// Save the current file
     fn save_file(&mut self) -> io::Result<()> {
-        if self.buffer.path.is_some() {
+        if self.buffer.readonly {
+            self.message = Some("Buffer is read-only. Use :w! to save anyway".to_string());
+        } else if self.buffer.path.is_some() {
             self.buffer.save()?;
             self.message = Some("File saved".to_string());
+            self.remember_positions();
         } else {
             self.message = Some("No filename. Use :w <filename>".to_string());
         }
         Ok(())
     }
 
+    /// Save with `:w!`, overriding read-only and creating parent directories
+    fn force_save_file(&mut self, path: Option<PathBuf>) {
+        if path.is_none() && self.buffer.path.is_none() {
+            self.message = Some("No filename. Use :w <filename>".to_string());
+            return;
+        }
+        let previous = self.buffer.path.clone();
+        if let Some(path) = path {
+            self.buffer.path = Some(path);
+        }
+
+        let name = self.buffer.path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+        self.message = Some(match self.buffer.force_save() {
+            Ok(()) => format!("Saved to {}", name),
+            Err(e) => {
+                self.buffer.path = previous;
+                format!("Could not save {}: {}", name, e)
+            }
+        });
+    }
+
     /// Perform search
     fn perform_search(&mut self) {
         if self.search_buffer.is_empty() {
             return;
         }
+        let Some(pattern) = self.search_pattern() else {
+            return;
+        };
+        self.highlight_search = true;
 
         let start_row = self.cursor_row;
         let start_col = self.cursor_col + 1;
 
         // Search forward from cursor
         for row in start_row..self.buffer.line_count() {
-            if let Some(line) = self.buffer.line(row) {
-                let line_str = line.to_string();
-                let search_start = if row == start_row { start_col } else { 0 };
-                if search_start < line_str.len() {
-                    if let Some(pos) = line_str[search_start..].find(&self.search_buffer) {
-                        self.cursor_row = row;
-                        self.cursor_col = search_start + pos;
-                        return;
-                    }
-                }
+            let from = if row == start_row { start_col } else { 0 };
+            if let Some(col) = self.next_match(&pattern, row, from) {
+                (self.cursor_row, self.cursor_col) = (row, col);
+                self.report_match(&pattern, false);
+                return;
             }
         }
 
         // Wrap around
         for row in 0..=start_row {
-            if let Some(line) = self.buffer.line(row) {
-                let line_str = line.to_string();
-                if let Some(pos) = line_str.find(&self.search_buffer) {
-                    self.cursor_row = row;
-                    self.cursor_col = pos;
-                    self.message = Some("Search wrapped".to_string());
-                    return;
-                }
+            if let Some(col) = self.next_match(&pattern, row, 0) {
+                (self.cursor_row, self.cursor_col) = (row, col);
+                self.report_match(&pattern, true);
+                return;
             }
         }
 
         self.message = Some(format!("Pattern not found: {}", self.search_buffer));
     }
 
+    /// The search as typed, or `None` after reporting why it isn't a valid pattern
+    fn search_pattern(&mut self) -> Option<Regex> {
+        let pattern = self.search_regex().clone();
+        match pattern {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                self.message = Some(format!("Invalid pattern: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Compile a search or `:s` pattern: a regex with `:set regex`, otherwise plain text
+    fn compile_pattern(&self, pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+        if self.use_regex {
+            Regex::new(pattern, ignore_case)
+        } else {
+            Ok(Regex::literal(pattern, ignore_case))
+        }
+    }
+
+    /// Column of the first non-empty match of `pattern` on `row` starting at or after `from`
+    fn next_match(&self, pattern: &Regex, row: usize, from: usize) -> Option<usize> {
+        let line = self.buffer.line(row)?;
+        pattern.find_non_empty_at(&line.chars, from).map(|m| m.start)
+    }
+
+    /// Column of the last non-empty match of `pattern` on `row` starting before `end`
+    fn prev_match(&self, pattern: &Regex, row: usize, end: usize) -> Option<usize> {
+        let line = self.buffer.line(row)?;
+        pattern.find_iter(&line.chars).into_iter().map(|(start, _)| start).take_while(|&start| start < end).last()
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.buffer.line(row).map_or(0, |line| line.len())
+    }
+
+    /// Show which match the cursor is on, e.g. "Match 3 of 17"
+    fn report_match(&mut self, pattern: &Regex, wrapped: bool) {
+        let total = self.buffer.count_matches(pattern);
+        let cursor = (self.cursor_row, self.cursor_col);
+        let index = self
+            .buffer
+            .find_all(pattern)
+            .iter()
+            .take_while(|&&pos| pos < cursor)
+            .count()
+            + 1;
+        let wrapped = if wrapped { " (search wrapped)" } else { "" };
+        self.message = Some(format!("Match {} of {}{}", index, total, wrapped));
+    }
+
     fn search_next(&mut self) {
         self.perform_search();
     }
 
+    /// Search forward after `/`, backward after `?`
+    fn search_in_direction(&mut self) {
+        if self.search_direction > 0 {
+            self.perform_search();
+        } else {
+            self.search_prev();
+        }
+    }
+
     fn search_prev(&mut self) {
         if self.search_buffer.is_empty() {
             return;
         }
+        let Some(pattern) = self.search_pattern() else {
+            return;
+        };
+        self.highlight_search = true;
 
         let start_row = self.cursor_row;
         let start_col = self.cursor_col;
 
         // Search backward from cursor
         for row in (0..=start_row).rev() {
-            if let Some(line) = self.buffer.line(row) {
-                let line_str = line.to_string();
-                let search_end = if row == start_row { start_col } else { line_str.len() };
-                if let Some(pos) = line_str[..search_end].rfind(&self.search_buffer) {
-                    self.cursor_row = row;
-                    self.cursor_col = pos;
-                    return;
-                }
+            let end = if row == start_row { start_col } else { self.line_len(row) };
+            if let Some(col) = self.prev_match(&pattern, row, end) {
+                (self.cursor_row, self.cursor_col) = (row, col);
+                self.report_match(&pattern, false);
+                return;
             }
         }
 
         // Wrap around
         for row in (start_row..self.buffer.line_count()).rev() {
-            if let Some(line) = self.buffer.line(row) {
-                let line_str = line.to_string();
-                if let Some(pos) = line_str.rfind(&self.search_buffer) {
-                    self.cursor_row = row;
-                    self.cursor_col = pos;
-                    self.message = Some("Search wrapped".to_string());
-                    return;
-                }
+            if let Some(col) = self.prev_match(&pattern, row, self.line_len(row)) {
+                (self.cursor_row, self.cursor_col) = (row, col);
+                self.report_match(&pattern, true);
+                return;
             }
         }
 
@@ -573,57 +2888,138 @@ impl Editor {
         }
     }
 
-    fn move_word_forward(&mut self) {
-        if let Some(line) = self.buffer.line(self.cursor_row) {
-            let chars: Vec<char> = line.chars.clone();
-            let mut col = self.cursor_col;
+    /// Word class of the character at a position; line ends count as whitespace
+    fn class_at(&self, (row, col): Pos, big_word: bool) -> u8 {
+        self.buffer
+            .line(row)
+            .and_then(|l| l.chars.get(col))
+            .map_or(0, |&c| textobject::char_class(c, big_word))
+    }
 
-            // Skip current word
-            while col < chars.len() && chars[col].is_alphanumeric() {
-                col += 1;
-            }
-            // Skip whitespace
-            while col < chars.len() && !chars[col].is_alphanumeric() {
-                col += 1;
-            }
+    fn line_is_empty(&self, row: usize) -> bool {
+        self.buffer.line(row).is_none_or(|l| l.is_empty())
+    }
 
-            if col >= chars.len() && self.cursor_row + 1 < self.buffer.line_count() {
-                self.cursor_row += 1;
-                self.cursor_col = 0;
-            } else {
-                self.cursor_col = col.min(chars.len().saturating_sub(1));
-            }
+    /// Next character position, stepping onto the following line at the end
+    fn next_pos(&self, (row, col): Pos) -> Option<Pos> {
+        let len = self.buffer.line(row)?.len();
+        if col + 1 < len {
+            Some((row, col + 1))
+        } else if row + 1 < self.buffer.line_count() {
+            Some((row + 1, 0))
+        } else {
+            None
         }
     }
 
-    fn move_word_backward(&mut self) {
-        if let Some(line) = self.buffer.line(self.cursor_row) {
-            let chars: Vec<char> = line.chars.clone();
-            let mut col = self.cursor_col;
+    /// Previous character position, stepping onto the end of the previous line
+    fn prev_pos(&self, (row, col): Pos) -> Option<Pos> {
+        if col > 0 {
+            Some((row, col - 1))
+        } else if row > 0 {
+            let len = self.buffer.line(row - 1)?.len();
+            Some((row - 1, len.saturating_sub(1)))
+        } else {
+            None
+        }
+    }
 
-            if col == 0 && self.cursor_row > 0 {
-                self.cursor_row -= 1;
-                if let Some(prev_line) = self.buffer.line(self.cursor_row) {
-                    self.cursor_col = prev_line.len().saturating_sub(1);
-                }
-                return;
+    /// `w`/`W`: start of the next word or punctuation run (`W`: next blank-separated word)
+    fn move_word_forward(&mut self, big_word: bool) {
+        let mut pos = (self.cursor_row, self.cursor_col);
+        let mut class = self.class_at(pos, big_word);
+
+        while let Some(next) = self.next_pos(pos) {
+            let new_line = next.0 != pos.0;
+            pos = next;
+            let next_class = self.class_at(pos, big_word);
+            // Empty lines count as words
+            if new_line && self.line_is_empty(pos.0) {
+                break;
+            }
+            if next_class != 0 && (next_class != class || new_line) {
+                break;
             }
+            if next_class == 0 {
+                class = 0;
+            }
+        }
+
+        (self.cursor_row, self.cursor_col) = pos;
+    }
 
-            // Skip whitespace
-            while col > 0 && !chars[col - 1].is_alphanumeric() {
-                col -= 1;
+    /// `b`/`B`: start of the current or previous word
+    fn move_word_backward(&mut self, big_word: bool) {
+        let Some(mut pos) = self.prev_pos((self.cursor_row, self.cursor_col)) else {
+            return;
+        };
+
+        // Skip whitespace, stopping on empty lines
+        while self.class_at(pos, big_word) == 0 && !self.line_is_empty(pos.0) {
+            match self.prev_pos(pos) {
+                Some(prev) => pos = prev,
+                None => break,
             }
-            // Skip word
-            while col > 0 && chars[col - 1].is_alphanumeric() {
-                col -= 1;
+        }
+
+        let class = self.class_at(pos, big_word);
+        if class != 0 {
+            while pos.1 > 0 && self.class_at((pos.0, pos.1 - 1), big_word) == class {
+                pos.1 -= 1;
             }
+        }
 
-            self.cursor_col = col;
+        (self.cursor_row, self.cursor_col) = pos;
+    }
+
+    /// `e`/`E`: end of the current or next word
+    fn move_word_end(&mut self, big_word: bool) {
+        let Some(mut pos) = self.next_pos((self.cursor_row, self.cursor_col)) else {
+            return;
+        };
+
+        while self.class_at(pos, big_word) == 0 {
+            match self.next_pos(pos) {
+                Some(next) => pos = next,
+                None => break,
+            }
+        }
+
+        let class = self.class_at(pos, big_word);
+        while let Some(next) = self.next_pos(pos)
+            && next.0 == pos.0
+            && self.class_at(next, big_word) == class
+        {
+            pos = next;
+        }
+
+        (self.cursor_row, self.cursor_col) = pos;
+    }
+
+    /// Text rows in the focused window
+    fn content_height(&self) -> usize {
+        self.window_layout()[self.current_window].1
+    }
+
+    /// Scroll the view down a line, dragging the cursor along if it leaves the screen
+    fn scroll_lines_down(&mut self) {
+        if self.scroll_row + 1 < self.buffer.line_count() {
+            self.scroll_row += 1;
+            self.cursor_row = self.cursor_row.max(self.scroll_row);
+        }
+    }
+
+    /// Scroll the view up a line, dragging the cursor along if it leaves the screen
+    fn scroll_lines_up(&mut self) {
+        if self.scroll_row > 0 {
+            self.scroll_row -= 1;
+            let bottom = self.scroll_row + self.content_height().saturating_sub(1);
+            self.cursor_row = self.cursor_row.min(bottom);
         }
     }
 
     fn page_up(&mut self) {
-        let page_size = (self.size.rows as usize).saturating_sub(4);
+        let page_size = self.content_height().saturating_sub(2);
         if self.cursor_row > page_size {
             self.cursor_row -= page_size;
         } else {
@@ -632,7 +3028,7 @@ impl Editor {
     }
 
     fn page_down(&mut self) {
-        let page_size = (self.size.rows as usize).saturating_sub(4);
+        let page_size = self.content_height().saturating_sub(2);
         self.cursor_row = (self.cursor_row + page_size).min(self.buffer.line_count() - 1);
     }
 
@@ -663,6 +3059,8 @@ impl Editor {
 
         if self.mode == Mode::Browser {
             self.draw_browser(&mut output)?;
+        } else if self.mode == Mode::Finder {
+            self.draw_finder(&mut output)?;
         } else {
             self.draw_editor(&mut output)?;
         }
@@ -673,9 +3071,45 @@ impl Editor {
     }
 
     fn draw_editor(&mut self, output: &mut String) -> io::Result<()> {
-        let content_height = self.size.rows.saturating_sub(2) as usize;
+        self.fit_windows();
+        let layout = self.window_layout();
+
+        // Draw the other windows by briefly giving each one focus
+        let focused = self.current_window;
+        for (idx, &(top, height)) in layout.iter().enumerate() {
+            if idx != focused {
+                self.focus_window(idx);
+                self.draw_window(output, top, height, false)?;
+            }
+        }
+        self.focus_window(focused);
+        let (top, height) = layout[focused];
+        self.draw_window(output, top, height, true)?;
+
+        // Draw command/message line
+        self.draw_command_line(output, self.size.rows.saturating_sub(1))?;
+
+        // Position cursor
+        // A terminal too short for any text leaves the cursor on the status line
+        let cursor_screen_row = (top + self.cursor_row).saturating_sub(self.scroll_row) as u16;
+        let cursor_screen_col = (self.cursor_visual_col() + self.gutter_width()).saturating_sub(self.scroll_col) as u16;
+        output.push_str(&ansi::cursor_position(cursor_screen_row, cursor_screen_col));
+        output.push_str(ansi::CURSOR_SHOW);
+
+        Ok(())
+    }
+
+    /// Draw the focused window's text in `content_height` rows from screen row `top`,
+    /// followed by its status line
+    fn draw_window(
+        &mut self,
+        output: &mut String,
+        top: usize,
+        content_height: usize,
+        focused: bool,
+    ) -> io::Result<()> {
         let content_width = self.size.cols as usize;
-        let gutter_width = 5; // Line numbers
+        let gutter_width = self.gutter_width();
 
         // Update scroll
         self.update_scroll(content_height);
@@ -686,49 +3120,148 @@ impl Editor {
         for row in 0..self.scroll_row {
             if let Some(line) = self.buffer.line(row) {
                 let line_str = line.to_string();
-                self.highlighter.highlight_line(&line_str, &mut highlight_state);
+                let mut tokens = self.highlighter.highlight_line(&line_str, &mut highlight_state);
+                if self.rainbow_brackets {
+                    colorize_brackets(&mut tokens, &mut highlight_state);
+                }
             }
         }
 
+        // Screen column of `:set colorcolumn`, counted from the start of the line
+        let ruler = self.color_column.checked_sub(1);
+        let ruler_bg = self.theme.color_column.bg();
+
         // Draw content lines
         for screen_row in 0..content_height {
             let file_row = self.scroll_row + screen_row;
 
             // Move to line start
-            output.push_str(&ansi::cursor_position(screen_row as u16, 0));
+            output.push_str(&ansi::cursor_position((top + screen_row) as u16, 0));
             output.push_str(ansi::CLEAR_LINE);
 
             if file_row < self.buffer.line_count() {
+                // Background for the whole cursor line, restored after every reset
+                let line_bg = if self.cursor_line && file_row == self.cursor_row {
+                    self.theme.cursor_line.bg()
+                } else {
+                    String::new()
+                };
+                output.push_str(&line_bg);
+
                 // Line number
-                output.push_str(ansi::FG_BRIGHT_BLACK);
-                output.push_str(&format!("{:>4} ", file_row + 1));
-                output.push_str(ansi::RESET);
+                if gutter_width > 0 {
+                    output.push_str(&self.theme.line_number.fg());
+                    output.push_str(&format!("{:>1$} ", self.line_number(file_row), gutter_width - 1));
+                    output.push_str(ansi::RESET);
+                    output.push_str(&line_bg);
+                }
 
                 // Line content
                 if let Some(line) = self.buffer.line(file_row) {
                     let line_str = line.to_string();
-                    let tokens = self.highlighter.highlight_line(&line_str, &mut highlight_state);
+                    let mut tokens = self.highlighter.highlight_line(&line_str, &mut highlight_state);
+                    if self.rainbow_brackets {
+                        colorize_brackets(&mut tokens, &mut highlight_state);
+                    }
+
+                    // Char positions where indentation ends and trailing whitespace begins
+                    let indent_end = line_str.chars().take_while(|c| c.is_whitespace()).count();
+                    let trailing_start = line_str.trim_end().chars().count();
+
+                    let selection = if focused { self.selected_columns(file_row) } else { None };
+                    let matches = self.search_matches(file_row);
 
-                    let mut col = 0;
+                    // Visible screen columns, with tabs expanded to the next tab stop
                     let visible_start = self.scroll_col;
                     let visible_end = visible_start + content_width - gutter_width;
+                    let mut pos = 0;
+                    let mut vcol = 0;
 
                     for token in tokens {
-                        let token_start = col;
-                        let token_end = col + token.text.len();
+                        let style = self.theme.style(token.token_type);
+                        output.push_str(&style);
 
-                        if token_end > visible_start && token_start < visible_end {
-                            output.push_str(token.token_type.color());
+                        for ch in token.text.chars() {
+                            let width = display_width(ch, vcol, self.tab_stop);
+                            let cells = vcol.max(visible_start)..(vcol + width).min(visible_end);
+                            if !cells.is_empty() {
+                                let selected = selection.is_some_and(|(from, to)| (from..to).contains(&pos));
+                                let found = matches.iter().find(|(from, to)| (*from..*to).contains(&pos));
+                                if let Some(&(from, _)) = found {
+                                    // The match under the cursor stands out from the rest
+                                    let current = file_row == self.cursor_row && from == self.cursor_col;
+                                    output.push_str(if current { ansi::BG_MAGENTA } else { ansi::BG_YELLOW });
+                                    output.push_str(ansi::FG_BLACK);
+                                }
+                                if selected {
+                                    output.push_str(ansi::REVERSE);
+                                }
+                                let on_ruler = ruler.is_some_and(|c| (vcol..vcol + width).contains(&c));
+                                let (glyph, background) = if self.show_whitespace && pos >= trailing_start {
+                                    (ch, Some(ansi::BG_RED))
+                                } else if self.show_whitespace && ch == '\t' && pos < indent_end {
+                                    ('»', Some(ansi::BG_BLUE))
+                                } else if on_ruler && found.is_none() && !selected {
+                                    (ch, Some(ruler_bg.as_str()))
+                                } else {
+                                    (ch, None)
+                                };
+                                if let Some(background) = background {
+                                    output.push_str(background);
+                                }
+                                if ch == '\t' {
+                                    // A tab fills its cells with spaces, after any marker
+                                    for cell in cells {
+                                        output.push(if cell == vcol && glyph != '\t' { glyph } else { ' ' });
+                                    }
+                                } else if cells.len() < width {
+                                    // Part of a wide char cut off at the edge of the view
+                                    output.extend(cells.map(|_| ' '));
+                                } else {
+                                    output.push(glyph);
+                                }
+                                if background.is_some() {
+                                    output.push_str(if line_bg.is_empty() { ansi::BG_DEFAULT } else { &line_bg });
+                                }
+                                if selected {
+                                    output.push_str(ansi::REVERSE_OFF);
+                                }
+                                if found.is_some() {
+                                    output.push_str(ansi::RESET);
+                                    output.push_str(&line_bg);
+                                    output.push_str(&style);
+                                }
+                            }
+                            pos += 1;
+                            vcol += width;
+                        }
+                        output.push_str(ansi::RESET);
+                        output.push_str(&line_bg);
+                    }
+                    let mut drawn = vcol.clamp(visible_start, visible_end) - visible_start;
 
-                            let start = token_start.max(visible_start) - token_start;
-                            let end = token_end.min(visible_end) - token_start;
+                    // Show a selected empty line as one highlighted cell
+                    if line.is_empty() && selection.is_some() && visible_start == 0 {
+                        output.push_str(ansi::REVERSE);
+                        output.push(' ');
+                        output.push_str(ansi::RESET);
+                        output.push_str(&line_bg);
+                        drawn += 1;
+                    }
 
-                            let visible_text: String = token.text.chars().skip(start).take(end - start).collect();
-                            output.push_str(&visible_text);
-                            output.push_str(ansi::RESET);
-                        }
+                    // Mark the color column past the end of the line
+                    if let Some(column) = ruler.filter(|c| (visible_start + drawn..visible_end).contains(c)) {
+                        output.push_str(&" ".repeat(column - visible_start - drawn));
+                        output.push_str(&ruler_bg);
+                        output.push(' ');
+                        output.push_str(if line_bg.is_empty() { ansi::BG_DEFAULT } else { &line_bg });
+                        drawn = column - visible_start + 1;
+                    }
 
-                        col = token_end;
+                    // Carry the cursor line background to the edge of the window
+                    if !line_bg.is_empty() {
+                        output.push_str(&" ".repeat((visible_end - visible_start).saturating_sub(drawn)));
+                        output.push_str(ansi::RESET);
                     }
                 }
             } else {
@@ -740,16 +3273,7 @@ impl Editor {
         }
 
         // Draw status line
-        self.draw_status_line(output, content_height as u16)?;
-
-        // Draw command/message line
-        self.draw_command_line(output, content_height as u16 + 1)?;
-
-        // Position cursor
-        let cursor_screen_row = (self.cursor_row - self.scroll_row) as u16;
-        let cursor_screen_col = (self.cursor_col - self.scroll_col + gutter_width) as u16;
-        output.push_str(&ansi::cursor_position(cursor_screen_row, cursor_screen_col));
-        output.push_str(ansi::CURSOR_SHOW);
+        self.draw_status_line(output, (top + content_height) as u16, focused)?;
 
         Ok(())
     }
@@ -770,6 +3294,17 @@ impl Editor {
             ));
             output.push_str(ansi::RESET);
 
+            // Narrow terminals drop the permissions and date, then the size, so rows
+            // never reach the last column and wrap; the name column takes what is left
+            let cols = self.size.cols as usize;
+            let show_details = cols >= BROWSER_MIN_NAME + 42;
+            let show_size = cols >= BROWSER_MIN_NAME + 13;
+            let max_name_len = cols.saturating_sub(match (show_details, show_size) {
+                (true, _) => 42,
+                (false, true) => 13,
+                (false, false) => 2,
+            });
+
             // Draw entries
             for (screen_row, (idx, entry)) in browser.visible_entries(content_height - 1).enumerate() {
                 output.push_str(&ansi::cursor_position((screen_row + 1) as u16, 0));
@@ -783,24 +3318,47 @@ impl Editor {
                 if entry.is_directory() {
                     output.push_str(ansi::FG_BLUE);
                     output.push_str(ansi::BOLD);
+                } else if entry.is_symlink() {
+                    output.push_str(ansi::FG_CYAN);
                 } else {
                     output.push_str(ansi::FG_DEFAULT);
                 }
 
-                let name = entry.display_name();
-                let max_name_len = (self.size.cols as usize).saturating_sub(15);
-                let display_name = if name.len() > max_name_len {
-                    format!("{}...", &name[..max_name_len - 3])
-                } else {
+                // Name column, then size, permissions and modification date
+                let mut name = entry.display_name();
+                if browser.tree_view && entry.name != ".." {
+                    let expandable = entry.is_dir_like();
+                    let marker = if !expandable {
+                        "  "
+                    } else if browser.expanded.contains(&entry.path) {
+                        "- "
+                    } else {
+                        "+ "
+                    };
+                    name = format!("{}{}{}", "  ".repeat(entry.depth), marker, name);
+                }
+                let display_name = if name.chars().count() <= max_name_len {
                     name
+                } else if max_name_len > 3 {
+                    let truncated: String = name.chars().take(max_name_len - 3).collect();
+                    format!("{}...", truncated)
+                } else {
+                    name.chars().take(max_name_len).collect()
                 };
 
                 output.push_str(&format!(" {:<width$}", display_name, width = max_name_len));
 
-                // Size
-                if entry.is_file() {
-                    output.push_str(ansi::FG_BRIGHT_BLACK);
-                    output.push_str(&format!(" {:>10}", entry.size_string()));
+                output.push_str(ansi::FG_BRIGHT_BLACK);
+                if show_size {
+                    let size = if entry.is_file() { entry.size_string() } else { String::new() };
+                    output.push_str(&format!(" {:>10}", size));
+                }
+                if show_details {
+                    output.push_str(&format!(
+                        "  {:<10} {:<16}",
+                        entry.permissions.as_deref().unwrap_or(""),
+                        entry.modified_string()
+                    ));
                 }
 
                 output.push_str(ansi::RESET);
@@ -817,30 +3375,93 @@ impl Editor {
             output.push_str(ansi::CLEAR_LINE);
             output.push_str(ansi::REVERSE);
             output.push_str(&format!(
-                " BROWSER | {} items | . toggle hidden | Enter/l open | h/Backspace up | q close ",
-                browser.entries.len()
+                " BROWSER | {} items | {} | . toggle hidden | s sort | t tree | Enter/l open | h/Backspace up | q close ",
+                browser.entries.len(),
+                browser.sort_description()
             ));
             output.push_str(ansi::RESET);
 
             // Message line
             output.push_str(&ansi::cursor_position(content_height as u16 + 1, 0));
             output.push_str(ansi::CLEAR_LINE);
+            if let Some(msg) = &self.message {
+                output.push_str(msg);
+            }
         }
 
         Ok(())
     }
 
-    fn draw_status_line(&self, output: &mut String, row: u16) -> io::Result<()> {
+    fn draw_finder(&mut self, output: &mut String) -> io::Result<()> {
+        let content_height = self.size.rows.saturating_sub(2) as usize;
+        let width = self.size.cols as usize;
+
+        if let Some(finder) = &mut self.finder {
+            finder.update_scroll(content_height);
+
+            // Draw matches
+            let mut drawn = 0;
+            for (screen_row, (idx, file)) in finder.visible_matches(content_height).enumerate() {
+                output.push_str(&ansi::cursor_position(screen_row as u16, 0));
+                output.push_str(ansi::CLEAR_LINE);
+                if idx == finder.selected {
+                    output.push_str(ansi::REVERSE);
+                }
+                let text: String = format!(" {}", file).chars().take(width).collect();
+                output.push_str(&text);
+                output.push_str(ansi::RESET);
+                drawn += 1;
+            }
+
+            // Fill remaining lines
+            for screen_row in drawn..content_height {
+                output.push_str(&ansi::cursor_position(screen_row as u16, 0));
+                output.push_str(ansi::CLEAR_LINE);
+            }
+
+            // Status line
+            output.push_str(&ansi::cursor_position(content_height as u16, 0));
+            output.push_str(ansi::CLEAR_LINE);
+            output.push_str(ansi::REVERSE);
+            output.push_str(&format!(
+                " FIND | {}/{} files | Enter open | Esc close ",
+                finder.matches.len(),
+                finder.files.len()
+            ));
+            output.push_str(ansi::RESET);
+
+            // Query line
+            let prompt = format!("> {}", finder.query);
+            output.push_str(&ansi::cursor_position(content_height as u16 + 1, 0));
+            output.push_str(ansi::CLEAR_LINE);
+            output.push_str(&prompt);
+            output.push_str(&ansi::cursor_position(
+                content_height as u16 + 1,
+                prompt.chars().count() as u16,
+            ));
+            output.push_str(ansi::CURSOR_SHOW);
+        }
+
+        Ok(())
+    }
+
+    fn draw_status_line(&self, output: &mut String, row: u16, focused: bool) -> io::Result<()> {
         output.push_str(&ansi::cursor_position(row, 0));
         output.push_str(ansi::CLEAR_LINE);
-        output.push_str(ansi::REVERSE);
+        output.push_str(&self.theme.status_bar());
 
         let mode_str = match self.mode {
+            // Only the focused window shows the mode
+            _ if !focused => "",
             Mode::Normal => " NORMAL ",
             Mode::Insert => " INSERT ",
             Mode::Command => " COMMAND ",
             Mode::Search => " SEARCH ",
             Mode::Browser => " BROWSER ",
+            Mode::Finder => " FIND ",
+            Mode::Confirm => " CONFIRM ",
+            Mode::Visual => " VISUAL ",
+            Mode::VisualLine => " VISUAL LINE ",
         };
 
         let filename = self
@@ -851,16 +3472,39 @@ impl Editor {
         let modified = if self.buffer.modified { " [+]" } else { "" };
         let readonly = if self.buffer.readonly { " [RO]" } else { "" };
 
-        let left = format!("{} {}{}{}", mode_str, filename, modified, readonly);
+        let language = self
+            .highlighter
+            .language
+            .map(|lang| format!("{} | ", lang.name))
+            .unwrap_or_default();
+        let line_count = self.buffer.line_count();
         let right = format!(
-            " {}:{} ",
+            " {}{} | {} lines | {}:{} | {} ",
+            language,
+            self.buffer.line_ending.name(),
+            line_count,
             self.cursor_row + 1,
-            self.cursor_col + 1
+            self.cursor_col + 1,
+            scroll_position(self.scroll_row, self.content_height(), line_count)
+        );
+
+        // Truncate the filename from the left when space runs out
+        let available = (self.size.cols as usize).saturating_sub(
+            mode_str.len() + 1 + modified.len() + readonly.len() + right.chars().count(),
         );
+        let name_len = filename.chars().count();
+        let filename = if name_len > available {
+            let tail: String = filename.chars().skip(name_len + 1 - available.max(1)).collect();
+            format!("<{}", tail)
+        } else {
+            filename
+        };
+
+        let left = format!("{} {}{}{}", mode_str, filename, modified, readonly);
 
         let padding = (self.size.cols as usize)
-            .saturating_sub(left.len())
-            .saturating_sub(right.len());
+            .saturating_sub(left.chars().count())
+            .saturating_sub(right.chars().count());
 
         output.push_str(&left);
         output.push_str(&" ".repeat(padding));
@@ -882,9 +3526,16 @@ impl Editor {
                 let prefix = if self.search_direction > 0 { "/" } else { "?" };
                 output.push_str(&format!("{}{}", prefix, self.search_buffer));
             }
+            Mode::Confirm => {
+                if let Some(confirm) = &self.confirm {
+                    output.push_str(&format!("{} {}", confirm.prompt, confirm.action.choices()));
+                }
+            }
             _ => {
                 if let Some(msg) = &self.message {
                     output.push_str(msg);
+                } else if let Some((register, _)) = &self.macro_recording {
+                    output.push_str(&format!("recording @{}", register));
                 }
             }
         }
@@ -892,6 +3543,30 @@ impl Editor {
         Ok(())
     }
 
+    /// Width of the line number column including its trailing space; at least 4 digits,
+    /// or 0 when neither absolute nor relative numbers are shown
+    fn gutter_width(&self) -> usize {
+        if !self.show_line_numbers && !self.relative_numbers {
+            return 0;
+        }
+        let digits = self.buffer.line_count().to_string().len();
+        digits.max(4) + 1
+    }
+
+    /// Number shown in the gutter for a row. With relative numbers the cursor line shows
+    /// its absolute number if `:set number` is also on, and 0 otherwise.
+    fn line_number(&self, row: usize) -> usize {
+        if !self.relative_numbers {
+            row + 1
+        } else if row != self.cursor_row {
+            row.abs_diff(self.cursor_row)
+        } else if self.show_line_numbers {
+            row + 1
+        } else {
+            0
+        }
+    }
+
     fn update_scroll(&mut self, content_height: usize) {
         // Vertical scroll
         if self.cursor_row < self.scroll_row {
@@ -901,13 +3576,1587 @@ impl Editor {
         }
 
         // Horizontal scroll
-        let gutter_width = 5;
-        let visible_width = (self.size.cols as usize).saturating_sub(gutter_width);
+        let visible_width = (self.size.cols as usize).saturating_sub(self.gutter_width());
+
+        // Keep all of the cell under the cursor in view, even a wide tab
+        let start = self.cursor_visual_col();
+        let under_cursor = self.buffer.line(self.cursor_row).and_then(|line| line.chars.get(self.cursor_col));
+        let end = start + under_cursor.map_or(1, |&c| display_width(c, start, self.tab_stop));
+        if start < self.scroll_col {
+            self.scroll_col = start;
+        } else if end > self.scroll_col + visible_width {
+            self.scroll_col = end.saturating_sub(visible_width);
+        }
+    }
+
+    /// Screen column of the cursor within its line, with tabs expanded
+    fn cursor_visual_col(&self) -> usize {
+        self.buffer
+            .line(self.cursor_row)
+            .map_or(self.cursor_col, |line| line.visual_col(self.cursor_col, self.tab_stop))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor_with(text: &str) -> Editor {
+        let mut editor = Editor::new_headless(Size { rows: 12, cols: 80 });
+        editor.buffer.lines = text.lines().map(Line::from_str).collect();
+        editor
+    }
+
+    fn keys(s: &str) -> Vec<Key> {
+        s.chars()
+            .map(|c| match c {
+                '\n' => Key::Enter,
+                '\x1b' => Key::Escape,
+                c => Key::Char(c),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_headless_insert_and_escape() {
+        let mut editor = editor_with("world");
+        editor.feed_keys(keys("ihello \x1b")).unwrap();
+
+        assert_eq!(editor.text(), "hello world");
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.cursor(), (0, 5));
+    }
+
+    #[test]
+    fn test_headless_command() {
+        let mut editor = editor_with("b\na\nc");
+        editor.feed_keys(keys(":sort\n")).unwrap();
+
+        assert_eq!(editor.text(), "a\nb\nc");
+        assert_eq!(editor.message(), Some("Sorted 3 lines"));
+
+        editor.feed_keys(keys(":nosuch\n")).unwrap();
+        assert_eq!(editor.message(), Some("Unknown command: nosuch"));
+    }
+
+    #[test]
+    fn test_sort_line_range() {
+        let mut editor = editor_with("z\nd\nb\nc\na");
+        editor.feed_keys(keys(":2,4sort\n")).unwrap();
+        assert_eq!(editor.text(), "z\nb\nc\nd\na");
+        assert_eq!(editor.message(), Some("Sorted 3 lines"));
+
+        // `:` from a visual selection fills in its lines
+        editor.feed_keys(keys("jVj:")).unwrap();
+        assert_eq!(editor.mode(), Mode::Command);
+        assert_eq!(editor.command_buffer, "2,3");
+        editor.feed_keys(keys("sort!\n")).unwrap();
+        assert_eq!(editor.text(), "z\nc\nb\nd\na");
+
+        editor.feed_keys(keys(":9,12sort\n")).unwrap();
+        assert_eq!(editor.message(), Some("Invalid range"));
+        editor.feed_keys(keys(":0,2sort\n")).unwrap();
+        assert_eq!(editor.message(), Some("Unknown command: 0,2sort"));
+        editor.feed_keys(keys(":%sort\n")).unwrap();
+        assert_eq!(editor.text(), "a\nb\nc\nd\nz");
+    }
+
+    #[test]
+    fn test_headless_search() {
+        let mut editor = editor_with("one\ntwo\nthree two");
+        editor.feed_keys(keys("/two\n")).unwrap();
+        assert_eq!(editor.cursor(), (1, 0));
+
+        editor.feed_keys(keys("n")).unwrap();
+        assert_eq!(editor.cursor(), (2, 6));
+        assert_eq!(editor.message(), Some("Match 2 of 2"));
+
+        editor.feed_keys(keys("n")).unwrap();
+        assert_eq!(editor.cursor(), (1, 0));
+        assert_eq!(editor.message(), Some("Match 1 of 2 (search wrapped)"));
+    }
+
+    #[test]
+    fn test_paste_in_insert_mode() {
+        let mut editor = editor_with("x");
+        editor.feed_keys(keys("i")).unwrap();
+        editor.feed_keys([Key::Paste("fn f(\n".to_string())]).unwrap();
+        // Auto-pairs don't add a `)` to pasted text
+        assert_eq!(editor.text(), "fn f(\nx");
+        assert_eq!(editor.cursor(), (1, 0));
+    }
+
+    #[test]
+    fn test_paste_in_normal_mode() {
+        let mut editor = editor_with("ad");
+        editor.feed_keys(keys("l")).unwrap();
+        editor.feed_keys([Key::Paste("bc".to_string())]).unwrap();
+        assert_eq!(editor.text(), "abcd");
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.cursor(), (0, 2));
+
+        // `.` repeats the paste
+        editor.feed_keys(keys("$.")).unwrap();
+        assert_eq!(editor.text(), "abcbcd");
+    }
+
+    #[test]
+    fn test_paste_in_command_line() {
+        let mut editor = editor_with("b\na\nc");
+        editor.feed_keys(keys(":")).unwrap();
+        editor.feed_keys([Key::Paste("so\nrt".to_string())]).unwrap();
+        assert_eq!(editor.command_buffer, "sort");
+        editor.feed_keys(keys("\n")).unwrap();
+        assert_eq!(editor.text(), "a\nb\nc");
+    }
+
+    #[test]
+    fn test_paste_in_search() {
+        let mut editor = editor_with("one\ntwo\nthree two");
+        editor.feed_keys(keys("/")).unwrap();
+        editor.feed_keys([Key::Paste("tw\no".to_string())]).unwrap();
+        assert_eq!(editor.search_buffer, "two");
+        // The preview moves as for typed text
+        assert_eq!(editor.cursor(), (1, 0));
+        editor.feed_keys(keys("\n")).unwrap();
+        assert_eq!(editor.cursor(), (1, 0));
+    }
+
+    #[test]
+    fn test_paste_in_finder() {
+        let temp_dir = std::env::temp_dir().join("zedit_finder_paste_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        std::fs::write(temp_dir.join("alpha.txt"), "a\n").unwrap();
+        std::fs::write(temp_dir.join("beta.txt"), "b\n").unwrap();
+
+        let mut editor = editor_with("");
+        editor.swap_file = false;
+        editor.finder = Some(Finder::new(&temp_dir));
+        editor.mode = Mode::Finder;
+        editor.feed_keys([Key::Paste("beta\n".to_string())]).unwrap();
+        assert_eq!(editor.finder.as_ref().unwrap().query, "beta");
+        editor.feed_keys([Key::Enter]).unwrap();
+        assert_eq!(editor.text(), "b");
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_search_after_multibyte_chars() {
+        let mut editor = editor_with("naïve café word\n日本語 word word");
+        editor.feed_keys(keys("/word\n")).unwrap();
+        assert_eq!(editor.cursor(), (0, 11));
+
+        editor.feed_keys(keys("n")).unwrap();
+        assert_eq!(editor.cursor(), (1, 4));
+        editor.feed_keys(keys("n")).unwrap();
+        assert_eq!(editor.cursor(), (1, 9));
+        assert_eq!(editor.message(), Some("Match 3 of 3"));
+
+        // Wrapping forward and searching backward land on char columns too
+        editor.feed_keys(keys("n")).unwrap();
+        assert_eq!(editor.cursor(), (0, 11));
+        editor.feed_keys(keys("N")).unwrap();
+        assert_eq!(editor.cursor(), (1, 9));
+        editor.feed_keys(keys("N")).unwrap();
+        assert_eq!(editor.cursor(), (1, 4));
+    }
+
+    #[test]
+    fn test_substitute_all() {
+        let mut editor = editor_with("foo bar\nFoo foo");
+        editor.feed_keys(keys(":%s/foo/baz/gi\n")).unwrap();
+        assert_eq!(editor.text(), "baz bar\nbaz baz");
+        assert_eq!(editor.message(), Some("3 replacements"));
+
+        editor.feed_keys(keys(":%s//x/g\n")).unwrap();
+        assert_eq!(editor.message(), Some("Empty pattern"));
+    }
+
+    #[test]
+    fn test_substitute_current_line() {
+        let mut editor = editor_with("a a a\na a");
+        editor.feed_keys(keys(":s/a/b/\n")).unwrap();
+        assert_eq!(editor.text(), "b a a\na a");
+        assert_eq!(editor.message(), Some("1 replacement"));
+
+        editor.feed_keys(keys("j:s/a/c/g\n")).unwrap();
+        assert_eq!(editor.text(), "b a a\nc c");
+        assert_eq!(editor.message(), Some("2 replacements"));
+
+        // Without g, :%s replaces the first match on every line
+        editor.feed_keys(keys(":%s/ /_\n")).unwrap();
+        assert_eq!(editor.text(), "b_a a\nc_c");
+
+        editor.feed_keys(keys(":s/zz/y/\n")).unwrap();
+        assert_eq!(editor.message(), Some("Pattern not found: zz"));
+
+        // The whole substitution is one undo step
+        editor.feed_keys(keys("u")).unwrap();
+        assert_eq!(editor.text(), "b a a\nc c");
+    }
+
+    #[test]
+    fn test_headless_delete_and_paste() {
+        let mut editor = editor_with("a\nb\nc");
+        editor.feed_keys(keys("ddp")).unwrap();
+
+        assert_eq!(editor.text(), "b\na\nc");
+        assert_eq!(editor.cursor(), (1, 0));
+    }
+
+    #[test]
+    fn test_yank_line_and_paste() {
+        let mut editor = editor_with("one\ntwo\nthree");
+        editor.feed_keys(keys("jllyyP")).unwrap();
+        assert_eq!(editor.text(), "one\ntwo\ntwo\nthree");
+        assert_eq!(editor.cursor(), (1, 0));
+        assert!(editor.buffer.modified);
+
+        editor.feed_keys(keys("Gp")).unwrap();
+        assert_eq!(editor.text(), "one\ntwo\ntwo\nthree\ntwo");
+        assert_eq!(editor.cursor(), (4, 0));
+    }
+
+    #[test]
+    fn test_counts() {
+        let text = (1..=20).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+        let mut editor = editor_with(&text);
+
+        editor.feed_keys(keys("3j")).unwrap();
+        assert_eq!(editor.cursor(), (3, 0));
+        editor.feed_keys(keys("10G")).unwrap();
+        assert_eq!(editor.cursor(), (9, 0));
+        editor.feed_keys(keys("2w")).unwrap();
+        assert_eq!(editor.cursor(), (10, 0));
+        editor.feed_keys(keys("3gg$0")).unwrap();
+        assert_eq!(editor.cursor(), (2, 0));
+
+        editor.feed_keys(keys("2dd")).unwrap();
+        assert_eq!(editor.buffer.line(2).unwrap().to_string(), "line 5");
+        assert_eq!(editor.register.lines.len(), 2);
+
+        editor.feed_keys(keys("3x")).unwrap();
+        assert_eq!(editor.buffer.line(2).unwrap().to_string(), "e 5");
+        editor.feed_keys(keys("2yyGp")).unwrap();
+        assert_eq!(editor.text().lines().last(), Some("line 6"));
+
+        // A count larger than the buffer stops at the end
+        editor.feed_keys(keys("99G100k")).unwrap();
+        assert_eq!(editor.cursor(), (0, 0));
+    }
+
+    #[test]
+    fn test_operator_motions() {
+        let mut editor = editor_with("one two three four");
+        editor.feed_keys(keys("dw")).unwrap();
+        assert_eq!(editor.text(), "two three four");
+        assert_eq!(editor.register.lines[0].to_string(), "one ");
+
+        editor.feed_keys(keys("wd$")).unwrap();
+        assert_eq!(editor.text(), "two ");
+        editor.feed_keys(keys("p")).unwrap();
+        assert_eq!(editor.text(), "two three four");
+
+        editor.feed_keys(keys("$bd0")).unwrap();
+        assert_eq!(editor.text(), "four");
+
+        let mut editor = editor_with("alpha beta gamma\nnext");
+        editor.feed_keys(keys("cwALPHA\x1b")).unwrap();
+        assert_eq!(editor.text(), "ALPHA beta gamma\nnext");
+        editor.feed_keys(keys("w2dw")).unwrap();
+        assert_eq!(editor.text(), "ALPHA \nnext");
+
+        editor.feed_keys(keys("dq")).unwrap();
+        assert_eq!(editor.message(), Some("Unknown command: dq"));
+        assert_eq!(editor.text(), "ALPHA \nnext");
+    }
+
+    #[test]
+    fn test_word_motions_punctuation() {
+        let mut editor = editor_with("foo.bar(baz) qux");
+        let mut stops = Vec::new();
+        for _ in 0..6 {
+            editor.feed_keys(keys("w")).unwrap();
+            stops.push(editor.cursor().1);
+        }
+        assert_eq!(stops, [3, 4, 7, 8, 11, 13]);
+
+        editor.feed_keys(keys("bb")).unwrap();
+        assert_eq!(editor.cursor(), (0, 8));
+
+        editor.feed_keys(keys("0ee")).unwrap();
+        assert_eq!(editor.cursor(), (0, 3));
+    }
+
+    #[test]
+    fn test_big_word_motions() {
+        let mut editor = editor_with("foo.bar(baz) qux_1 end");
+        editor.feed_keys(keys("W")).unwrap();
+        assert_eq!(editor.cursor(), (0, 13));
+
+        editor.feed_keys(keys("E")).unwrap();
+        assert_eq!(editor.cursor(), (0, 17));
+
+        editor.feed_keys(keys("BB")).unwrap();
+        assert_eq!(editor.cursor(), (0, 0));
+    }
+
+    #[test]
+    fn test_word_motions_across_lines() {
+        let mut editor = editor_with("one\n\n  two");
+        editor.feed_keys(keys("w")).unwrap();
+        assert_eq!(editor.cursor(), (1, 0));
+
+        editor.feed_keys(keys("w")).unwrap();
+        assert_eq!(editor.cursor(), (2, 2));
+
+        editor.feed_keys(keys("bb")).unwrap();
+        assert_eq!(editor.cursor(), (0, 0));
+    }
+
+    #[test]
+    fn test_open_line_below_and_above() {
+        let mut editor = editor_with("a\nb");
+        editor.feed_keys(keys("ox\x1b")).unwrap();
+        assert_eq!(editor.text(), "a\nx\nb");
+        assert_eq!(editor.cursor(), (1, 0));
+
+        editor.feed_keys(keys("Oy\x1b")).unwrap();
+        assert_eq!(editor.text(), "a\ny\nx\nb");
+        assert_eq!(editor.cursor(), (1, 0));
+
+        editor.feed_keys(keys("Gox")).unwrap();
+        assert_eq!(editor.text(), "a\ny\nx\nb\nx");
+        assert_eq!(editor.cursor(), (4, 1));
+        assert_eq!(editor.mode(), Mode::Insert);
+    }
+
+    #[test]
+    fn test_open_line_scrolls_into_view() {
+        let text: Vec<String> = (0..30).map(|i| i.to_string()).collect();
+        let mut editor = editor_with(&text.join("\n"));
+        editor.feed_keys(keys("Go")).unwrap();
+
+        assert_eq!(editor.cursor(), (30, 0));
+        assert!(editor.scroll_row + editor.content_height() > 30);
+    }
+
+    #[test]
+    fn test_history_navigation() {
+        let mut history = History::default();
+        assert_eq!(history.older(""), None);
+        assert_eq!(history.newer(), None);
+
+        history.push("one");
+        history.push("two");
+        history.push("two");
+        history.push("");
+        assert_eq!(history.entries, ["one", "two"]);
+
+        assert_eq!(history.older("dra").as_deref(), Some("two"));
+        assert_eq!(history.older("two").as_deref(), Some("one"));
+        assert_eq!(history.older("one").as_deref(), Some("one"));
+        assert_eq!(history.newer().as_deref(), Some("two"));
+        assert_eq!(history.newer().as_deref(), Some("dra"));
+        assert_eq!(history.newer(), None);
+    }
+
+    #[test]
+    fn test_command_history_recall_and_edit() {
+        let mut editor = editor_with("b\na");
+        editor.feed_keys(keys(":sort\n:")).unwrap();
+        editor.feed_keys([Key::Up]).unwrap();
+        assert_eq!(editor.command_buffer, "sort");
+
+        // Edit the recalled entry before running it
+        editor.feed_keys(keys("!\n")).unwrap();
+        assert_eq!(editor.text(), "b\na");
+        assert_eq!(editor.command_history.entries, ["sort", "sort!"]);
+    }
+
+    #[test]
+    fn test_command_tab_completion_cycles() {
+        let mut editor = editor_with("");
+        editor.feed_keys(keys(":set no")).unwrap();
+        editor.feed_keys([Key::Tab]).unwrap();
+        assert_eq!(editor.command_buffer, "set noautoindent");
+
+        editor.feed_keys([Key::Tab]).unwrap();
+        assert_eq!(editor.command_buffer, "set noautopairs");
+
+        editor.feed_keys([Key::Backspace]).unwrap();
+        editor.feed_keys(keys("\x1b:set rai")).unwrap();
+        editor.feed_keys([Key::Tab]).unwrap();
+        assert_eq!(editor.command_buffer, "set rainbow");
+    }
+
+    #[test]
+    fn test_open_or_report_missing_and_unreadable() {
+        let temp_dir = std::env::temp_dir().join("zedit_open_report_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let mut editor = editor_with("");
+        let new_file = temp_dir.join("new.rs");
+        editor.open_or_report(&new_file);
+        assert_eq!(editor.buffer.path.as_ref(), Some(&new_file));
+        assert!(editor.message().unwrap().contains("[New]"));
+
+        // Invalid UTF-8 fails to load but leaves the editor usable
+        let binary = temp_dir.join("binary.txt");
+        std::fs::write(&binary, [0xff, 0xfe, 0x00]).unwrap();
+        editor.open_or_report(&binary);
+        assert!(editor.message().unwrap().starts_with("Could not open"));
+        assert_eq!(editor.buffer.path.as_ref(), Some(&new_file));
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_quit_confirmation() {
+        let mut editor = editor_with("text");
+        editor.buffer.modified = true;
 
-        if self.cursor_col < self.scroll_col {
-            self.scroll_col = self.cursor_col;
-        } else if self.cursor_col >= self.scroll_col + visible_width {
-            self.scroll_col = self.cursor_col - visible_width + 1;
+        editor.feed_keys(keys(":q\n")).unwrap();
+        assert_eq!(editor.mode(), Mode::Confirm);
+        editor.feed_keys(keys("c")).unwrap();
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.message(), Some("Cancelled"));
+        assert!(!editor.quit);
+
+        // Saving without a filename keeps the editor open
+        editor.feed_keys([Key::Ctrl('q')]).unwrap();
+        editor.feed_keys(keys("y")).unwrap();
+        assert!(!editor.quit);
+        assert_eq!(editor.message(), Some("No filename. Use :w <filename>"));
+
+        editor.feed_keys([Key::Ctrl('q')]).unwrap();
+        editor.feed_keys(keys("n")).unwrap();
+        assert!(editor.quit);
+    }
+
+    #[test]
+    fn test_write_quit_with_other_buffers() {
+        let temp_dir = std::env::temp_dir().join("zedit_write_quit_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let first = temp_dir.join("first.txt");
+        let second = temp_dir.join("second.txt");
+        std::fs::write(&first, "one\n").unwrap();
+        std::fs::write(&second, "two\n").unwrap();
+
+        let mut editor = editor_with("");
+        editor.swap_file = false;
+        editor.feed_keys(keys(&format!(":e {}\nx", first.display()))).unwrap();
+        editor.feed_keys(keys(&format!(":e {}\nx", second.display()))).unwrap();
+
+        // The other buffer's changes would be lost, so it asks first
+        editor.feed_keys(keys(":wq\n")).unwrap();
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "wo\n");
+        assert_eq!(editor.mode(), Mode::Confirm);
+        assert!(!editor.quit);
+        editor.feed_keys(keys("c")).unwrap();
+
+        // A read-only buffer isn't saved, so it isn't quit either
+        editor.feed_keys(keys(":bn\n")).unwrap();
+        editor.buffer.readonly = true;
+        editor.feed_keys(keys(":wq\n")).unwrap();
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "one\n");
+        assert_eq!(editor.mode(), Mode::Confirm);
+        assert!(!editor.quit);
+        editor.feed_keys(keys("c")).unwrap();
+
+        editor.buffer.readonly = false;
+        editor.feed_keys(keys(":wq\n")).unwrap();
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "ne\n");
+        assert!(editor.quit);
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_swap_file_recovery() {
+        let temp_dir = std::env::temp_dir().join("zedit_swap_recovery_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("draft.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        std::fs::write(temp_dir.join(".draft.txt.zedit.swp"), "new\n").unwrap();
+
+        let mut editor = editor_with("");
+        editor.open(&path).unwrap();
+        assert_eq!(editor.mode(), Mode::Confirm);
+        editor.feed_keys(keys("y")).unwrap();
+        assert_eq!(editor.text(), "new");
+        assert!(editor.buffer.modified);
+
+        // Declining deletes the swap file so the prompt is not repeated
+        editor.open(&path).unwrap();
+        editor.feed_keys(keys("n")).unwrap();
+        assert_eq!(editor.text(), "old");
+        assert!(!temp_dir.join(".draft.txt.zedit.swp").exists());
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_jump_back_and_insert_again() {
+        let mut editor = editor_with("one\ntwo\nthree");
+        editor.feed_keys(keys("``")).unwrap();
+        assert_eq!(editor.message(), Some("No previous jump"));
+
+        editor.feed_keys(keys("jlG")).unwrap();
+        assert_eq!(editor.cursor(), (2, 1));
+        editor.feed_keys(keys("``")).unwrap();
+        assert_eq!(editor.cursor(), (1, 1));
+        editor.feed_keys(keys("``")).unwrap();
+        assert_eq!(editor.cursor(), (2, 1));
+
+        editor.feed_keys(keys("gi")).unwrap();
+        assert_eq!(editor.message(), Some("No previous insert"));
+        editor.feed_keys(keys("0i!\x1bgg")).unwrap();
+        assert_eq!(editor.cursor().0, 0);
+        editor.feed_keys(keys("gi?\x1b")).unwrap();
+        assert_eq!(editor.text(), "one\ntwo\n!?three");
+    }
+
+    #[test]
+    fn test_readonly_refuses_edits() {
+        let mut editor = editor_with("keep");
+        editor.buffer.readonly = true;
+
+        editor.feed_keys(keys("xddihi\x1b:sort\n")).unwrap();
+        assert_eq!(editor.text(), "keep");
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert!(editor.message().unwrap().contains("read-only"));
+
+        // Yanking is still allowed
+        editor.feed_keys(keys("yiw")).unwrap();
+        assert_eq!(editor.register.lines[0].to_string(), "keep");
+
+        editor.feed_keys(keys(":set noro\nx")).unwrap();
+        assert_eq!(editor.text(), "eep");
+    }
+
+    #[test]
+    fn test_startup_commands() {
+        let mut editor = editor_with("alpha\nbeta\ngamma\ndelta");
+        editor.run_startup_command("3");
+        assert_eq!(editor.cursor(), (2, 0));
+
+        editor.run_startup_command("/lta");
+        assert_eq!(editor.cursor(), (3, 2));
+
+        editor.run_startup_command(":1");
+        assert_eq!(editor.cursor(), (0, 0));
+        editor.run_startup_command("$");
+        assert_eq!(editor.cursor(), (3, 0));
+
+        editor.run_startup_command("set list");
+        assert!(editor.show_whitespace);
+    }
+
+    #[test]
+    fn test_pwd_and_failed_cd() {
+        let cwd = std::env::current_dir().unwrap();
+        let mut editor = editor_with("");
+        editor.feed_keys(keys(":pwd\n")).unwrap();
+        assert_eq!(editor.message(), Some(cwd.display().to_string().as_str()));
+
+        editor.buffer.path = Some(PathBuf::from("relative.txt"));
+        editor.feed_keys(keys(":cd /no/such/zedit/dir\n")).unwrap();
+        assert!(editor.message().unwrap().starts_with("Cannot change directory"));
+        assert_eq!(std::env::current_dir().unwrap(), cwd);
+        assert_eq!(editor.buffer.path, Some(cwd.join("relative.txt")));
+    }
+
+    #[test]
+    fn test_undo_groups_insert_session() {
+        let mut editor = editor_with("one\ntwo");
+        editor.feed_keys(keys("jothree\nfour\x1bddggx")).unwrap();
+        assert_eq!(editor.text(), "ne\ntwo\nthree");
+
+        editor.feed_keys(keys("u")).unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree");
+        editor.feed_keys(keys("u")).unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree\nfour");
+        editor.feed_keys(keys("u")).unwrap();
+        assert_eq!(editor.text(), "one\ntwo");
+        assert_eq!(editor.cursor(), (1, 0));
+        editor.feed_keys(keys("u")).unwrap();
+        assert_eq!(editor.message(), Some("Already at oldest change"));
+
+        editor.feed_keys([Key::Ctrl('r'), Key::Ctrl('r')]).unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn test_undo_to_save_point_clears_modified() {
+        let temp_dir = std::env::temp_dir().join("zedit_undo_save_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("file.txt");
+        std::fs::write(&path, "abc\n").unwrap();
+
+        let mut editor = editor_with("");
+        editor.open(&path).unwrap();
+        editor.feed_keys(keys("x")).unwrap();
+        assert!(editor.buffer.modified);
+        editor.feed_keys(keys("u")).unwrap();
+        assert!(!editor.buffer.modified);
+
+        editor.feed_keys(keys("x:w\nx")).unwrap();
+        editor.feed_keys(keys("u")).unwrap();
+        assert!(!editor.buffer.modified);
+        editor.feed_keys(keys("u")).unwrap();
+        assert!(editor.buffer.modified);
+        assert_eq!(editor.text(), "abc");
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    /// Screen output with escape sequences removed
+    fn strip_ansi(output: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = output.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                // Skip to the final byte of the sequence
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            } else {
+                plain.push(c);
+            }
         }
+        plain
+    }
+
+    #[test]
+    fn test_browser_rows_fit_narrow_terminals() {
+        let temp_dir = std::env::temp_dir().join("zedit_browser_width_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        std::fs::write(temp_dir.join("a_rather_long_file_name_for_a_narrow_terminal.txt"), "x").unwrap();
+
+        for cols in [20, 40, 57, 58, 80] {
+            let mut editor = Editor::new_headless(Size { rows: 6, cols });
+            editor.browser = Some(Browser::new(&temp_dir).unwrap());
+            let mut output = String::new();
+            editor.draw_browser(&mut output).unwrap();
+            // Entry rows start at screen rows 1 and 2; each must end before the last column
+            for row in 1..=2 {
+                let start = output.find(&ansi::cursor_position(row, 0)).unwrap();
+                let end = output.find(&ansi::cursor_position(row + 1, 0)).unwrap();
+                let width = strip_ansi(&output[start..end]).chars().count();
+                assert!(width < cols as usize, "{} cols: row {} is {} wide", cols, row, width);
+            }
+            let plain = strip_ansi(&output);
+            assert!(plain.contains(" a_"));
+            assert_eq!(plain.contains(" 1 B"), cols >= 29, "{} cols", cols);
+        }
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_draw_scrolled_multibyte_line() {
+        let mut editor = editor_with("let π = \"café\"; x()");
+        editor.highlighter = Highlighter::for_file(None, Some("rs"), None);
+        editor.cursor_col = 4;
+        editor.scroll_col = 4;
+
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(strip_ansi(&output).contains("   1 π = \"café\"; x()"));
+
+        // Scrolled past the multi-byte tokens
+        editor.cursor_col = 15;
+        editor.scroll_col = 15;
+        output.clear();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(strip_ansi(&output).contains("   1  x()"));
+    }
+
+    #[test]
+    fn test_gutter_grows_with_line_count() {
+        let mut editor = editor_with("x");
+        assert_eq!(editor.gutter_width(), 5);
+
+        editor.buffer.lines = vec![Line::from_str("line"); 12_345];
+        editor.cursor_row = 12_344;
+        assert_eq!(editor.gutter_width(), 6);
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(strip_ansi(&output).contains("12345 line"));
+        assert!(output.ends_with(&format!("{}{}", ansi::cursor_position(9, 6), ansi::CURSOR_SHOW)));
+
+        editor.feed_keys(keys(":set nonu\n")).unwrap();
+        assert_eq!(editor.gutter_width(), 0);
+        output.clear();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(!strip_ansi(&output).contains("12344 "));
+        assert!(output.ends_with(&format!("{}{}", ansi::cursor_position(9, 0), ansi::CURSOR_SHOW)));
+    }
+
+    #[test]
+    fn test_relative_line_numbers() {
+        let mut editor = editor_with("a\nb\nc\nd\ne");
+        editor.cursor_row = 2;
+        assert_eq!(editor.line_number(0), 1);
+
+        editor.feed_keys(keys(":set rnu\n")).unwrap();
+        let numbers: Vec<usize> = (0..5).map(|row| editor.line_number(row)).collect();
+        assert_eq!(numbers, [2, 1, 3, 1, 2]);
+
+        editor.feed_keys(keys(":set nonu\n")).unwrap();
+        assert_eq!(editor.line_number(2), 0);
+        assert_eq!(editor.gutter_width(), 5);
+
+        editor.feed_keys(keys(":set nornu\n")).unwrap();
+        assert_eq!(editor.gutter_width(), 0);
+    }
+
+    #[test]
+    fn test_repeat_last_change() {
+        let mut editor = editor_with("abcdef\none\ntwo\nthree\nfour");
+        editor.feed_keys(keys("x.")).unwrap();
+        assert_eq!(editor.text(), "cdef\none\ntwo\nthree\nfour");
+
+        // Motions in between are not recorded, and a count replaces the original one
+        editor.feed_keys(keys("2xl.")).unwrap();
+        assert_eq!(editor.text(), "e\none\ntwo\nthree\nfour");
+        editor.feed_keys(keys("j0dd.")).unwrap();
+        assert_eq!(editor.text(), "e\nthree\nfour");
+
+        // Inserted text is replayed verbatim
+        editor.feed_keys(keys("iab")).unwrap();
+        editor.feed_keys([Key::Escape]).unwrap();
+        editor.feed_keys(keys("j0.")).unwrap();
+        assert_eq!(editor.text(), "e\nabthree\nabfour");
+
+        // Undo leaves the last change alone
+        editor.feed_keys(keys("u0.")).unwrap();
+        assert_eq!(editor.text(), "e\nabthree\nabfour");
+    }
+
+    #[test]
+    fn test_replace_char() {
+        let mut editor = editor_with("abcd\n\nend");
+        editor.feed_keys(keys("rx")).unwrap();
+        assert_eq!(editor.text(), "xbcd\n\nend");
+        assert_eq!(editor.mode(), Mode::Normal);
+
+        editor.feed_keys(keys("l2ri")).unwrap();
+        assert_eq!(editor.text(), "xiid\n\nend");
+        assert_eq!(editor.cursor(), (0, 2));
+
+        // Escape cancels, and a count past the end of the line does nothing
+        editor.feed_keys([Key::Char('r'), Key::Escape]).unwrap();
+        editor.feed_keys(keys("5rz")).unwrap();
+        assert_eq!(editor.text(), "xiid\n\nend");
+
+        // Nothing to replace on an empty line
+        editor.feed_keys(keys("jrq")).unwrap();
+        assert_eq!(editor.text(), "xiid\n\nend");
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let mut editor = editor_with("fn main() {\n    body(\n    )\n}\n\nend");
+        editor.feed_keys(keys("J")).unwrap();
+        assert_eq!(editor.text(), "fn main() { body(\n    )\n}\n\nend");
+        assert_eq!(editor.cursor(), (0, 11));
+
+        // No space before a closing paren; a count joins that many lines
+        editor.feed_keys(keys("3J")).unwrap();
+        assert_eq!(editor.text(), "fn main() { body() }\n\nend");
+
+        // Joining an empty line adds no space, and the last line can't be joined
+        editor.feed_keys(keys("jJJ")).unwrap();
+        assert_eq!(editor.text(), "fn main() { body() }\nend");
+        assert_eq!(editor.cursor(), (1, 0));
+    }
+
+    #[test]
+    fn test_toggle_case() {
+        let mut editor = editor_with("abC-ß");
+        editor.feed_keys(keys("~")).unwrap();
+        assert_eq!(editor.text(), "AbC-ß");
+        assert_eq!(editor.cursor(), (0, 1));
+        assert!(editor.buffer.modified);
+
+        // A count stops at the end of the line
+        editor.feed_keys(keys("9~")).unwrap();
+        assert_eq!(editor.text(), "ABc-ß");
+        assert_eq!(editor.cursor(), (0, 4));
+    }
+
+    #[test]
+    fn test_find_char_motions() {
+        let mut editor = editor_with("a,b,c,d,e");
+        editor.feed_keys(keys("f,")).unwrap();
+        assert_eq!(editor.cursor(), (0, 1));
+        editor.feed_keys(keys(";")).unwrap();
+        assert_eq!(editor.cursor(), (0, 3));
+        editor.feed_keys(keys(",")).unwrap();
+        assert_eq!(editor.cursor(), (0, 1));
+
+        editor.feed_keys(keys("2t,")).unwrap();
+        assert_eq!(editor.cursor(), (0, 4));
+        // Repeating a `t` moves on to the next match
+        editor.feed_keys(keys(";")).unwrap();
+        assert_eq!(editor.cursor(), (0, 6));
+
+        editor.feed_keys(keys("$Fb")).unwrap();
+        assert_eq!(editor.cursor(), (0, 2));
+        editor.feed_keys(keys("$Ta")).unwrap();
+        assert_eq!(editor.cursor(), (0, 1));
+
+        // Missing characters leave the cursor alone
+        editor.feed_keys(keys("fz")).unwrap();
+        assert_eq!(editor.cursor(), (0, 1));
+    }
+
+    #[test]
+    fn test_percent_jumps_to_matching_bracket() {
+        let mut editor = editor_with("if (a) {\n    b;\n}");
+        editor.feed_keys(keys("%")).unwrap();
+        assert_eq!(editor.cursor(), (0, 5));
+        editor.feed_keys(keys("%")).unwrap();
+        assert_eq!(editor.cursor(), (0, 3));
+        editor.feed_keys(keys("$%")).unwrap();
+        assert_eq!(editor.cursor(), (2, 0));
+        editor.feed_keys(keys("``")).unwrap();
+        assert_eq!(editor.cursor(), (0, 7));
+    }
+
+    #[test]
+    fn test_draw_search_highlights() {
+        let mut editor = editor_with("héllo x héllo\nhéllo");
+        editor.show_line_numbers = false;
+        editor.feed_keys(keys("/héllo\n")).unwrap();
+        assert_eq!(editor.cursor(), (0, 8));
+
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        let other = format!("{}{}h", ansi::BG_YELLOW, ansi::FG_BLACK);
+        let current = format!("{}{}h", ansi::BG_MAGENTA, ansi::FG_BLACK);
+        assert_eq!(output.matches(&other).count(), 2);
+        assert_eq!(output.matches(&current).count(), 1);
+
+        editor.feed_keys(keys(":noh\n")).unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(!output.contains(ansi::BG_YELLOW));
+
+        // The next search turns highlighting back on
+        editor.feed_keys(keys("n")).unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(output.contains(&current));
+    }
+
+    #[test]
+    fn test_set_fileformat() {
+        let mut editor = editor_with("a");
+        editor.feed_keys(keys(":set ff\n")).unwrap();
+        assert_eq!(editor.message(), Some("fileformat=unix"));
+        editor.feed_keys(keys(":set fileformat=dos\n")).unwrap();
+        assert_eq!(editor.buffer.line_ending, LineEnding::Crlf);
+        assert!(editor.buffer.modified);
+        editor.feed_keys(keys(":set ff=mac\n")).unwrap();
+        assert_eq!(editor.message(), Some("Unknown fileformat: mac"));
+    }
+
+    #[test]
+    fn test_reload_discards_changes() {
+        let mut editor = editor_with("unsaved");
+        editor.feed_keys(keys(":e!\n")).unwrap();
+        assert_eq!(editor.message(), Some("No file name"));
+
+        let temp_dir = std::env::temp_dir().join("zedit_reload_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("file.txt");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+
+        editor.swap_file = false;
+        editor.open(&path).unwrap();
+        editor.feed_keys(keys("jddx")).unwrap();
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        editor.feed_keys(keys(":edit!\n")).unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree");
+        assert!(!editor.buffer.modified);
+        assert_eq!(editor.cursor(), (0, 0));
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_trim_trailing_keeps_buffer() {
+        let temp_dir = std::env::temp_dir().join("zedit_trim_cursor_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("file.txt");
+        std::fs::write(&path, "text    \n").unwrap();
+
+        let mut editor = editor_with("");
+        editor.swap_file = false;
+        editor.open(&path).unwrap();
+        editor.feed_keys(keys(":set trimtrailing\n$:w\n")).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "text\n");
+        assert_eq!(editor.cursor(), (0, 7));
+        assert!(!editor.buffer.modified);
+
+        // Saving mid-insert leaves the space just typed in place
+        editor.feed_keys(keys("olet x = ")).unwrap();
+        editor.feed_keys([Key::Ctrl('s')]).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "text\nlet x =\n");
+        editor.feed_keys(keys("5\x1b")).unwrap();
+        assert_eq!(editor.text(), "text    \nlet x = 5");
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_open_in_new_buffer() {
+        let temp_dir = std::env::temp_dir().join("zedit_open_new_buffer_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let first = temp_dir.join("first.txt");
+        let second = temp_dir.join("second.txt");
+        std::fs::write(&first, "one\n").unwrap();
+        std::fs::write(&second, "two\n").unwrap();
+
+        // The empty scratch buffer is reused for the first file
+        let mut editor = editor_with("");
+        editor.swap_file = false;
+        editor.open_in_new_buffer(&first).unwrap();
+        assert_eq!(editor.buffers.len(), 1);
+        assert_eq!(editor.text(), "one");
+        editor.feed_keys(keys("x")).unwrap();
+
+        editor.open_in_new_buffer(&second).unwrap();
+        assert_eq!(editor.buffers.len(), 2);
+        assert_eq!(editor.text(), "two");
+
+        // Opening a file that is already open switches to its buffer
+        editor.open_in_new_buffer(&first).unwrap();
+        assert_eq!(editor.buffers.len(), 2);
+        assert_eq!(editor.current_buffer, 0);
+        assert_eq!(editor.text(), "ne");
+        assert_eq!(editor.message(), Some("\"first.txt\" opened in buffer 1"));
+
+        // Including the one being edited, which keeps its changes
+        editor.open_in_new_buffer(&first).unwrap();
+        assert_eq!(editor.buffers.len(), 2);
+        assert_eq!(editor.text(), "ne");
+        assert!(editor.buffer.modified);
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_buffer_commands() {
+        let temp_dir = std::env::temp_dir().join("zedit_buffer_commands_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let first = temp_dir.join("first.txt");
+        let second = temp_dir.join("second.txt");
+        std::fs::write(&first, "one\ntwo\n").unwrap();
+        std::fs::write(&second, "other\n").unwrap();
+
+        let mut editor = editor_with("");
+        editor.swap_file = false;
+        editor.feed_keys(keys(&format!(":e {}\n", first.display()))).unwrap();
+        editor.feed_keys(keys("jx")).unwrap();
+        editor.feed_keys(keys(&format!(":e {}\n", second.display()))).unwrap();
+        assert_eq!(editor.text(), "other");
+        assert_eq!(editor.message(), Some("\"second.txt\" opened in buffer 2"));
+
+        // Each buffer keeps its cursor and changes
+        editor.feed_keys(keys(":bn\n")).unwrap();
+        assert_eq!(editor.text(), "one\nwo");
+        assert_eq!(editor.cursor(), (1, 0));
+        assert_eq!(editor.message(), Some("\"first.txt\" buffer 1 of 2"));
+        editor.feed_keys(keys(":bp\n")).unwrap();
+        assert_eq!(editor.text(), "other");
+
+        editor.feed_keys(keys(":ls\n")).unwrap();
+        let listing = format!("1 \"{}\" [+]  2% \"{}\"", first.display(), second.display());
+        assert_eq!(editor.message(), Some(listing.as_str()));
+
+        editor.feed_keys(keys(":b 1\n")).unwrap();
+        assert_eq!(editor.text(), "one\nwo");
+        editor.feed_keys(keys(":b 3\n")).unwrap();
+        assert_eq!(editor.message(), Some("No buffer 3"));
+
+        // A new file gets its own buffer too
+        let new_path = temp_dir.join("new.txt");
+        editor.feed_keys(keys(&format!(":e {}\n", new_path.display()))).unwrap();
+        assert_eq!(editor.message(), Some("\"new.txt\" [New] opened in buffer 3"));
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_split_windows() {
+        let text: Vec<String> = (1..=30).map(|i| format!("line {}", i)).collect();
+        let mut editor = editor_with(&text.join("\n"));
+        editor.feed_keys(keys(":sp\n")).unwrap();
+        assert_eq!(editor.windows.len(), 2);
+
+        // Each window keeps its own cursor
+        editor.feed_keys(keys("20G")).unwrap();
+        editor.feed_keys([Key::Ctrl('w'), Key::Char('w')]).unwrap();
+        assert_eq!(editor.cursor(), (0, 0));
+        editor.feed_keys([Key::Ctrl('w'), Key::Char('k')]).unwrap();
+        assert_eq!(editor.cursor(), (19, 0));
+
+        // Both windows are drawn with their own status line
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        let screen = strip_ansi(&output);
+        assert!(screen.contains("line 20"));
+        assert!(screen.contains("line 1 "));
+        assert_eq!(screen.matches("30 lines").count(), 2);
+        assert_eq!(screen.matches(" NORMAL ").count(), 1);
+
+        // Edits show in both windows of the same buffer; :q closes just one
+        editor.feed_keys(keys("dd:q\n")).unwrap();
+        assert_eq!(editor.windows.len(), 1);
+        assert_eq!(editor.cursor(), (0, 0));
+        assert_eq!(editor.buffer.line_count(), 29);
+        assert!(!editor.quit);
+
+        // Shrinking the terminal closes the windows that no longer fit, never the focused one
+        editor.feed_keys(keys(":sp\n:sp\n")).unwrap();
+        editor.feed_keys([Key::Ctrl('w'), Key::Char('j'), Key::Ctrl('w'), Key::Char('j')]).unwrap();
+        editor.feed_keys(keys("9G")).unwrap();
+        assert_eq!(editor.windows.len(), 3);
+        editor.size.rows = 5;
+        editor.draw_editor(&mut String::new()).unwrap();
+        assert_eq!(editor.windows.len(), 2);
+        assert_eq!(editor.current_window, 1);
+        assert_eq!(editor.cursor(), (8, 0));
+        editor.size.rows = 2;
+        editor.draw_editor(&mut String::new()).unwrap();
+        assert_eq!(editor.windows.len(), 1);
+        assert_eq!(editor.cursor(), (8, 0));
+    }
+
+    #[test]
+    fn test_auto_indent() {
+        let mut editor = editor_with("    fn x() {");
+        editor.feed_keys(keys("$i")).unwrap();
+        editor.feed_keys([Key::Right, Key::Enter]).unwrap();
+        editor.feed_keys(keys("body")).unwrap();
+        assert_eq!(editor.text(), "    fn x() {\n    body");
+        assert_eq!(editor.cursor(), (1, 8));
+
+        // Splitting mid-line indents the moved text; splitting inside the indent keeps
+        // only the whitespace before the cursor
+        editor.feed_keys([Key::Left, Key::Left, Key::Enter]).unwrap();
+        assert_eq!(editor.text(), "    fn x() {\n    bo\n    dy");
+        editor.feed_keys([Key::Escape]).unwrap();
+        editor.feed_keys(keys("0lli")).unwrap();
+        editor.feed_keys([Key::Enter, Key::Escape]).unwrap();
+        assert_eq!(editor.text(), "    fn x() {\n    bo\n  \n    dy");
+
+        // o copies the indentation too, unless turned off
+        editor.feed_keys(keys("Go")).unwrap();
+        editor.feed_keys([Key::Escape]).unwrap();
+        assert_eq!(editor.buffer.lines[4].to_string(), "    ");
+        editor.feed_keys(keys(":set noai\n")).unwrap();
+        editor.feed_keys(keys("o")).unwrap();
+        editor.feed_keys([Key::Enter, Key::Escape]).unwrap();
+        assert_eq!(editor.buffer.lines[5].to_string(), "");
+    }
+
+    #[test]
+    fn test_shift_lines() {
+        let mut editor = editor_with("fn x() {\nbody\n\tnext\n}");
+        editor.feed_keys(keys("j>>")).unwrap();
+        assert_eq!(editor.text(), "fn x() {\n    body\n\tnext\n}");
+        assert_eq!(editor.cursor(), (1, 4));
+
+        // A count shifts several lines; a tab counts as one shiftwidth
+        editor.feed_keys(keys("3<<")).unwrap();
+        assert_eq!(editor.text(), "fn x() {\nbody\nnext\n}");
+        assert_eq!(editor.message(), Some("3 lines unindented"));
+        editor.feed_keys(keys("u")).unwrap();
+        assert_eq!(editor.text(), "fn x() {\n    body\n\tnext\n}");
+
+        editor.feed_keys(keys(":set sw=2\n")).unwrap();
+        editor.feed_keys(keys("gg>>.")).unwrap();
+        assert_eq!(editor.buffer.lines[0].to_string(), "    fn x() {");
+        editor.feed_keys(keys(":set sw=0\n")).unwrap();
+        assert_eq!(editor.message(), Some("Invalid shiftwidth: 0"));
+    }
+
+    #[test]
+    fn test_tab_settings() {
+        let mut editor = editor_with("x");
+        editor.feed_keys(keys("i")).unwrap();
+        editor.feed_keys([Key::Tab, Key::Escape]).unwrap();
+        assert_eq!(editor.text(), "    x");
+
+        editor.feed_keys(keys(":set noet\n")).unwrap();
+        editor.feed_keys(keys(":set ts=8\n")).unwrap();
+        editor.feed_keys(keys("0i")).unwrap();
+        editor.feed_keys([Key::Tab]).unwrap();
+        assert_eq!(editor.text(), "\t    x");
+
+        // The tab fills to the next tab stop and the cursor lands after it
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(strip_ansi(&output).contains("   1             x"));
+        assert!(output.ends_with(&format!("{}{}", ansi::cursor_position(0, 13), ansi::CURSOR_SHOW)));
+
+        editor.feed_keys(keys("\x1b:set ts=0\n")).unwrap();
+        assert_eq!(editor.message(), Some("Invalid tabstop: 0"));
+    }
+
+    #[test]
+    fn test_draw_mixed_tabs_aligned() {
+        let mut editor = editor_with("\tx = 1;\n    y = 2;\n  \tz = 3;");
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        let plain = strip_ansi(&output);
+        for text in ["   1     x = 1;", "   2     y = 2;", "   3     z = 3;"] {
+            assert!(plain.contains(text), "{:?} not in {:?}", text, plain);
+        }
+
+        // Scrolling moves every line by the same number of screen columns
+        let long = format!("\t{}", "a".repeat(100));
+        editor.buffer.lines = vec![Line::from_str(&long), Line::from_str(&"b".repeat(104))];
+        editor.feed_keys(keys("$")).unwrap();
+        output.clear();
+        editor.draw_editor(&mut output).unwrap();
+        assert_eq!(editor.scroll_col, 29);
+        assert!(output.ends_with(&format!("{}{}", ansi::cursor_position(0, 79), ansi::CURSOR_SHOW)));
+        editor.feed_keys(keys("j$")).unwrap();
+        output.clear();
+        editor.draw_editor(&mut output).unwrap();
+        assert_eq!(editor.scroll_col, 29);
+        assert!(output.ends_with(&format!("{}{}", ansi::cursor_position(1, 79), ansi::CURSOR_SHOW)));
+    }
+
+    #[test]
+    fn test_draw_wide_chars() {
+        let mut editor = editor_with("世界 x");
+        editor.feed_keys(keys("$")).unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(strip_ansi(&output).contains("   1 世界 x"));
+        assert!(output.ends_with(&format!("{}{}", ansi::cursor_position(0, 10), ansi::CURSOR_SHOW)));
+
+        // Half of a wide char scrolled out of view leaves a blank cell
+        editor.scroll_col = 1;
+        output.clear();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(strip_ansi(&output).contains("   1  界 x"));
+        assert!(output.ends_with(&format!("{}{}", ansi::cursor_position(0, 9), ansi::CURSOR_SHOW)));
+    }
+
+    #[test]
+    fn test_scroll_cursor_line() {
+        let text: Vec<String> = (0..30).map(|n| format!("line {}", n)).collect();
+        let mut editor = editor_with(&text.join("\n"));
+        assert_eq!(editor.content_height(), 10);
+        editor.feed_keys(keys("16Gzt")).unwrap();
+        assert_eq!(editor.scroll_row, 15);
+        editor.feed_keys(keys("zz")).unwrap();
+        assert_eq!(editor.scroll_row, 10);
+        editor.feed_keys(keys("zb")).unwrap();
+        assert_eq!(editor.scroll_row, 6);
+        assert_eq!(editor.cursor(), (15, 0));
+
+        // Never scrolls above the first line; other keys after z do nothing
+        editor.feed_keys(keys("3Gzzzb")).unwrap();
+        assert_eq!(editor.scroll_row, 0);
+        editor.feed_keys(keys("zq")).unwrap();
+        assert_eq!((editor.scroll_row, editor.cursor()), (0, (2, 0)));
+        assert_eq!(editor.message(), None);
+    }
+
+    #[test]
+    fn test_screen_motions() {
+        let text: Vec<String> = (0..30).map(|n| format!("line {}", n)).collect();
+        let mut editor = editor_with(&text.join("\n"));
+        editor.scroll_row = 10;
+        editor.cursor_row = 12;
+        editor.feed_keys(keys("H")).unwrap();
+        assert_eq!(editor.cursor(), (10, 0));
+        editor.feed_keys(keys("M")).unwrap();
+        assert_eq!(editor.cursor(), (14, 0));
+        editor.feed_keys(keys("L")).unwrap();
+        assert_eq!(editor.cursor(), (19, 0));
+        assert_eq!(editor.scroll_row, 10);
+
+        // A file shorter than the screen only counts its own lines
+        let mut editor = editor_with("a\nb\nc\nd\ne");
+        editor.feed_keys(keys("M")).unwrap();
+        assert_eq!(editor.cursor(), (2, 0));
+        editor.feed_keys(keys("L")).unwrap();
+        assert_eq!(editor.cursor(), (4, 0));
+        editor.feed_keys(keys("H")).unwrap();
+        assert_eq!(editor.cursor(), (0, 0));
+
+        // The view can start past the end after a mass delete with no redraw since
+        let text: Vec<String> =
+            (0..100).map(|n| if n < 3 { "keep".to_string() } else { format!("line {}", n) }).collect();
+        let mut editor = editor_with(&text.join("\n"));
+        editor.feed_keys(keys("90G")).unwrap();
+        editor.draw_editor(&mut String::new()).unwrap();
+        assert_eq!(editor.scroll_row, 80);
+        editor.feed_keys(keys(":g/l/d\nM")).unwrap();
+        assert_eq!(editor.cursor(), (2, 0));
+        editor.feed_keys(keys("ggH")).unwrap();
+        assert_eq!(editor.cursor(), (2, 0));
+        editor.feed_keys(keys("ggL")).unwrap();
+        assert_eq!(editor.cursor(), (2, 0));
+    }
+
+    #[test]
+    fn test_scroll_lines() {
+        let text: Vec<String> = (0..15).map(|n| format!("line {}", n)).collect();
+        let mut editor = editor_with(&format!("a long first line\n{}", text.join("\n")));
+        editor.feed_keys(keys("$")).unwrap();
+        editor.feed_keys([Key::Ctrl('e')]).unwrap();
+        assert_eq!((editor.scroll_row, editor.cursor()), (1, (1, 5)));
+        editor.feed_keys([Key::Ctrl('e'), Key::Ctrl('e')]).unwrap();
+        assert_eq!((editor.scroll_row, editor.cursor()), (3, (3, 5)));
+
+        // The cursor only moves when it would leave the screen
+        editor.feed_keys([Key::Ctrl('y')]).unwrap();
+        assert_eq!((editor.scroll_row, editor.cursor()), (2, (3, 5)));
+        editor.feed_keys(keys("G")).unwrap();
+        for _ in 0..20 {
+            editor.feed_keys([Key::Ctrl('e')]).unwrap();
+        }
+        assert_eq!((editor.scroll_row, editor.cursor_row), (15, 15));
+        for _ in 0..20 {
+            editor.feed_keys([Key::Ctrl('y')]).unwrap();
+        }
+        assert_eq!((editor.scroll_row, editor.cursor_row), (0, 9));
+    }
+
+    #[test]
+    fn test_delete_and_change_to_line_end() {
+        let mut editor = editor_with("let x = 1;\n\nend");
+        editor.feed_keys(keys("wD")).unwrap();
+        assert_eq!(editor.text(), "let \n\nend");
+        assert_eq!(editor.cursor(), (0, 3));
+        editor.feed_keys(keys("jP")).unwrap();
+        assert_eq!(editor.text(), "let \nx = 1;\nend");
+
+        editor.feed_keys(keys("0wC2\x1b")).unwrap();
+        assert_eq!(editor.text(), "let \nx 2\nend");
+        editor.feed_keys(keys("u")).unwrap();
+        assert_eq!(editor.text(), "let \nx = 1;\nend");
+
+        // Nothing to remove on an empty line
+        let mut editor = editor_with("\nx");
+        editor.feed_keys(keys("jyyk")).unwrap();
+        editor.feed_keys(keys("D")).unwrap();
+        editor.feed_keys(keys("Cy\x1b")).unwrap();
+        assert_eq!(editor.text(), "y\nx");
+        editor.feed_keys(keys("p")).unwrap();
+        assert_eq!(editor.text(), "y\nx\nx");
+    }
+
+    #[test]
+    fn test_change_lines() {
+        let mut editor = editor_with("fn x() {\n    old();\n    more();\n}");
+        editor.feed_keys(keys("jwcc")).unwrap();
+        assert_eq!(editor.mode, Mode::Insert);
+        assert_eq!(editor.cursor(), (1, 4));
+        editor.feed_keys(keys("new();\x1b")).unwrap();
+        assert_eq!(editor.text(), "fn x() {\n    new();\n    more();\n}");
+
+        // The old lines go to the register, and the change is one undo step
+        editor.feed_keys(keys("k2S\x1b")).unwrap();
+        assert_eq!(editor.text(), "\n    more();\n}");
+        editor.feed_keys(keys("p")).unwrap();
+        assert_eq!(editor.text(), "\nfn x() {\n    new();\n    more();\n}");
+        editor.feed_keys(keys("uu")).unwrap();
+        assert_eq!(editor.text(), "fn x() {\n    new();\n    more();\n}");
+
+        editor.feed_keys(keys(":set noai\njS\x1b")).unwrap();
+        assert_eq!(editor.text(), "fn x() {\n\n    more();\n}");
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut editor = editor_with("one\n  two\nthree\nfour");
+        editor.feed_keys(keys("jllmaG")).unwrap();
+        editor.feed_keys(keys("`a")).unwrap();
+        assert_eq!(editor.cursor(), (1, 2));
+        editor.feed_keys(keys("$G'a")).unwrap();
+        assert_eq!(editor.cursor(), (1, 2));
+        editor.feed_keys(keys("ll''")).unwrap();
+        assert_eq!(editor.cursor(), (3, 0));
+
+        // Marks move with their line and are dropped when it is deleted
+        editor.feed_keys(keys("ggOzero\x1b")).unwrap();
+        editor.feed_keys(keys("`a")).unwrap();
+        assert_eq!(editor.cursor(), (2, 2));
+        editor.feed_keys(keys("dd`a")).unwrap();
+        assert_eq!(editor.message(), Some("Mark not set: a"));
+    }
+
+    #[test]
+    fn test_jump_list() {
+        let text: Vec<String> = (0..30).map(|n| format!("line {}", n)).collect();
+        let mut editor = editor_with(&text.join("\n"));
+        editor.feed_keys(keys("5G20G/line 2\n")).unwrap();
+        assert_eq!(editor.cursor(), (20, 0));
+
+        editor.feed_keys([Key::Ctrl('o')]).unwrap();
+        assert_eq!(editor.cursor(), (19, 0));
+        editor.feed_keys([Key::Ctrl('o'), Key::Ctrl('o')]).unwrap();
+        assert_eq!(editor.cursor(), (0, 0));
+        editor.feed_keys([Key::Ctrl('o')]).unwrap();
+        assert_eq!(editor.cursor(), (0, 0));
+        editor.feed_keys(keys("2")).unwrap();
+        editor.feed_keys([Key::Tab]).unwrap();
+        assert_eq!(editor.cursor(), (19, 0));
+        editor.feed_keys([Key::Tab, Key::Tab]).unwrap();
+        assert_eq!(editor.cursor(), (20, 0));
+
+        // A new jump after stepping back drops the newer entries
+        editor.feed_keys([Key::Ctrl('o'), Key::Ctrl('o')]).unwrap();
+        editor.feed_keys(keys("G")).unwrap();
+        editor.feed_keys([Key::Tab]).unwrap();
+        assert_eq!(editor.cursor(), (29, 0));
+        editor.feed_keys([Key::Ctrl('o')]).unwrap();
+        assert_eq!(editor.cursor(), (4, 0));
+    }
+
+    #[test]
+    fn test_macros() {
+        let mut editor = editor_with("a\nb\nc\nd\ne");
+        editor.feed_keys(keys("qaI- \x1bjq")).unwrap();
+        assert_eq!(editor.macros.get(&'a').map(Vec::len), Some(5));
+        let mut output = String::new();
+        editor.feed_keys(keys("qb")).unwrap();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(strip_ansi(&output).contains("recording @b"));
+        editor.feed_keys(keys("@aq")).unwrap();
+        assert_eq!(editor.text(), "- a\n- b\nc\nd\ne");
+
+        editor.feed_keys(keys("2@a")).unwrap();
+        assert_eq!(editor.text(), "- a\n- b\n- c\n- d\ne");
+        editor.feed_keys(keys("@@")).unwrap();
+        assert_eq!(editor.text(), "- a\n- b\n- c\n- d\n- e");
+        editor.feed_keys(keys("u")).unwrap();
+        assert_eq!(editor.text(), "- a\n- b\n- c\n- d\ne");
+
+        // Macros may call others, but one that calls itself stops
+        editor.feed_keys(keys("ggqcx@c")).unwrap();
+        assert_eq!(editor.message(), Some("Register is empty: c"));
+        editor.feed_keys(keys("q")).unwrap();
+        editor.feed_keys(keys("@c")).unwrap();
+        assert_eq!(editor.message(), Some("Recursive macro: @c"));
+        assert_eq!(editor.buffer.lines[0].to_string(), "a");
+        editor.feed_keys(keys("j@b")).unwrap();
+        assert_eq!(editor.buffer.lines[1].to_string(), "- - b");
+    }
+
+    #[test]
+    fn test_incremental_search() {
+        let text: Vec<String> = (0..30).map(|n| format!("item {}", n)).collect();
+        let mut editor = editor_with(&text.join("\n"));
+        editor.feed_keys(keys("/item 2")).unwrap();
+        assert_eq!(editor.mode, Mode::Search);
+        assert_eq!(editor.cursor(), (2, 0));
+        editor.feed_keys(keys("5")).unwrap();
+        assert_eq!(editor.cursor(), (25, 0));
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert_eq!(output.matches(ansi::BG_MAGENTA).count(), "item 25".len());
+
+        // Escape puts back the cursor, the view and the previous pattern
+        editor.feed_keys(keys("\x1b")).unwrap();
+        assert_eq!((editor.cursor(), editor.scroll_row), ((0, 0), 0));
+        assert_eq!(editor.search_buffer, "");
+        assert!(editor.search_matches(2).is_empty());
+
+        // Enter searches from the start, so the jump list returns there
+        editor.feed_keys(keys("/item 1\n")).unwrap();
+        assert_eq!(editor.cursor(), (1, 0));
+        editor.feed_keys(keys("/item 3")).unwrap();
+        editor.feed_keys([Key::Backspace, Key::Backspace]).unwrap();
+        assert_eq!(editor.cursor(), (2, 0));
+        editor.feed_keys([Key::Backspace, Key::Backspace, Key::Backspace]).unwrap();
+        editor.feed_keys([Key::Backspace, Key::Backspace]).unwrap();
+        assert_eq!((editor.mode, editor.cursor()), (Mode::Normal, (1, 0)));
+        assert_eq!(editor.search_buffer, "item 1");
+        editor.feed_keys(keys("?item 2\n")).unwrap();
+        assert_eq!(editor.cursor(), (29, 0));
+        editor.feed_keys([Key::Ctrl('o')]).unwrap();
+        assert_eq!(editor.cursor(), (1, 0));
+    }
+
+    #[test]
+    fn test_colorscheme() {
+        let mut editor = editor_with("fn main() {}");
+        editor.highlighter = Highlighter::for_file(None, Some("rs"), None);
+        editor.feed_keys(keys(":colorscheme\n")).unwrap();
+        assert_eq!(editor.message.as_deref(), Some("dark"));
+
+        editor.feed_keys(keys(":colo light\n")).unwrap();
+        assert_eq!(editor.theme.name, "light");
+        editor.truecolor = true;
+        editor.feed_keys(keys(":colo dark\n")).unwrap();
+        assert_eq!(editor.theme.line_number, crate::theme::Color::Rgb(0x5c, 0x63, 0x70));
+        editor.feed_keys(keys(":colo solarized\n")).unwrap();
+        assert_eq!(editor.message.as_deref(), Some("Unknown color scheme: solarized (try dark, light or custom)"));
+
+        editor.theme = Theme::parse("keyword = #102030\nstatus_bg = #000000\nline_number = #404040").unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(output.contains(&format!("{}{}fn", ansi::fg_rgb(16, 32, 48), ansi::BOLD)));
+        assert!(output.contains(&format!("{}   1 ", ansi::fg_rgb(64, 64, 64))));
+        assert!(output.contains(&ansi::bg_rgb(0, 0, 0)));
+    }
+
+    #[test]
+    fn test_cursorline() {
+        let mut editor = editor_with("one\ntwo");
+        editor.highlighter = Highlighter::for_file(None, Some("rs"), None);
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(!output.contains(ansi::BG_BLACK));
+
+        editor.feed_keys(keys(":set cursorline\nj")).unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        // The gutter, the text and the rest of the row share the background
+        let gutter = format!("{}{}   2 {}{}", ansi::BG_BLACK, ansi::FG_BRIGHT_BLACK, ansi::RESET, ansi::BG_BLACK);
+        assert!(output.contains(&gutter));
+        assert!(output.contains(&format!("two{}{}{}{}", ansi::RESET, ansi::BG_BLACK, " ".repeat(72), ansi::RESET)));
+        assert!(!output.contains(&format!("{}{}   1", ansi::BG_BLACK, ansi::FG_BRIGHT_BLACK)));
+
+        editor.feed_keys(keys(":set nocul\n")).unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(!output.contains(ansi::BG_BLACK));
+    }
+
+    #[test]
+    fn test_colorcolumn() {
+        let mut editor = editor_with(&format!("short\nthis line is long\n{}", "y".repeat(100)));
+        let ruler = format!("{} {}", ansi::BG_RED, ansi::BG_DEFAULT);
+        editor.feed_keys(keys(":set cc=8\n")).unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        // Padded out on the short line, and behind the eighth char of the long one
+        assert!(output.contains(&format!("short{}{}", ansi::RESET, &format!("  {}", ruler))));
+        assert!(output.contains(&format!("{}n{}", ansi::BG_RED, ansi::BG_DEFAULT)));
+
+        // Scrolled sideways the column moves with the text
+        editor.feed_keys(keys(":set nonu\n:set cc=85\nG$")).unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert_eq!(editor.scroll_col, 20);
+        assert!(output.contains(&format!("{}{}", " ".repeat(64), ruler)));
+        assert_eq!(output.matches(&format!("{}y{}", ansi::BG_RED, ansi::BG_DEFAULT)).count(), 1);
+
+        editor.feed_keys(keys(":set cc=x\n")).unwrap();
+        assert_eq!(editor.message.as_deref(), Some("Invalid colorcolumn: x"));
+        editor.feed_keys(keys(":set colorcolumn=0\n:set cc\n")).unwrap();
+        assert_eq!(editor.message.as_deref(), Some("colorcolumn=0"));
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(!output.contains(ansi::BG_RED));
+    }
+
+    #[test]
+    fn test_regex_search_and_substitute() {
+        let mut editor = editor_with("a.c abc\nkey = value\nfoo");
+        editor.feed_keys(keys("/a.c\n")).unwrap();
+        assert_eq!(editor.message.as_deref(), Some("Match 1 of 1 (search wrapped)"));
+        assert_eq!(editor.search_matches(0), [(0, 3)]);
+        // The cached pattern is rebuilt when `:set regex` changes
+        editor.feed_keys(keys(":set regex\n")).unwrap();
+        assert_eq!(editor.search_matches(0), [(0, 3), (4, 7)]);
+        editor.feed_keys(keys("/a.c\n")).unwrap();
+        assert_eq!(editor.cursor(), (0, 4));
+        assert_eq!(editor.search_matches(0), [(0, 3), (4, 7)]);
+        editor.feed_keys(keys("?^\\w+ =\n")).unwrap();
+        assert_eq!(editor.cursor(), (1, 0));
+
+        editor.feed_keys(keys(":s/(\\w+) = (\\w+)/\\2: \\1/\n")).unwrap();
+        assert_eq!(editor.buffer.lines[1].to_string(), "value: key");
+        editor.feed_keys(keys(":%s/[a-c]/<&>/g\n")).unwrap();
+        assert_eq!(editor.message.as_deref(), Some("6 replacements"));
+        assert_eq!(editor.buffer.lines[0].to_string(), "<a>.<c> <a><b><c>");
+
+        editor.feed_keys(keys(":s/(x/y/\n")).unwrap();
+        assert_eq!(editor.message.as_deref(), Some("Invalid pattern: unmatched ("));
+        editor.feed_keys(keys("/fo[\n")).unwrap();
+        assert_eq!(editor.message.as_deref(), Some("Invalid pattern: unmatched ["));
+    }
+
+    #[test]
+    fn test_visual_yank_and_delete() {
+        let mut editor = editor_with("one two\nthree four\nfive");
+        editor.feed_keys(keys("wvjy")).unwrap();
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.cursor(), (0, 4));
+        let yanked: Vec<String> = editor.register.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(yanked, ["two", "three"]);
+
+        // A backwards selection covers the same text
+        editor.feed_keys(keys("jlvhhhd")).unwrap();
+        assert_eq!(editor.text(), "one two\nthfour\nfive");
+        assert_eq!(editor.register.lines[0].to_string(), "ree ");
+        assert_eq!(editor.cursor(), (1, 2));
+
+        // Linewise selections take whole lines
+        editor.feed_keys(keys("Vkx")).unwrap();
+        assert_eq!(editor.text(), "five");
+        assert!(editor.register.linewise);
+        assert_eq!(editor.register.lines.len(), 2);
+    }
+
+    #[test]
+    fn test_visual_escape_and_reselect() {
+        let mut editor = editor_with("abc\ndef");
+        editor.feed_keys(keys("lvj")).unwrap();
+        assert_eq!(editor.mode(), Mode::Visual);
+        editor.feed_keys([Key::Escape]).unwrap();
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.text(), "abc\ndef");
+
+        editor.feed_keys(keys("gggvd")).unwrap();
+        assert_eq!(editor.text(), "af");
+    }
+
+    #[test]
+    fn test_draw_visual_selection() {
+        let mut editor = editor_with("abcd");
+        editor.show_line_numbers = false;
+        editor.feed_keys(keys("lvl")).unwrap();
+        let mut output = String::new();
+        editor.draw_editor(&mut output).unwrap();
+        assert!(output.contains(&format!("{}b{}", ansi::REVERSE, ansi::REVERSE_OFF)));
+        assert!(output.contains(&format!("{}c{}", ansi::REVERSE, ansi::REVERSE_OFF)));
+        assert!(!output.contains(&format!("{}a", ansi::REVERSE)));
+        assert!(strip_ansi(&output).contains(" VISUAL "));
+    }
+
+    #[test]
+    fn test_headless_text_object() {
+        let mut editor = editor_with("call(foo, bar)");
+        editor.cursor_col = 6;
+        editor.feed_keys(keys("ciwbaz\x1b")).unwrap();
+        assert_eq!(editor.text(), "call(baz, bar)");
+
+        editor.feed_keys(keys("di(")).unwrap();
+        assert_eq!(editor.text(), "call()");
     }
 }
@@ -0,0 +1,293 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Maximum directory depth walked when listing project files
+const MAX_DEPTH: usize = 16;
+
+/// Maximum number of files collected when listing project files
+const MAX_FILES: usize = 20_000;
+
+/// Recursively list files under `root`, skipping hidden entries and simple `.gitignore` names
+pub fn walk_files(root: &Path) -> Vec<PathBuf> {
+    let ignored = read_gitignore(root);
+    let mut files = Vec::new();
+    let mut stack = vec![(root.to_path_buf(), 0)];
+
+    while let Some((dir, depth)) = stack.pop() {
+        let Ok(read_dir) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in read_dir.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || ignored.contains(&name) {
+                continue;
+            }
+
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+
+            if file_type.is_dir() {
+                if depth + 1 < MAX_DEPTH {
+                    stack.push((entry.path(), depth + 1));
+                }
+            } else {
+                files.push(entry.path());
+                if files.len() >= MAX_FILES {
+                    files.sort();
+                    return files;
+                }
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Read plain names (no globs or negations) from the root `.gitignore`
+fn read_gitignore(root: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(root.join(".gitignore")) else {
+        return Vec::new();
+    };
+
+    content
+        .lines()
+        .map(|line| line.trim().trim_matches('/'))
+        .filter(|line| {
+            !line.is_empty()
+                && !line.starts_with('#')
+                && !line.starts_with('!')
+                && !line.contains(['*', '?', '[', '/'])
+        })
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Score `candidate` against `query` as a case-insensitive subsequence match.
+/// Returns `None` if the query characters don't all appear in order.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score: i64 = 0;
+    let mut pos = 0;
+    let mut prev_match: Option<usize> = None;
+
+    for qc in query.chars() {
+        let qc = qc.to_ascii_lowercase();
+        let found = (pos..candidate.len()).find(|&i| candidate[i].to_ascii_lowercase() == qc)?;
+
+        score += 1;
+        if prev_match.is_some_and(|p| p + 1 == found) {
+            // Consecutive characters
+            score += 8;
+        } else if let Some(p) = prev_match {
+            score -= (found - p - 1).min(5) as i64;
+        }
+        if found == 0 || matches!(candidate[found - 1], '/' | '\\' | '_' | '-' | '.' | ' ') {
+            // Start of a path segment or word
+            score += 6;
+        }
+
+        prev_match = Some(found);
+        pos = found + 1;
+    }
+
+    // Prefer matches in the file name over the directories
+    let name_start = candidate
+        .iter()
+        .rposition(|&c| c == '/' || c == '\\')
+        .map_or(0, |i| i + 1);
+    if prev_match.is_some_and(|p| p >= name_start) {
+        score += 10;
+    }
+
+    Some(score - candidate.len() as i64 / 10)
+}
+
+/// Fuzzy file picker state
+pub struct Finder {
+    pub root: PathBuf,
+    pub files: Vec<String>,
+    pub query: String,
+    pub matches: Vec<usize>,
+    pub selected: usize,
+    pub scroll_offset: usize,
+}
+
+impl Finder {
+    pub fn new(root: &Path) -> Self {
+        let files = walk_files(root)
+            .iter()
+            .map(|p| {
+                p.strip_prefix(root)
+                    .unwrap_or(p)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+
+        let mut finder = Finder {
+            root: root.to_path_buf(),
+            files,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            scroll_offset: 0,
+        };
+        finder.update_matches();
+        finder
+    }
+
+    /// Recompute the ranked match list for the current query
+    pub fn update_matches(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| fuzzy_score(&self.query, f).map(|s| (s, i)))
+            .collect();
+
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+        self.scroll_offset = 0;
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.update_matches();
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.query.push_str(text);
+        self.update_matches();
+    }
+
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.update_matches();
+    }
+
+    pub fn move_up(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Full path of the selected match
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        self.matches
+            .get(self.selected)
+            .map(|&i| self.root.join(&self.files[i]))
+    }
+
+    /// Update scroll offset for display
+    pub fn update_scroll(&mut self, visible_height: usize) {
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        } else if self.selected >= self.scroll_offset + visible_height {
+            self.scroll_offset = self.selected - visible_height + 1;
+        }
+    }
+
+    /// Get visible matches as (match index, relative path)
+    pub fn visible_matches(&self, visible_height: usize) -> impl Iterator<Item = (usize, &str)> {
+        self.matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll_offset)
+            .take(visible_height)
+            .map(|(idx, &file)| (idx, self.files[file].as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score_subsequence() {
+        assert!(fuzzy_score("edr", "src/editor.rs").is_some());
+        assert!(fuzzy_score("", "anything").is_some());
+        assert!(fuzzy_score("xyz", "src/editor.rs").is_none());
+        assert!(fuzzy_score("rse", "src/editor.rs").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_score_case_insensitive() {
+        assert!(fuzzy_score("README", "readme.md").is_some());
+        assert!(fuzzy_score("readme", "README.md").is_some());
+    }
+
+    #[test]
+    fn test_fuzzy_score_prefers_contiguous_and_filename() {
+        let contiguous = fuzzy_score("edit", "src/editor.rs").unwrap();
+        let scattered = fuzzy_score("edit", "src/e_d_i_t.rs").unwrap();
+        assert!(contiguous > scattered);
+
+        let in_name = fuzzy_score("main", "src/main.rs").unwrap();
+        let in_dir = fuzzy_score("main", "main/lib.rs").unwrap();
+        assert!(in_name > in_dir);
+    }
+
+    #[test]
+    fn test_walk_files_skips_hidden_and_ignored() {
+        let temp_dir = std::env::temp_dir().join("zedit_finder_walk_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src").join("deep")).unwrap();
+        fs::create_dir_all(temp_dir.join(".git")).unwrap();
+        fs::create_dir_all(temp_dir.join("target")).unwrap();
+        fs::write(temp_dir.join("src").join("deep").join("a.rs"), "").unwrap();
+        fs::write(temp_dir.join("src").join("b.rs"), "").unwrap();
+        fs::write(temp_dir.join(".git").join("HEAD"), "").unwrap();
+        fs::write(temp_dir.join("target").join("out"), "").unwrap();
+        fs::write(temp_dir.join(".gitignore"), "/target\n*.log\n").unwrap();
+
+        let files = walk_files(&temp_dir);
+        assert_eq!(
+            files,
+            vec![
+                temp_dir.join("src").join("b.rs"),
+                temp_dir.join("src").join("deep").join("a.rs"),
+            ]
+        );
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_finder_matches_and_selection() {
+        let temp_dir = std::env::temp_dir().join("zedit_finder_select_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src")).unwrap();
+        fs::write(temp_dir.join("src").join("editor.rs"), "").unwrap();
+        fs::write(temp_dir.join("src").join("main.rs"), "").unwrap();
+
+        let mut finder = Finder::new(&temp_dir);
+        assert_eq!(finder.matches.len(), 2);
+
+        for c in "main".chars() {
+            finder.push_char(c);
+        }
+        assert_eq!(finder.matches.len(), 1);
+        assert_eq!(finder.selected_path(), Some(temp_dir.join("src").join("main.rs")));
+
+        finder.pop_char();
+        finder.pop_char();
+        finder.pop_char();
+        finder.pop_char();
+        finder.move_down();
+        assert_eq!(finder.selected, 1);
+        finder.move_down();
+        assert_eq!(finder.selected, 1);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+}
@@ -1,12 +1,22 @@
 mod browser;
 mod buffer;
+mod completion;
 mod editor;
+mod finder;
+mod regex;
+mod state;
 mod syntax;
 mod terminal;
+mod textobject;
+mod theme;
+mod undo;
 
+use buffer::Buffer;
 use editor::Editor;
 use std::env;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal};
+use std::panic;
+use std::path::{Path, PathBuf};
 use std::process;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -16,45 +26,122 @@ fn print_help() {
     println!("{} v{}", NAME, VERSION);
     println!("Fast, dependency-free editing with syntax highlighting and directory browsing.");
     println!();
-    println!("Usage: {} [options] [file/directory]", NAME);
+    println!("Usage: {} [options] [+cmd] [file/directory]", NAME);
+    println!("       <command> | {} [options]   (edit piped input)", NAME);
     println!();
     println!("Options:");
     println!("  -h, --help     Show this help message and exit");
     println!("  -v, --version  Show version information and exit");
+    println!("  -R, --readonly Open the file read-only");
+    println!("  -c <command>   Run a : command after opening (repeatable, in order)");
+    println!("  +<line>        Start at a line number (+ alone for the last line)");
+    println!("  +/<pattern>    Start at the first match of a pattern");
+    println!("  <file>:<line>[:<col>]  Open a file at a line (and column)");
     println!();
     println!("Keybindings (Normal mode):");
     println!("  h/j/k/l        Move left/down/up/right");
-    println!("  w/b            Move word forward/backward");
+    println!("  w/b/e          Move to next word/previous word/end of word");
+    println!("  W/B/E          Same, with words separated only by whitespace");
+    println!("  Ctrl+Left/Right  Move word backward/forward (also in insert mode)");
     println!("  0/$            Move to start/end of line");
-    println!("  g/G            Move to first/last line");
+    println!("  f/F<char>      Move to the next/previous <char> on the line");
+    println!("  t/T<char>      Move to just before/after the next/previous <char>");
+    println!("  %              Jump to the matching bracket");
+    println!("  ;/,            Repeat the last f/F/t/T forward/backward");
+    println!("  <count><cmd>   Repeat a motion or command, e.g. 3j, 2dd, 10G (line 10)");
+    println!("  gg/G           Move to first/last line");
+    println!("  ``             Jump back to the position before the last jump");
+    println!("  m<a-z>         Set a mark; `<a-z> jumps to it, '<a-z> to its line");
+    println!("  Ctrl+o/Ctrl+i  Go to the previous/next position in the jump list");
+    println!("  gi             Insert where insert mode was last left");
+    println!("  H/M/L          Move to top/middle/bottom of the screen");
+    println!("  Ctrl+e/Ctrl+y  Scroll the view down/up one line");
+    println!("  zz/zt/zb       Put the current line at center/top/bottom of the screen");
     println!("  i/I            Enter insert mode (at cursor/line start)");
     println!("  a/A            Enter insert mode (after cursor/line end)");
     println!("  o/O            Insert new line below/above");
     println!("  x              Delete character");
-    println!("  d              Delete line");
-    println!("  e              Open file browser");
-    println!("  /              Search forward");
+    println!("  ~              Toggle the case of the character under the cursor");
+    println!("  >>/<<          Indent/unindent the line by the shiftwidth (3>> shifts three)");
+    println!("  J              Join the next line onto this one (3J joins three lines)");
+    println!("  r<char>        Replace the character under the cursor (3ra replaces three)");
+    println!("  dd             Delete line");
+    println!("  D/C            Delete/change to the end of the line");
+    println!("  cc/S           Change the whole line, keeping its indentation");
+    println!("  dw/d$/d0       Delete to next word/end of line/start of line (also c, y)");
+    println!("  yy             Yank (copy) line");
+    println!("  p/P            Paste after/before the cursor");
+    println!("  v/V            Select characters/lines (y yanks, d or x deletes)");
+    println!("  gv             Reselect the last visual selection");
+    println!("  u/Ctrl+r       Undo/redo");
+    println!("  .              Repeat the last change");
+    println!("  q<a-z>/q       Start/stop recording a macro");
+    println!("  @<a-z>/@@      Replay a macro/the last one (5@a replays it five times)");
+    println!("  ciw, da(, yi\"  Change/delete/yank a text object (w W quotes brackets)");
+    println!("  -              Open file browser");
+    println!("  /              Search forward (jumps to matches as you type; Esc goes back)");
     println!("  ?              Search backward");
     println!("  n/N            Next/previous search result");
     println!("  :              Enter command mode");
+    println!("  Ctrl+p         Fuzzy find a file under the current directory");
     println!("  Ctrl+s         Save file");
-    println!("  Ctrl+q         Quit");
+    println!("  Ctrl+q         Quit (asks to save unsaved changes)");
     println!();
     println!("Commands:");
     println!("  :w             Save file");
     println!("  :w <file>      Save as file");
-    println!("  :q             Quit (fails if unsaved changes)");
+    println!("  :w! [file]     Save even if read-only, creating directories");
+    println!("  :q             Quit (asks to save unsaved changes)");
     println!("  :q!            Force quit");
     println!("  :wq            Save and quit");
-    println!("  :e <file>      Edit file");
-    println!("  :e             Open file browser");
-    println!("  :<number>      Go to line number");
+    println!("  :e <file>      Edit file in a new buffer");
+    println!("  :bn/:bp        Switch to the next/previous buffer");
+    println!("  :b <n>         Switch to buffer n");
+    println!("  :ls            List open buffers");
+    println!("  :sp [file]     Split the window (Ctrl+w w/j/k to move, :q or :close to close)");
+    println!("  :e!            Reload the file from disk, discarding changes");
+    println!("  :view <file>   Open file read-only (:set noreadonly to edit)");
+    println!("  :e             Open file browser in the working directory");
+    println!("  :pwd / :cd <dir>  Show/change the working directory");
+    println!("  Up/Down        Recall previous commands or searches");
+    println!("  Tab            Complete file names after :e/:w and options after :set");
+    println!("  :<number>      Go to line number (:$ for the last line)");
+    println!("  :<number>%     Go to a percentage through the file");
+    println!("  :sort[!] [ui]  Sort lines (! reverse, u unique, i ignore case)");
+    println!("  :g/pat/d       Delete lines containing pat (:v/pat/d for the rest)");
+    println!("  :s/old/new/    Replace the first occurrence on the current line");
+    println!("  :s/old/new/g   Replace all occurrences on the line (:%s for every line)");
+    println!("                 Add i to the flags to ignore case");
+    println!("  :set regex     Use regular expressions in / ? and :s (\\1 in the replacement for groups)");
+    println!("  :noh           Clear search highlighting until the next search");
+    println!("  :set ft=<lang> Set the syntax language (e.g. rust, sh, none)");
+    println!("  :set ff=dos    Save with CRLF line endings (ff=unix for LF)");
+    println!("  :set fixeol    Always end the file with a newline (kept as loaded by default)");
+    println!("  :set trimtrailing  Strip trailing whitespace when saving");
+    println!("  :set nonumber  Hide line numbers (:set number shows them)");
+    println!("  :set relativenumber  Number lines by distance from the cursor");
+    println!("  :set list      Highlight trailing whitespace and indent tabs");
+    println!("  :set cursorline  Highlight the line the cursor is on");
+    println!("  :set colorcolumn=<n>  Mark column n on every line as a ruler (0 turns it off)");
+    println!("  :set rainbow   Color brackets by nesting depth");
+    println!("  :set clipboard=unnamed  Copy yanks to the system clipboard (OSC 52)");
+    println!("  :set shiftwidth=<n>  Columns that >> and << shift by (default 4)");
+    println!("  :set tabstop=<n>  Width of a tab (default 4)");
+    println!("  :set noexpandtab  Insert a real tab for Tab (default: tabstop spaces)");
+    println!("  :set autoindent  Keep the current indentation on new lines (on by default)");
+    println!("  :set autopairs Auto-close brackets and quotes (on by default)");
+    println!("  :set swapfile  Keep unsaved changes in .<name>.zedit.swp (on by default)");
+    println!("  :colorscheme <name>  Switch colors: dark, light, or custom (~/.config/zedit/theme)");
+    println!("                 Built-in schemes use 24-bit color when COLORTERM is truecolor");
     println!();
     println!("File Browser:");
     println!("  j/k or arrows  Navigate");
     println!("  Enter/l        Open file/directory");
     println!("  h/Backspace    Go to parent directory");
     println!("  .              Toggle hidden files");
+    println!("  s/S            Cycle sort mode/reverse sort order");
+    println!("  d              Toggle directories first");
+    println!("  t              Toggle tree view (Enter expands/collapses)");
     println!("  q/Esc          Close browser");
 }
 
@@ -62,12 +149,22 @@ fn print_version() {
     println!("{} v{}", NAME, VERSION);
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+/// Command-line options
+#[derive(Default)]
+struct Options {
+    path: Option<PathBuf>,
+    /// 1-based line and optional column from a `file:line[:col]` argument
+    position: Option<(usize, Option<usize>)>,
+    readonly: bool,
+    /// Commands from `+cmd` and `-c cmd`, run in order after opening the file
+    commands: Vec<String>,
+}
 
-    let mut path: Option<PathBuf> = None;
+fn main() {
+    let mut options = Options::default();
+    let mut args = env::args().skip(1);
 
-    for arg in args.iter().skip(1) {
+    while let Some(arg) = args.next() {
         match arg.as_str() {
             "-h" | "--help" => {
                 print_help();
@@ -77,19 +174,31 @@ fn main() {
                 print_version();
                 return;
             }
+            "-R" | "--readonly" => options.readonly = true,
+            "-c" => match args.next() {
+                Some(cmd) => options.commands.push(cmd),
+                None => {
+                    eprintln!("Option -c requires a command");
+                    process::exit(1);
+                }
+            },
+            "+" => options.commands.push("$".to_string()),
+            arg if arg.starts_with('+') => options.commands.push(arg[1..].to_string()),
             arg if arg.starts_with('-') => {
                 eprintln!("Unknown option: {}", arg);
                 eprintln!("Use --help for usage information.");
                 process::exit(1);
             }
             _ => {
-                path = Some(PathBuf::from(arg));
+                let (path, position) = split_position(&arg);
+                options.path = Some(PathBuf::from(path));
+                options.position = position;
             }
         }
     }
 
     // Run the editor
-    let result = run_editor(path);
+    let result = run_editor(options);
 
     if let Err(e) = result {
         eprintln!("Error: {}", e);
@@ -97,18 +206,75 @@ fn main() {
     }
 }
 
-fn run_editor(path: Option<PathBuf>) -> std::io::Result<()> {
+/// Split a trailing `:line` or `:line:col` off a path argument, as printed by compilers
+/// and grep. Only all-digit suffixes count, so `C:\foo` and existing files named with
+/// colons are left alone.
+fn split_position(arg: &str) -> (&str, Option<(usize, Option<usize>)>) {
+    if Path::new(arg).exists() {
+        return (arg, None);
+    }
+    let number = |s: &str| {
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse::<usize>().ok()
+        } else {
+            None
+        }
+    };
+
+    let Some((rest, last)) = arg.rsplit_once(':') else {
+        return (arg, None);
+    };
+    let Some(last) = number(last) else {
+        return (arg, None);
+    };
+    if let Some((path, line)) = rest.rsplit_once(':')
+        && let Some(line) = number(line)
+        && !path.is_empty()
+    {
+        return (path, Some((line, Some(last))));
+    }
+    if rest.is_empty() {
+        return (arg, None);
+    }
+    (rest, Some((last, None)))
+}
+
+/// Restore the terminal before the default hook prints the panic message, so the
+/// message is readable and the shell is usable afterwards
+fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        terminal::Terminal::clear_screen();
+        terminal::Terminal::restore();
+        default_hook(info);
+    }));
+}
+
+fn run_editor(options: Options) -> io::Result<()> {
+    install_panic_hook();
+
+    // Piped input becomes the buffer; keys then come from the terminal instead
+    let piped = if options.path.is_none() && !io::stdin().is_terminal() {
+        let buffer = Buffer::from_reader(io::stdin().lock())?;
+        terminal::Terminal::reattach_stdin()?;
+        Some(buffer)
+    } else {
+        None
+    };
+
     let mut editor = Editor::new()?;
+    if let Some(buffer) = piped {
+        editor.open_unnamed(buffer);
+    }
 
-    if let Some(p) = path {
-        if p.exists() {
-            editor.open(&p)?;
-        } else if p.to_string_lossy().contains('.') {
-            // Assume it's a new file
-            editor.open(&p).or_else(|_: std::io::Error| -> std::io::Result<()> {
-                // Create new file by just setting the path
-                Ok(())
-            })?;
+    if let Some(p) = options.path {
+        if p.exists() || p.to_string_lossy().contains('.') {
+            // Unreadable files and new files are reported inside the editor
+            if options.readonly {
+                editor.open_readonly(&p);
+            } else {
+                editor.open_or_report(&p);
+            }
         } else {
             // Could be a new directory or file
             eprintln!("Path does not exist: {}", p.display());
@@ -116,5 +282,29 @@ fn run_editor(path: Option<PathBuf>) -> std::io::Result<()> {
         }
     }
 
+    if let Some((line, col)) = options.position {
+        editor.go_to(line, col.unwrap_or(1));
+    }
+
+    for cmd in &options.commands {
+        editor.run_startup_command(cmd);
+    }
+
     editor.run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_position() {
+        assert_eq!(split_position("src/main.rs"), ("src/main.rs", None));
+        assert_eq!(split_position("src/x.rs:120"), ("src/x.rs", Some((120, None))));
+        assert_eq!(split_position("src/x.rs:12:5"), ("src/x.rs", Some((12, Some(5)))));
+        assert_eq!(split_position("notes:txt"), ("notes:txt", None));
+        assert_eq!(split_position(r"C:\foo"), (r"C:\foo", None));
+        assert_eq!(split_position(r"C:\foo.rs:3"), (r"C:\foo.rs", Some((3, None))));
+        assert_eq!(split_position(":7"), (":7", None));
+    }
+}
@@ -0,0 +1,597 @@
+/// Longest pattern accepted, so a pasted blob can't make matching crawl
+const MAX_PATTERN_LEN: usize = 1000;
+
+/// One element of a parsed pattern
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    /// `.`: any char
+    Any,
+    /// `[...]`, `\d`, `\w`, `\s` and their negations
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    /// `^`
+    Start,
+    /// `$`
+    End,
+    /// Alternatives, each a sequence, and the capture group they fill (0 for the whole
+    /// match)
+    Group(Vec<Vec<Node>>, usize),
+    /// `*`, `+` or `?` applied to a node: at least `min`, at most `max` times
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+/// A match: char columns of the whole match and of each capture group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    groups: Vec<Option<(usize, usize)>>,
+}
+
+/// One instruction of a compiled pattern
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    Start,
+    End,
+    /// Continue at both targets, preferring the first
+    Split(usize, usize),
+    Jump(usize),
+    /// Record the current position in a capture slot
+    Save(usize),
+    Match,
+}
+
+/// A compiled search pattern. Supports `.`, `*`, `+`, `?`, `[...]` classes, `^`, `$`,
+/// `|` and `(...)` groups, plus `\d`, `\w` and `\s`. Plain text compiles through
+/// `literal`, so both kinds of search share one matcher.
+///
+/// Matching runs all alternatives in step through the text (a Pike VM), so it takes
+/// time proportional to the line length times the pattern length and never recurses.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+    ignore_case: bool,
+    literal: bool,
+}
+
+impl Regex {
+    /// Compile a pattern, or describe why it is invalid
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+        if pattern.chars().count() > MAX_PATTERN_LEN {
+            return Err("pattern too long".to_string());
+        }
+        let mut parser = Parser { chars: pattern.chars().collect(), pos: 0, groups: 0 };
+        let alternatives = parser.parse_alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err("unmatched )".to_string());
+        }
+        Ok(Regex {
+            program: compile(&Node::Group(alternatives, 0)),
+            groups: parser.groups,
+            ignore_case,
+            literal: false,
+        })
+    }
+
+    /// A pattern matching `text` exactly, with no special characters
+    pub fn literal(text: &str, ignore_case: bool) -> Regex {
+        Regex {
+            program: compile(&Node::Group(vec![text.chars().map(Node::Char).collect()], 0)),
+            groups: 0,
+            ignore_case,
+            literal: true,
+        }
+    }
+
+    /// The first match starting at or after `from`
+    pub fn find_at(&self, text: &[char], from: usize) -> Option<Match> {
+        self.run(text, from, true)
+    }
+
+    /// The first match of at least one char starting at or after `from`
+    pub fn find_non_empty_at(&self, text: &[char], from: usize) -> Option<Match> {
+        self.run(text, from, false)
+    }
+
+    /// Non-overlapping, non-empty matches in `text` as (start, end) columns
+    pub fn find_iter(&self, text: &[char]) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut from = 0;
+        while let Some(m) = self.find_non_empty_at(text, from) {
+            matches.push((m.start, m.end));
+            from = m.end;
+        }
+        matches
+    }
+
+    /// Run the program over `text` from `from`, starting a new thread at each position
+    /// until one matches. Threads are kept in priority order, so the first to reach
+    /// `Match` wins the way a backtracking matcher's first success would.
+    fn run(&self, text: &[char], from: usize, allow_empty: bool) -> Option<Match> {
+        if from > text.len() {
+            return None;
+        }
+        let slots = 2 * (self.groups + 1);
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut found: Option<Vec<Option<usize>>> = None;
+
+        for pos in from..=text.len() {
+            if found.is_none() {
+                // Lowest priority: a match starting here loses to any started earlier
+                self.add_thread(&mut current, 0, pos, vec![None; slots], text.len());
+            }
+            if current.list.is_empty() {
+                break;
+            }
+            for (pc, slots) in current.list.drain(..) {
+                match &self.program[pc] {
+                    Inst::Match => {
+                        if allow_empty || slots[0] != Some(pos) {
+                            found = Some(slots);
+                            // Threads after this one have lower priority
+                            break;
+                        }
+                    }
+                    inst => {
+                        if text.get(pos).is_some_and(|&c| self.matches_char(inst, c)) {
+                            self.add_thread(&mut next, pc + 1, pos + 1, slots, text.len());
+                        }
+                    }
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+
+        let slots = found?;
+        let groups = (0..=self.groups)
+            .map(|group| Some((slots[2 * group]?, slots[2 * group + 1]?)))
+            .collect::<Vec<_>>();
+        let (start, end) = groups[0]?;
+        Some(Match { start, end, groups })
+    }
+
+    /// Add the thread at `pc`, following jumps, splits, saves and anchors to the
+    /// instructions that consume a char. Uses an explicit stack so deep patterns
+    /// can't overflow the call stack.
+    fn add_thread(&self, threads: &mut Threads, pc: usize, pos: usize, slots: Vec<Option<usize>>, len: usize) {
+        let mut stack = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = stack.pop() {
+            if threads.seen[pc] {
+                continue;
+            }
+            threads.seen[pc] = true;
+            match self.program[pc] {
+                Inst::Jump(target) => stack.push((target, slots)),
+                Inst::Split(first, second) => {
+                    // Pushed in reverse so the preferred branch is explored first
+                    stack.push((second, slots.clone()));
+                    stack.push((first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[slot] = Some(pos);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::Start => {
+                    if pos == 0 {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::End => {
+                    if pos == len {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                _ => threads.list.push((pc, slots)),
+            }
+        }
+    }
+
+    fn matches_char(&self, inst: &Inst, c: char) -> bool {
+        match inst {
+            Inst::Any => true,
+            Inst::Char(expected) => {
+                c == *expected || (self.ignore_case && c.to_lowercase().eq(expected.to_lowercase()))
+            }
+            Inst::Class { ranges, negated } => {
+                let in_class = |c: char| ranges.iter().any(|&(start, end)| (start..=end).contains(&c));
+                let found = in_class(c)
+                    || (self.ignore_case && (c.to_lowercase().any(in_class) || c.to_uppercase().any(in_class)));
+                found != *negated
+            }
+            _ => false,
+        }
+    }
+
+    /// The text to replace a match with. `&` and `\0` stand for the whole match and
+    /// `\1` to `\9` for groups; a backslash makes any other char literal. Replacements
+    /// for a literal pattern are used as typed.
+    pub fn expand(&self, replacement: &str, text: &[char], m: &Match) -> Vec<char> {
+        if self.literal {
+            return replacement.chars().collect();
+        }
+        let group = |index: usize| -> &[char] {
+            match m.groups.get(index).copied().flatten() {
+                Some((start, end)) => &text[start..end],
+                None => &[],
+            }
+        };
+
+        let mut result = Vec::new();
+        let mut chars = replacement.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '&' => result.extend_from_slice(group(0)),
+                '\\' => match chars.next() {
+                    Some(d @ '0'..='9') => result.extend_from_slice(group(d as usize - '0' as usize)),
+                    Some(other) => result.push(other),
+                    None => result.push('\\'),
+                },
+                c => result.push(c),
+            }
+        }
+        result
+    }
+}
+
+/// Recursive-descent parser from pattern text to nodes
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    /// Sequences separated by `|`, up to a `)` or the end
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn parse_sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            let (min, max) = match c {
+                '|' | ')' => break,
+                '*' => (0, None),
+                '+' => (1, None),
+                '?' => (0, Some(1)),
+                _ => {
+                    nodes.push(self.parse_atom()?);
+                    continue;
+                }
+            };
+            self.pos += 1;
+            match nodes.pop() {
+                Some(node @ (Node::Start | Node::End | Node::Repeat(..))) => {
+                    return Err(format!("can't repeat {}", describe(&node)));
+                }
+                Some(node) => nodes.push(Node::Repeat(Box::new(node), min, max)),
+                None => return Err(format!("nothing to repeat before {}", c)),
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let c = self.next().ok_or("unexpected end of pattern")?;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                self.groups += 1;
+                let index = self.groups;
+                let alternatives = self.parse_alternatives()?;
+                if self.next() != Some(')') {
+                    return Err("unmatched (".to_string());
+                }
+                Node::Group(alternatives, index)
+            }
+            '[' => self.parse_class()?,
+            '\\' => match self.next().ok_or("trailing backslash")? {
+                'd' => class(DIGIT, false),
+                'D' => class(DIGIT, true),
+                'w' => class(WORD, false),
+                'W' => class(WORD, true),
+                's' => class(SPACE, false),
+                'S' => class(SPACE, true),
+                't' => Node::Char('\t'),
+                other => Node::Char(other),
+            },
+            c => Node::Char(c),
+        })
+    }
+
+    /// The rest of a `[...]` class after the `[`
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("unmatched [")?;
+            let start = match c {
+                // A `]` right after the `[` is part of the class
+                ']' if !first => break,
+                '\\' => match self.next().ok_or("unmatched [")? {
+                    'd' => {
+                        ranges.extend_from_slice(DIGIT);
+                        continue;
+                    }
+                    'w' => {
+                        ranges.extend_from_slice(WORD);
+                        continue;
+                    }
+                    's' => {
+                        ranges.extend_from_slice(SPACE);
+                        continue;
+                    }
+                    't' => '\t',
+                    other => other,
+                },
+                c => c,
+            };
+            first = false;
+
+            // `a-z`, but a `-` before the closing `]` is literal
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                self.pos += 1;
+                let end = match self.next() {
+                    Some('\\') => self.next().ok_or("unmatched [")?,
+                    Some(end) => end,
+                    None => return Err("unmatched [".to_string()),
+                };
+                if end < start {
+                    return Err(format!("invalid range {}-{}", start, end));
+                }
+                ranges.push((start, end));
+            } else {
+                ranges.push((start, start));
+            }
+        }
+        Ok(Node::Class { ranges, negated })
+    }
+}
+
+fn class(ranges: &[(char, char)], negated: bool) -> Node {
+    Node::Class { ranges: ranges.to_vec(), negated }
+}
+
+fn describe(node: &Node) -> &'static str {
+    match node {
+        Node::Start => "^",
+        Node::End => "$",
+        _ => "a repeat",
+    }
+}
+
+/// Threads alive at one position: (instruction, capture slots) in priority order,
+/// plus which instructions already have a thread so each is run at most once
+struct Threads {
+    list: Vec<(usize, Vec<Option<usize>>)>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Threads { list: Vec::new(), seen: vec![false; len] }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.seen.fill(false);
+    }
+}
+
+/// Compile a parsed pattern to instructions ending in `Match`
+fn compile(root: &Node) -> Vec<Inst> {
+    let mut program = Vec::new();
+    emit(root, &mut program);
+    program.push(Inst::Match);
+    program
+}
+
+fn emit(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class { ranges, negated } => program.push(Inst::Class { ranges: ranges.clone(), negated: *negated }),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Group(alternatives, index) => {
+            program.push(Inst::Save(2 * index));
+            let mut jumps = Vec::new();
+            for (i, nodes) in alternatives.iter().enumerate() {
+                let split = program.len();
+                let last = i + 1 == alternatives.len();
+                if !last {
+                    program.push(Inst::Split(split + 1, 0));
+                }
+                for node in nodes {
+                    emit(node, program);
+                }
+                if !last {
+                    jumps.push(program.len());
+                    program.push(Inst::Jump(0));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+            }
+            for jump in jumps {
+                program[jump] = Inst::Jump(program.len());
+            }
+            program.push(Inst::Save(2 * index + 1));
+        }
+        // The parser only produces `*`, `+` and `?`
+        Node::Repeat(inner, min, max) => {
+            let start = program.len();
+            if *min == 0 {
+                // Split(body, exit), patched once the exit is known
+                program.push(Inst::Split(start + 1, 0));
+                emit(inner, program);
+                if max.is_none() {
+                    program.push(Inst::Jump(start));
+                }
+                program[start] = Inst::Split(start + 1, program.len());
+            } else {
+                emit(inner, program);
+                if max.is_none() {
+                    program.push(Inst::Split(start, program.len() + 1));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    /// The first match of `pattern` in `text` as a string
+    fn first(pattern: &str, text: &str) -> Option<String> {
+        let text = chars(text);
+        let m = Regex::new(pattern, false).unwrap().find_at(&text, 0)?;
+        Some(text[m.start..m.end].iter().collect())
+    }
+
+    #[test]
+    fn test_literals_and_dot() {
+        assert_eq!(first("abc", "xxabcxx").as_deref(), Some("abc"));
+        assert_eq!(first("a.c", "abc adc").as_deref(), Some("abc"));
+        assert_eq!(first("a\\.c", "abc a.c").as_deref(), Some("a.c"));
+        assert_eq!(first("héllo", "say héllo").as_deref(), Some("héllo"));
+        assert_eq!(first("abd", "abc"), None);
+    }
+
+    #[test]
+    fn test_repeats() {
+        assert_eq!(first("ab*c", "ac abbbc").as_deref(), Some("ac"));
+        assert_eq!(first("ab+c", "ac abbbc").as_deref(), Some("abbbc"));
+        assert_eq!(first("colou?r", "color colour").as_deref(), Some("color"));
+        assert_eq!(first("a.*b", "a1b2b3").as_deref(), Some("a1b2b"));
+        assert_eq!(first("x*", "abc").as_deref(), Some(""));
+        // Backtracking gives back what the rest of the pattern needs
+        assert_eq!(first("a+ab", "aaab").as_deref(), Some("aaab"));
+        assert_eq!(first("(a*)*b", "aaab").as_deref(), Some("aaab"));
+    }
+
+    #[test]
+    fn test_long_lines() {
+        let line = "a".repeat(100_000);
+        assert_eq!(first(".*b", &line), None);
+        assert_eq!(first(".*b", &(line.clone() + "b")).map(|m| m.len()), Some(100_001));
+        assert_eq!(first("(a|b)*$", &line).map(|m| m.len()), Some(100_000));
+        assert_eq!(first("^a+c", &line), None);
+    }
+
+    #[test]
+    fn test_nested_quantifiers() {
+        let line = "a".repeat(10_000);
+        assert_eq!(first("(a*)*b", &line), None);
+        assert_eq!(first("((a+)+)+c", &line), None);
+        assert_eq!(first("(a?)*(a*)+b", &(line.clone() + "b")).map(|m| m.len()), Some(10_001));
+        // Groups keep the last iteration's capture
+        let text = chars("abcabd");
+        let regex = Regex::new("((ab)(c|d))+", false).unwrap();
+        let m = regex.find_at(&text, 0).unwrap();
+        assert_eq!(regex.expand("\\1 \\3", &text, &m), chars("abd d"));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(first("[0-9]+", "abc 123 x").as_deref(), Some("123"));
+        assert_eq!(first("[^a-z ]+", "abc DEF").as_deref(), Some("DEF"));
+        assert_eq!(first("[]x]+", "a]x]b").as_deref(), Some("]x]"));
+        assert_eq!(first("[a-]+", "b-a-c").as_deref(), Some("-a-"));
+        assert_eq!(first("\\d+\\s\\w+", "is 42 apples").as_deref(), Some("42 apples"));
+        assert_eq!(first("[\\d.]+", "v1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(first("\\S+", "  word  ").as_deref(), Some("word"));
+    }
+
+    #[test]
+    fn test_anchors_and_alternation() {
+        assert_eq!(first("^ab", "ab ab").as_deref(), Some("ab"));
+        assert_eq!(first("^b", "ab"), None);
+        assert_eq!(first("b$", "ab").as_deref(), Some("b"));
+        assert_eq!(first("a$", "ab"), None);
+        assert_eq!(first("cat|dog", "hotdog cat").as_deref(), Some("dog"));
+        assert_eq!(first("gr(a|e)y", "grey").as_deref(), Some("grey"));
+        assert_eq!(first("^(fn|let) ", "let x").as_deref(), Some("let "));
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let regex = Regex::new("hel+o [a-c]", true).unwrap();
+        assert!(regex.find_at(&chars("HELLO B"), 0).is_some());
+        assert!(Regex::literal("ÉTÉ", true).find_at(&chars("un été"), 0).is_some());
+        assert!(Regex::new("hello", false).unwrap().find_at(&chars("HELLO"), 0).is_none());
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let regex = Regex::new("[0-9]+", false).unwrap();
+        assert_eq!(regex.find_iter(&chars("a1 22 333")), [(1, 2), (3, 5), (6, 9)]);
+        // Empty matches are skipped, since there's nothing to highlight
+        let regex = Regex::new("x*", false).unwrap();
+        assert!(regex.find_iter(&chars("ab")).is_empty());
+        assert_eq!(regex.find_non_empty_at(&chars("axxbx"), 0).map(|m| (m.start, m.end)), Some((1, 3)));
+        // Literal patterns treat special characters as text
+        assert_eq!(Regex::literal("a.c", false).find_iter(&chars("abc a.c")), [(4, 7)]);
+    }
+
+    #[test]
+    fn test_expand_replacement() {
+        let text = chars("John Smith");
+        let regex = Regex::new("(\\w+) (\\w+)", false).unwrap();
+        let m = regex.find_at(&text, 0).unwrap();
+        let expand = |replacement: &str| regex.expand(replacement, &text, &m).iter().collect::<String>();
+        assert_eq!(expand("\\2, \\1"), "Smith, John");
+        assert_eq!(expand("<&>"), "<John Smith>");
+        assert_eq!(expand("\\& \\\\ \\3"), "& \\ ");
+
+        let literal = Regex::literal("John", false);
+        let m = literal.find_at(&text, 0).unwrap();
+        assert_eq!(literal.expand("\\1&", &text, &m), chars("\\1&"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert_eq!(Regex::new("(ab", false).unwrap_err(), "unmatched (");
+        assert_eq!(Regex::new("ab)", false).unwrap_err(), "unmatched )");
+        assert_eq!(Regex::new("[ab", false).unwrap_err(), "unmatched [");
+        assert_eq!(Regex::new("*a", false).unwrap_err(), "nothing to repeat before *");
+        assert_eq!(Regex::new("a**", false).unwrap_err(), "can't repeat a repeat");
+        assert_eq!(Regex::new("[z-a]", false).unwrap_err(), "invalid range z-a");
+        assert_eq!(Regex::new("a\\", false).unwrap_err(), "trailing backslash");
+    }
+}
@@ -0,0 +1,130 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of files whose cursor position is remembered
+const MAX_POSITIONS: usize = 200;
+
+/// Directory for editor state files (`$XDG_STATE_HOME/zedit`, `~/.local/state/zedit`,
+/// or `%LOCALAPPDATA%\zedit` on Windows)
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("zedit"));
+    }
+    if cfg!(windows) {
+        return env::var_os("LOCALAPPDATA").map(|d| PathBuf::from(d).join("zedit"));
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state").join("zedit"))
+}
+
+/// Remembered cursor positions, stored one `row<TAB>col<TAB>path` entry per line with
+/// the most recently used first
+pub struct PositionStore {
+    file: PathBuf,
+}
+
+impl PositionStore {
+    pub fn new(file: PathBuf) -> Self {
+        PositionStore { file }
+    }
+
+    /// The store in the default state directory
+    pub fn default_location() -> Option<Self> {
+        state_dir().map(|dir| PositionStore::new(dir.join("positions")))
+    }
+
+    fn read(&self) -> Vec<(PathBuf, usize, usize)> {
+        let Ok(content) = fs::read_to_string(&self.file) else {
+            return Vec::new();
+        };
+
+        content
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, '\t');
+                let row = parts.next()?.parse().ok()?;
+                let col = parts.next()?.parse().ok()?;
+                let path = PathBuf::from(parts.next()?);
+                Some((path, row, col))
+            })
+            .collect()
+    }
+
+    /// Stored position for a file, if any
+    pub fn get(&self, path: &Path) -> Option<(usize, usize)> {
+        let path = fs::canonicalize(path).ok()?;
+        self.read()
+            .into_iter()
+            .find(|(p, _, _)| *p == path)
+            .map(|(_, row, col)| (row, col))
+    }
+
+    /// Record a file's position, dropping entries for files that no longer exist
+    pub fn set(&self, path: &Path, row: usize, col: usize) -> io::Result<()> {
+        let path = fs::canonicalize(path)?;
+        let mut entries = self.read();
+        entries.retain(|(p, _, _)| *p != path && p.exists());
+        entries.insert(0, (path, row, col));
+        entries.truncate(MAX_POSITIONS);
+
+        if let Some(parent) = self.file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content: String = entries
+            .iter()
+            .map(|(p, row, col)| format!("{}\t{}\t{}\n", row, col, p.display()))
+            .collect();
+        fs::write(&self.file, content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_store_round_trip() {
+        let temp_dir = env::temp_dir().join("zedit_positions_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let a = temp_dir.join("a.txt");
+        let b = temp_dir.join("b.txt");
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        let store = PositionStore::new(temp_dir.join("state").join("positions"));
+        assert_eq!(store.get(&a), None);
+
+        store.set(&a, 10, 2).unwrap();
+        store.set(&b, 3, 0).unwrap();
+        store.set(&a, 12, 4).unwrap();
+        assert_eq!(store.get(&a), Some((12, 4)));
+        assert_eq!(store.get(&b), Some((3, 0)));
+        assert_eq!(store.read().len(), 2);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_position_store_prunes_missing_files() {
+        let temp_dir = env::temp_dir().join("zedit_positions_prune_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let kept = temp_dir.join("kept.txt");
+        let gone = temp_dir.join("gone.txt");
+        fs::write(&kept, "").unwrap();
+        fs::write(&gone, "").unwrap();
+
+        let store = PositionStore::new(temp_dir.join("positions"));
+        store.set(&gone, 1, 1).unwrap();
+        fs::remove_file(&gone).unwrap();
+        store.set(&kept, 2, 2).unwrap();
+
+        let entries = store.read();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].1, 2);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+}
@@ -1,7 +1,7 @@
 use crate::terminal::ansi;
 
 /// Token types for syntax highlighting
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TokenType {
     Normal,
     Keyword,
@@ -16,9 +16,76 @@ pub enum TokenType {
     Macro,
     Attribute,
     Constant,
+    Lifetime,
+    /// Bracket colored by nesting depth
+    Bracket(usize),
+    /// Closing bracket with no opening bracket before it
+    UnmatchedBracket,
+    /// Module or namespace segment of a `::` path
+    Namespace,
+    /// TODO-style marker inside a comment
+    Todo,
+}
+
+/// Words that stand out inside comments
+const TODO_MARKERS: &[&str] = &["TODO", "FIXME", "HACK", "XXX"];
+
+/// Colors cycled through for nested brackets
+const BRACKET_COLORS: &[&str] = &[ansi::FG_YELLOW, ansi::FG_MAGENTA, ansi::FG_BRIGHT_BLUE];
+
+/// Display style for a token: foreground color plus text attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub fg: &'static str,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    pub const fn fg(fg: &'static str) -> Self {
+        Style {
+            fg,
+            bold: false,
+            italic: false,
+            underline: false,
+        }
+    }
+
+    pub const fn bold(self) -> Self {
+        Style { bold: true, ..self }
+    }
+
+    pub const fn italic(self) -> Self {
+        Style { italic: true, ..self }
+    }
+
+    /// ANSI escape sequence that switches to this style
+    pub fn ansi(&self) -> String {
+        let mut seq = String::from(self.fg);
+        if self.bold {
+            seq.push_str(ansi::BOLD);
+        }
+        if self.italic {
+            seq.push_str(ansi::ITALIC);
+        }
+        if self.underline {
+            seq.push_str(ansi::UNDERLINE);
+        }
+        seq
+    }
 }
 
 impl TokenType {
+    pub fn style(&self) -> Style {
+        let style = Style::fg(self.color());
+        match self {
+            TokenType::Keyword | TokenType::Todo => style.bold(),
+            TokenType::Comment => style.italic(),
+            _ => style,
+        }
+    }
+
     pub fn color(&self) -> &'static str {
         match self {
             TokenType::Normal => ansi::FG_DEFAULT,
@@ -34,6 +101,11 @@ impl TokenType {
             TokenType::Macro => ansi::FG_BRIGHT_MAGENTA,
             TokenType::Attribute => ansi::FG_YELLOW,
             TokenType::Constant => ansi::FG_BRIGHT_YELLOW,
+            TokenType::Lifetime => ansi::FG_BRIGHT_CYAN,
+            TokenType::Namespace => ansi::FG_BRIGHT_BLUE,
+            TokenType::Todo => ansi::FG_BRIGHT_YELLOW,
+            TokenType::Bracket(depth) => BRACKET_COLORS[depth % BRACKET_COLORS.len()],
+            TokenType::UnmatchedBracket => ansi::FG_BRIGHT_RED,
         }
     }
 }
@@ -50,6 +122,10 @@ pub struct Token {
 pub struct Language {
     pub name: &'static str,
     pub extensions: &'static [&'static str],
+    /// Exact file names matched regardless of extension (e.g. `Makefile`)
+    pub filenames: &'static [&'static str],
+    /// Interpreters named on a `#!` line (e.g. `python` for `#!/usr/bin/env python3`)
+    pub interpreters: &'static [&'static str],
     pub keywords: &'static [&'static str],
     pub types: &'static [&'static str],
     pub constants: &'static [&'static str],
@@ -57,12 +133,28 @@ pub struct Language {
     pub multi_line_comment: Option<(&'static str, &'static str)>,
     pub string_delimiters: &'static [char],
     pub char_delimiter: Option<char>,
+    /// Delimiters for strings that may span multiple lines (e.g. `"""` in Python)
+    pub multi_line_strings: &'static [&'static str],
+    pub interpolation: Option<Interpolation>,
+}
+
+/// Embedded expressions inside string literals (e.g. Python f-strings)
+#[derive(Debug, Clone)]
+pub struct Interpolation {
+    /// Prefixes that mark a string as interpolated; if empty, the delimiter alone does
+    pub prefixes: &'static [char],
+    /// String delimiters that support interpolation
+    pub delimiters: &'static [char],
+    /// Sequence that opens an embedded expression, closed by a matching `}`
+    pub open: &'static str,
 }
 
 // Language definitions
 pub static RUST: Language = Language {
     name: "Rust",
     extensions: &["rs"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[
         "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
         "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
@@ -79,11 +171,15 @@ pub static RUST: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['"'],
     char_delimiter: Some('\''),
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static PYTHON: Language = Language {
     name: "Python",
     extensions: &["py", "pyw", "pyi"],
+    filenames: &[],
+    interpreters: &["python"],
     keywords: &[
         "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
         "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
@@ -99,11 +195,19 @@ pub static PYTHON: Language = Language {
     multi_line_comment: None,
     string_delimiters: &['"', '\''],
     char_delimiter: None,
+    multi_line_strings: &["\"\"\"", "'''"],
+    interpolation: Some(Interpolation {
+        prefixes: &['f', 'F'],
+        delimiters: &['"', '\''],
+        open: "{",
+    }),
 };
 
 pub static JAVASCRIPT: Language = Language {
     name: "JavaScript",
     extensions: &["js", "jsx", "mjs", "cjs"],
+    filenames: &[],
+    interpreters: &["node"],
     keywords: &[
         "async", "await", "break", "case", "catch", "class", "const", "continue", "debugger",
         "default", "delete", "do", "else", "export", "extends", "finally", "for", "function",
@@ -119,11 +223,19 @@ pub static JAVASCRIPT: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['"', '\'', '`'],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: Some(Interpolation {
+        prefixes: &[],
+        delimiters: &['`'],
+        open: "${",
+    }),
 };
 
 pub static TYPESCRIPT: Language = Language {
     name: "TypeScript",
     extensions: &["ts", "tsx"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[
         "abstract", "as", "async", "await", "break", "case", "catch", "class", "const",
         "continue", "debugger", "declare", "default", "delete", "do", "else", "enum", "export",
@@ -142,11 +254,19 @@ pub static TYPESCRIPT: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['"', '\'', '`'],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: Some(Interpolation {
+        prefixes: &[],
+        delimiters: &['`'],
+        open: "${",
+    }),
 };
 
 pub static C: Language = Language {
     name: "C",
     extensions: &["c", "h"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[
         "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern",
         "for", "goto", "if", "inline", "register", "restrict", "return", "sizeof", "static",
@@ -164,11 +284,15 @@ pub static C: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['"'],
     char_delimiter: Some('\''),
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static CPP: Language = Language {
     name: "C++",
     extensions: &["cpp", "cc", "cxx", "hpp", "hh", "hxx", "h++"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[
         "alignas", "alignof", "and", "and_eq", "asm", "auto", "bitand", "bitor", "break", "case",
         "catch", "class", "compl", "concept", "const", "consteval", "constexpr", "constinit",
@@ -191,11 +315,15 @@ pub static CPP: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['"'],
     char_delimiter: Some('\''),
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static GO: Language = Language {
     name: "Go",
     extensions: &["go"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[
         "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough",
         "for", "func", "go", "goto", "if", "import", "interface", "map", "package", "range",
@@ -211,11 +339,15 @@ pub static GO: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['"', '`'],
     char_delimiter: Some('\''),
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static JAVA: Language = Language {
     name: "Java",
     extensions: &["java"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[
         "abstract", "assert", "break", "case", "catch", "class", "const", "continue", "default",
         "do", "else", "enum", "extends", "final", "finally", "for", "goto", "if", "implements",
@@ -233,11 +365,15 @@ pub static JAVA: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['"'],
     char_delimiter: Some('\''),
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static HTML: Language = Language {
     name: "HTML",
     extensions: &["html", "htm", "xhtml"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[],
     types: &[],
     constants: &[],
@@ -245,11 +381,15 @@ pub static HTML: Language = Language {
     multi_line_comment: Some(("<!--", "-->")),
     string_delimiters: &['"', '\''],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static CSS: Language = Language {
     name: "CSS",
     extensions: &["css", "scss", "sass", "less"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[
         "import", "media", "charset", "font-face", "keyframes", "supports", "page", "namespace",
     ],
@@ -261,11 +401,15 @@ pub static CSS: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['"', '\''],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static JSON: Language = Language {
     name: "JSON",
     extensions: &["json", "jsonc"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[],
     types: &[],
     constants: &["true", "false", "null"],
@@ -273,11 +417,15 @@ pub static JSON: Language = Language {
     multi_line_comment: None,
     string_delimiters: &['"'],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static YAML: Language = Language {
     name: "YAML",
     extensions: &["yaml", "yml"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[],
     types: &[],
     constants: &["true", "false", "null", "yes", "no", "on", "off"],
@@ -285,11 +433,15 @@ pub static YAML: Language = Language {
     multi_line_comment: None,
     string_delimiters: &['"', '\''],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static TOML: Language = Language {
     name: "TOML",
     extensions: &["toml"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[],
     types: &[],
     constants: &["true", "false"],
@@ -297,11 +449,15 @@ pub static TOML: Language = Language {
     multi_line_comment: None,
     string_delimiters: &['"', '\''],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static MARKDOWN: Language = Language {
     name: "Markdown",
     extensions: &["md", "markdown", "mdown", "mkdn"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[],
     types: &[],
     constants: &[],
@@ -309,11 +465,15 @@ pub static MARKDOWN: Language = Language {
     multi_line_comment: None,
     string_delimiters: &[],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static SHELL: Language = Language {
     name: "Shell",
     extensions: &["sh", "bash", "zsh", "fish"],
+    filenames: &[".bashrc", ".bash_profile", ".zshrc", ".profile"],
+    interpreters: &["sh", "bash", "zsh", "dash", "ksh", "fish"],
     keywords: &[
         "if", "then", "else", "elif", "fi", "case", "esac", "for", "while", "until", "do", "done",
         "in", "function", "select", "time", "coproc", "return", "exit", "break", "continue",
@@ -326,11 +486,15 @@ pub static SHELL: Language = Language {
     multi_line_comment: None,
     string_delimiters: &['"', '\''],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
 pub static SQL: Language = Language {
     name: "SQL",
     extensions: &["sql"],
+    filenames: &[],
+    interpreters: &[],
     keywords: &[
         "SELECT", "FROM", "WHERE", "INSERT", "UPDATE", "DELETE", "CREATE", "DROP", "ALTER",
         "TABLE", "INDEX", "VIEW", "DATABASE", "SCHEMA", "INTO", "VALUES", "SET", "AND", "OR",
@@ -360,12 +524,246 @@ pub static SQL: Language = Language {
     multi_line_comment: Some(("/*", "*/")),
     string_delimiters: &['\''],
     char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
+};
+
+pub static MAKE: Language = Language {
+    name: "Make",
+    extensions: &["mk", "mak"],
+    filenames: &["Makefile", "makefile", "GNUmakefile"],
+    interpreters: &["make"],
+    keywords: &[
+        "ifeq", "ifneq", "ifdef", "ifndef", "else", "endif", "include", "sinclude", "define",
+        "endef", "export", "unexport", "override", "private", "vpath",
+    ],
+    types: &[],
+    constants: &[],
+    single_line_comment: Some("#"),
+    multi_line_comment: None,
+    string_delimiters: &['"', '\''],
+    char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
+};
+
+pub static DOCKERFILE: Language = Language {
+    name: "Dockerfile",
+    extensions: &["dockerfile"],
+    filenames: &["Dockerfile", "Containerfile"],
+    interpreters: &[],
+    keywords: &[
+        "FROM", "AS", "RUN", "CMD", "LABEL", "MAINTAINER", "EXPOSE", "ENV", "ADD", "COPY",
+        "ENTRYPOINT", "VOLUME", "USER", "WORKDIR", "ARG", "ONBUILD", "STOPSIGNAL",
+        "HEALTHCHECK", "SHELL",
+    ],
+    types: &[],
+    constants: &[],
+    single_line_comment: Some("#"),
+    multi_line_comment: None,
+    string_delimiters: &['"', '\''],
+    char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
+};
+
+pub static CMAKE: Language = Language {
+    name: "CMake",
+    extensions: &["cmake"],
+    filenames: &["CMakeLists.txt"],
+    interpreters: &[],
+    keywords: &[
+        "if", "elseif", "else", "endif", "foreach", "endforeach", "while", "endwhile", "function",
+        "endfunction", "macro", "endmacro", "return", "break", "continue", "set", "unset", "option",
+        "include", "project", "cmake_minimum_required", "add_executable", "add_library",
+        "add_subdirectory", "find_package", "target_link_libraries", "target_include_directories",
+        "install", "message",
+    ],
+    types: &[],
+    constants: &["ON", "OFF", "TRUE", "FALSE", "YES", "NO"],
+    single_line_comment: Some("#"),
+    multi_line_comment: None,
+    string_delimiters: &['"'],
+    char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
+};
+
+pub static INI: Language = Language {
+    name: "INI",
+    extensions: &["ini", "cfg", "inf"],
+    filenames: &[],
+    interpreters: &[],
+    keywords: &[],
+    types: &[],
+    constants: &["true", "false", "yes", "no", "on", "off"],
+    single_line_comment: Some(";"),
+    multi_line_comment: None,
+    string_delimiters: &['"'],
+    char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
 };
 
+pub static IGNORE: Language = Language {
+    name: "Ignore",
+    extensions: &["gitignore", "dockerignore"],
+    filenames: &[".gitignore", ".dockerignore", ".npmignore", ".ignore"],
+    interpreters: &[],
+    keywords: &[],
+    types: &[],
+    constants: &[],
+    single_line_comment: Some("#"),
+    multi_line_comment: None,
+    string_delimiters: &[],
+    char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
+};
+
+pub static RUBY: Language = Language {
+    name: "Ruby",
+    extensions: &["rb", "rake", "gemspec"],
+    filenames: &["Gemfile", "Rakefile"],
+    interpreters: &["ruby"],
+    keywords: &[
+        "alias", "and", "begin", "break", "case", "class", "def", "defined?", "do", "else",
+        "elsif", "end", "ensure", "for", "if", "in", "module", "next", "not", "or", "redo",
+        "rescue", "retry", "return", "self", "super", "then", "undef", "unless", "until", "when",
+        "while", "yield", "require", "require_relative", "attr_reader", "attr_writer",
+        "attr_accessor", "private", "protected", "public", "raise",
+    ],
+    types: &[
+        "Array", "Hash", "String", "Symbol", "Integer", "Float", "Proc", "Range", "Struct",
+        "Object", "Class", "Module", "Kernel", "Comparable", "Enumerable",
+    ],
+    constants: &["true", "false", "nil", "__FILE__", "__LINE__", "__dir__"],
+    single_line_comment: Some("#"),
+    multi_line_comment: None,
+    string_delimiters: &['"', '\''],
+    char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: Some(Interpolation {
+        prefixes: &[],
+        delimiters: &['"'],
+        open: "#{",
+    }),
+};
+
+pub static PHP: Language = Language {
+    name: "PHP",
+    extensions: &["php", "phtml"],
+    filenames: &[],
+    interpreters: &["php"],
+    keywords: &[
+        "abstract", "and", "as", "break", "case", "catch", "class", "clone", "const", "continue",
+        "declare", "default", "do", "echo", "else", "elseif", "enum", "extends", "final",
+        "finally", "fn", "for", "foreach", "function", "global", "if", "implements", "include",
+        "include_once", "instanceof", "interface", "match", "namespace", "new", "or", "print",
+        "private", "protected", "public", "readonly", "require", "require_once", "return",
+        "static", "switch", "throw", "trait", "try", "use", "while", "yield", "php",
+    ],
+    types: &[
+        "array", "bool", "callable", "float", "int", "iterable", "mixed", "object", "string",
+        "void", "never", "self", "parent",
+    ],
+    constants: &["true", "false", "null", "TRUE", "FALSE", "NULL"],
+    single_line_comment: Some("//"),
+    multi_line_comment: Some(("/*", "*/")),
+    string_delimiters: &['"', '\''],
+    char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
+};
+
+pub static LUA: Language = Language {
+    name: "Lua",
+    extensions: &["lua"],
+    filenames: &[],
+    interpreters: &["lua", "luajit"],
+    keywords: &[
+        "and", "break", "do", "else", "elseif", "end", "for", "function", "goto", "if", "in",
+        "local", "not", "or", "repeat", "return", "then", "until", "while",
+    ],
+    types: &[],
+    constants: &["true", "false", "nil"],
+    single_line_comment: Some("--"),
+    multi_line_comment: None,
+    string_delimiters: &['"', '\''],
+    char_delimiter: None,
+    multi_line_strings: &[],
+    interpolation: None,
+};
+
+pub static KOTLIN: Language = Language {
+    name: "Kotlin",
+    extensions: &["kt", "kts"],
+    filenames: &[],
+    interpreters: &[],
+    keywords: &[
+        "abstract", "as", "break", "by", "catch", "class", "companion", "const", "constructor",
+        "continue", "data", "do", "else", "enum", "final", "finally", "for", "fun", "if",
+        "import", "in", "init", "inline", "interface", "internal", "is", "lateinit", "object",
+        "open", "operator", "override", "package", "private", "protected", "public", "return",
+        "sealed", "super", "suspend", "this", "throw", "try", "typealias", "val", "var", "when",
+        "where", "while",
+    ],
+    types: &[
+        "Any", "Array", "Boolean", "Byte", "Char", "Double", "Float", "Int", "List", "Long",
+        "Map", "MutableList", "MutableMap", "MutableSet", "Nothing", "Pair", "Set", "Short",
+        "String", "Unit",
+    ],
+    constants: &["true", "false", "null"],
+    single_line_comment: Some("//"),
+    multi_line_comment: Some(("/*", "*/")),
+    string_delimiters: &['"'],
+    char_delimiter: Some('\''),
+    multi_line_strings: &["\"\"\""],
+    interpolation: Some(Interpolation {
+        prefixes: &[],
+        delimiters: &['"'],
+        open: "${",
+    }),
+};
+
+pub static SWIFT: Language = Language {
+    name: "Swift",
+    extensions: &["swift"],
+    filenames: &[],
+    interpreters: &["swift"],
+    keywords: &[
+        "as", "associatedtype", "break", "case", "catch", "class", "continue", "default",
+        "defer", "deinit", "do", "else", "enum", "extension", "fallthrough", "fileprivate",
+        "for", "func", "guard", "if", "import", "in", "init", "inout", "internal", "is", "let",
+        "mutating", "open", "operator", "override", "private", "protocol", "public", "repeat",
+        "rethrows", "return", "self", "Self", "static", "struct", "subscript", "super", "switch",
+        "throw", "throws", "try", "typealias", "var", "where", "while", "async", "await",
+    ],
+    types: &[
+        "Any", "Array", "Bool", "Character", "Dictionary", "Double", "Float", "Int", "Optional",
+        "Set", "String", "UInt", "Void",
+    ],
+    constants: &["true", "false", "nil"],
+    single_line_comment: Some("//"),
+    multi_line_comment: Some(("/*", "*/")),
+    string_delimiters: &['"'],
+    char_delimiter: None,
+    multi_line_strings: &["\"\"\""],
+    interpolation: None,
+};
+
+/// Type suffixes accepted after numeric literals
+const NUMBER_SUFFIXES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    "f32", "f64", "u", "U", "l", "L", "ul", "UL", "lu", "LU", "ll", "LL", "ull", "ULL", "f",
+    "F", "d", "D", "n", "j", "J",
+];
+
 /// All supported languages
 pub static LANGUAGES: &[&Language] = &[
     &RUST, &PYTHON, &JAVASCRIPT, &TYPESCRIPT, &C, &CPP, &GO, &JAVA, &HTML, &CSS, &JSON, &YAML,
-    &TOML, &MARKDOWN, &SHELL, &SQL,
+    &TOML, &MARKDOWN, &SHELL, &SQL, &MAKE, &DOCKERFILE, &CMAKE, &INI, &IGNORE, &RUBY, &PHP,
+    &LUA, &KOTLIN, &SWIFT,
 ];
 
 /// Detect language from file extension
@@ -374,11 +772,121 @@ pub fn detect_language(extension: Option<&str>) -> Option<&'static Language> {
     LANGUAGES.iter().find(|lang| lang.extensions.contains(&ext)).copied()
 }
 
+/// Look up a language by name (case-insensitive) or by one of its extensions
+pub fn find_language(name: &str) -> Option<&'static Language> {
+    let lower = name.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.name.to_lowercase() == lower)
+        .or_else(|| {
+            LANGUAGES
+                .iter()
+                .find(|lang| lang.extensions.contains(&lower.as_str()))
+        })
+        .copied()
+}
+
+/// Detect language from the file name, falling back to its extension
+pub fn detect_language_for_file(
+    filename: Option<&str>,
+    extension: Option<&str>,
+) -> Option<&'static Language> {
+    filename
+        .and_then(|name| {
+            LANGUAGES
+                .iter()
+                .find(|lang| lang.filenames.contains(&name))
+                .copied()
+        })
+        .or_else(|| detect_language(extension))
+}
+
+/// Detect language from a `#!` line such as `#!/bin/bash` or `#!/usr/bin/env python3`
+pub fn detect_language_from_shebang(first_line: &str) -> Option<&'static Language> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // Skip options like `env -S`
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    // Versioned names like python3 and python3.12
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.interpreters.contains(&name))
+        .copied()
+}
+
+/// Classify identifiers by `::` paths and naming convention in languages that use them.
+/// `rest` is the remainder of the line after the identifier.
+fn path_token_type(lang: &Language, text: &str, rest: &[char]) -> Option<TokenType> {
+    if !matches!(lang.name, "Rust" | "C++") {
+        return None;
+    }
+
+    let first = text.chars().next()?;
+    let followed_by_path = rest.starts_with(&[':', ':']);
+    let all_caps = text.chars().count() > 1
+        && text.chars().any(|c| c.is_uppercase())
+        && !text.chars().any(|c| c.is_lowercase());
+
+    if all_caps {
+        Some(TokenType::Constant)
+    } else if followed_by_path && !first.is_uppercase() {
+        Some(TokenType::Namespace)
+    } else if first.is_uppercase() && lang.name == "Rust" {
+        // CamelCase names are types, traits or enum variants
+        Some(TokenType::Type)
+    } else {
+        None
+    }
+}
+
 /// Highlighter state for multi-line constructs
 #[derive(Clone, Default)]
 pub struct HighlightState {
     pub in_multiline_comment: bool,
     pub in_string: Option<char>,
+    /// Closing delimiter of an unterminated multi-line string
+    pub in_multiline_string: Option<String>,
+    /// Whether the unterminated string supports interpolation
+    pub interpolated: bool,
+    /// Whether the unterminated string is raw (no escape sequences)
+    pub raw_string: bool,
+    /// Bracket nesting depth carried over for rainbow brackets
+    pub bracket_depth: usize,
+}
+
+/// Recolor bracket punctuation by nesting depth, carrying the depth across lines
+pub fn colorize_brackets(tokens: &mut [Token], state: &mut HighlightState) {
+    for token in tokens.iter_mut() {
+        if token.token_type != TokenType::Punctuation {
+            continue;
+        }
+        match token.text.as_str() {
+            "(" | "[" | "{" | "${" => {
+                token.token_type = TokenType::Bracket(state.bracket_depth);
+                state.bracket_depth += 1;
+            }
+            ")" | "]" | "}" if state.bracket_depth == 0 => {
+                token.token_type = TokenType::UnmatchedBracket;
+            }
+            ")" | "]" | "}" => {
+                state.bracket_depth -= 1;
+                token.token_type = TokenType::Bracket(state.bracket_depth);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// How to scan the body of a string literal
+struct StringSpec {
+    close: Vec<char>,
+    /// Interpolation opener, if the string is interpolated
+    open: Option<Vec<char>>,
+    /// Raw strings don't treat backslash as an escape
+    raw: bool,
 }
 
 /// Syntax highlighter
@@ -393,6 +901,15 @@ impl Highlighter {
         }
     }
 
+    /// Create a highlighter for a file, matching by file name, then extension, then the
+    /// `#!` line at the top of the file
+    pub fn for_file(filename: Option<&str>, extension: Option<&str>, first_line: Option<&str>) -> Self {
+        Highlighter {
+            language: detect_language_for_file(filename, extension)
+                .or_else(|| first_line.and_then(detect_language_from_shebang)),
+        }
+    }
+
     /// Highlight a single line
     pub fn highlight_line(&self, line: &str, state: &mut HighlightState) -> Vec<Token> {
         let Some(lang) = self.language else {
@@ -404,72 +921,87 @@ impl Highlighter {
 
         let mut tokens = Vec::new();
         let chars: Vec<char> = line.chars().collect();
-        let mut i = 0;
+        self.tokenize(lang, &chars, 0, state, &mut tokens, false);
+        tokens
+    }
+
+    /// Tokenize `chars` from `i`, pushing onto `tokens`. Inside an interpolated
+    /// expression, stops at the unmatched `}` and returns its index; otherwise
+    /// returns the line length.
+    fn tokenize(
+        &self,
+        lang: &Language,
+        chars: &[char],
+        mut i: usize,
+        state: &mut HighlightState,
+        tokens: &mut Vec<Token>,
+        in_interpolation: bool,
+    ) -> usize {
+        let mut brace_depth = 0;
 
         while i < chars.len() {
             // Handle multi-line comment continuation
             if state.in_multiline_comment {
                 if let Some((_, end)) = lang.multi_line_comment {
                     let end_chars: Vec<char> = end.chars().collect();
-                    if self.matches_at(&chars, i, &end_chars) {
-                        let text: String = chars[..i + end_chars.len()].iter().collect();
-                        tokens.push(Token {
-                            text,
-                            token_type: TokenType::Comment,
-                        });
-                        i += end_chars.len();
+                    if let Some(end) = (i..chars.len()).find(|&j| self.matches_at(chars, j, &end_chars)) {
+                        self.push_comment(&chars[i..end + end_chars.len()], tokens);
+                        i = end + end_chars.len();
                         state.in_multiline_comment = false;
                         continue;
                     }
                 }
                 // Still in comment, consume entire line
-                let text: String = chars[i..].iter().collect();
-                tokens.push(Token {
-                    text,
-                    token_type: TokenType::Comment,
-                });
-                return tokens;
+                self.push_comment(&chars[i..], tokens);
+                return chars.len();
+            }
+
+            // Handle multi-line string continuation
+            if let Some(close) = &state.in_multiline_string {
+                let spec = StringSpec {
+                    close: close.chars().collect(),
+                    open: self.interpolation_open(lang, state.interpolated),
+                    raw: state.raw_string,
+                };
+                let (end, closed) = self.scan_string(lang, chars, i, i, &spec, tokens);
+                i = end;
+                if !closed {
+                    return chars.len();
+                }
+                state.in_multiline_string = None;
+                state.interpolated = false;
+                state.raw_string = false;
+                continue;
             }
 
             // Handle string continuation
             if let Some(delim) = state.in_string {
-                let start = i;
-                while i < chars.len() {
-                    if chars[i] == '\\' && i + 1 < chars.len() {
-                        i += 2;
-                    } else if chars[i] == delim {
-                        i += 1;
-                        state.in_string = None;
-                        break;
-                    } else {
-                        i += 1;
-                    }
+                let spec = StringSpec {
+                    close: vec![delim],
+                    open: self.interpolation_open(lang, self.interpolates_delimiter(lang, delim)),
+                    raw: false,
+                };
+                let (end, closed) = self.scan_string(lang, chars, i, i, &spec, tokens);
+                i = end;
+                if closed {
+                    state.in_string = None;
                 }
-                let text: String = chars[start..i].iter().collect();
-                tokens.push(Token {
-                    text,
-                    token_type: TokenType::String,
-                });
                 continue;
             }
 
             // Check for single-line comment
             if let Some(comment) = lang.single_line_comment {
                 let comment_chars: Vec<char> = comment.chars().collect();
-                if self.matches_at(&chars, i, &comment_chars) {
-                    let text: String = chars[i..].iter().collect();
-                    tokens.push(Token {
-                        text,
-                        token_type: TokenType::Comment,
-                    });
-                    return tokens;
+                if self.matches_at(chars, i, &comment_chars) {
+                    self.push_comment(&chars[i..], tokens);
+                    return chars.len();
                 }
             }
 
             // Check for multi-line comment start
             if let Some((start, _)) = lang.multi_line_comment {
                 let start_chars: Vec<char> = start.chars().collect();
-                if self.matches_at(&chars, i, &start_chars) {
+                if self.matches_at(chars, i, &start_chars) {
                     state.in_multiline_comment = true;
                     // Look for end on same line
                     let comment_start = i;
@@ -478,7 +1010,7 @@ impl Highlighter {
                     if let Some((_, end)) = lang.multi_line_comment {
                         let end_chars: Vec<char> = end.chars().collect();
                         while i < chars.len() {
-                            if self.matches_at(&chars, i, &end_chars) {
+                            if self.matches_at(chars, i, &end_chars) {
                                 i += end_chars.len();
                                 state.in_multiline_comment = false;
                                 break;
@@ -487,47 +1019,69 @@ impl Highlighter {
                         }
                     }
 
-                    let text: String = chars[comment_start..i].iter().collect();
-                    tokens.push(Token {
-                        text,
-                        token_type: TokenType::Comment,
-                    });
+                    self.push_comment(&chars[comment_start..i], tokens);
                     continue;
                 }
             }
 
-            // Check for string
-            if lang.string_delimiters.contains(&chars[i]) {
-                let delim = chars[i];
+            // Check for Rust raw strings (`r"..."`, `r#"..."#`, `br"..."`)
+            if lang.name == "Rust"
+                && let Some((prefix_len, hashes)) = self.raw_string_prefix(chars, i)
+            {
                 let start = i;
-                i += 1;
+                i += prefix_len;
+                let mut close = vec!['"'];
+                close.extend(std::iter::repeat_n('#', hashes));
+                let spec = StringSpec {
+                    close,
+                    open: None,
+                    raw: true,
+                };
 
-                while i < chars.len() {
-                    if chars[i] == '\\' && i + 1 < chars.len() {
-                        i += 2;
-                    } else if chars[i] == delim {
-                        i += 1;
-                        break;
-                    } else {
-                        i += 1;
-                    }
+                let (end, closed) = self.scan_string(lang, chars, start, i, &spec, tokens);
+                i = end;
+                if !closed {
+                    state.in_multiline_string = Some(spec.close.iter().collect());
+                    state.raw_string = true;
+                    return chars.len();
                 }
+                continue;
+            }
 
-                // Check if string is complete
-                if i <= chars.len() && chars.get(i - 1) == Some(&delim) {
-                    let text: String = chars[start..i].iter().collect();
-                    tokens.push(Token {
-                        text,
-                        token_type: TokenType::String,
-                    });
-                } else {
-                    state.in_string = Some(delim);
-                    let text: String = chars[start..].iter().collect();
-                    tokens.push(Token {
-                        text,
-                        token_type: TokenType::String,
-                    });
-                    return tokens;
+            // Check for string, including prefixed interpolated strings
+            let prefix_len = self.interpolation_prefix_len(lang, chars, i);
+            if prefix_len > 0 || lang.string_delimiters.contains(&chars[i]) {
+                let start = i;
+                i += prefix_len;
+                let delim = chars[i];
+                let interpolated = prefix_len > 0 || self.interpolates_delimiter(lang, delim);
+
+                let close: Vec<char> = match lang
+                    .multi_line_strings
+                    .iter()
+                    .find(|d| self.matches_at(chars, i, &d.chars().collect::<Vec<_>>()))
+                {
+                    Some(d) => d.chars().collect(),
+                    None => vec![delim],
+                };
+                i += close.len();
+
+                let spec = StringSpec {
+                    close,
+                    open: self.interpolation_open(lang, interpolated),
+                    raw: false,
+                };
+                let (end, closed) = self.scan_string(lang, chars, start, i, &spec, tokens);
+                i = end;
+
+                if !closed {
+                    if spec.close.len() > 1 {
+                        state.in_multiline_string = Some(spec.close.iter().collect());
+                        state.interpolated = interpolated;
+                    } else {
+                        state.in_string = Some(delim);
+                    }
+                    return chars.len();
                 }
                 continue;
             }
@@ -538,10 +1092,28 @@ impl Highlighter {
                     let start = i;
                     i += 1;
 
-                    // Handle escape sequence or single char
+                    // A Rust lifetime (`'a`) has no closing quote after its first char
+                    let is_lifetime = lang.name == "Rust"
+                        && chars.get(i).is_some_and(|c| c.is_alphabetic() || *c == '_')
+                        && chars.get(i + 1) != Some(&char_delim);
+                    if is_lifetime {
+                        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                            i += 1;
+                        }
+                        tokens.push(Token {
+                            text: chars[start..i].iter().collect(),
+                            token_type: TokenType::Lifetime,
+                        });
+                        continue;
+                    }
+
+                    // Handle escape sequence (including `\u{...}`) or single char
                     if i < chars.len() {
                         if chars[i] == '\\' && i + 1 < chars.len() {
                             i += 2;
+                            while i < chars.len() && chars[i] != char_delim && !chars[i].is_whitespace() {
+                                i += 1;
+                            }
                         } else {
                             i += 1;
                         }
@@ -561,41 +1133,15 @@ impl Highlighter {
                 }
             }
 
-            // Check for number
+            // Check for number (a leading `.` only starts one outside of paths/ranges)
             if chars[i].is_ascii_digit()
-                || (chars[i] == '.' && i + 1 < chars.len() && chars[i + 1].is_ascii_digit())
+                || (chars[i] == '.'
+                    && i + 1 < chars.len()
+                    && chars[i + 1].is_ascii_digit()
+                    && (i == 0 || !(chars[i - 1].is_alphanumeric() || chars[i - 1] == '.')))
             {
                 let start = i;
-                let mut has_dot = chars[i] == '.';
-                i += 1;
-
-                while i < chars.len() {
-                    if chars[i].is_ascii_digit() {
-                        i += 1;
-                    } else if chars[i] == '.' && !has_dot {
-                        has_dot = true;
-                        i += 1;
-                    } else if chars[i] == 'x' || chars[i] == 'X' || chars[i] == 'b' || chars[i] == 'o' {
-                        // Hex, binary, octal
-                        i += 1;
-                    } else if chars[i].is_ascii_hexdigit() {
-                        i += 1;
-                    } else if chars[i] == '_' {
-                        i += 1;
-                    } else if chars[i] == 'e' || chars[i] == 'E' {
-                        i += 1;
-                        if i < chars.len() && (chars[i] == '+' || chars[i] == '-') {
-                            i += 1;
-                        }
-                    } else {
-                        break;
-                    }
-                }
-
-                // Handle type suffixes
-                while i < chars.len() && (chars[i].is_ascii_alphabetic() || chars[i] == '_') {
-                    i += 1;
-                }
+                i = self.scan_number(chars, i);
 
                 let text: String = chars[start..i].iter().collect();
                 tokens.push(Token {
@@ -619,6 +1165,8 @@ impl Highlighter {
                     TokenType::Type
                 } else if lang.constants.contains(&text.as_str()) {
                     TokenType::Constant
+                } else if let Some(token_type) = path_token_type(lang, &text, &chars[i..]) {
+                    token_type
                 } else if i < chars.len() && chars[i] == '(' {
                     TokenType::Function
                 } else if i < chars.len() && chars[i] == '!' {
@@ -670,6 +1218,15 @@ impl Highlighter {
 
             // Punctuation
             if "()[]{}.,;@".contains(chars[i]) {
+                if in_interpolation {
+                    // Track nested braces so the closing `}` ends the expression
+                    match chars[i] {
+                        '{' => brace_depth += 1,
+                        '}' if brace_depth == 0 => return i,
+                        '}' => brace_depth -= 1,
+                        _ => {}
+                    }
+                }
                 tokens.push(Token {
                     text: chars[i].to_string(),
                     token_type: TokenType::Punctuation,
@@ -705,7 +1262,216 @@ impl Highlighter {
             }
         }
 
-        tokens
+        chars.len()
+    }
+
+    /// Scan a string body from `i` through the closing delimiter, splitting out
+    /// interpolated expressions. The string token starts at `start`. Returns the
+    /// index after the string and whether it was closed on this line.
+    fn scan_string(
+        &self,
+        lang: &Language,
+        chars: &[char],
+        mut start: usize,
+        mut i: usize,
+        spec: &StringSpec,
+        tokens: &mut Vec<Token>,
+    ) -> (usize, bool) {
+        let push_string = |tokens: &mut Vec<Token>, from: usize, to: usize| {
+            if to > from {
+                tokens.push(Token {
+                    text: chars[from..to].iter().collect(),
+                    token_type: TokenType::String,
+                });
+            }
+        };
+
+        while i < chars.len() {
+            if !spec.raw && chars[i] == '\\' && i + 1 < chars.len() {
+                i += 2;
+            } else if self.matches_at(chars, i, &spec.close) {
+                i += spec.close.len();
+                push_string(tokens, start, i);
+                return (i, true);
+            } else if let Some(open) = spec.open.as_deref().filter(|o| self.matches_at(chars, i, o)) {
+                // A doubled single-char opener (`{{`) is a literal brace
+                if open.len() == 1 && chars.get(i + 1) == Some(&open[0]) {
+                    i += 2;
+                    continue;
+                }
+
+                push_string(tokens, start, i);
+                tokens.push(Token {
+                    text: open.iter().collect(),
+                    token_type: TokenType::Punctuation,
+                });
+
+                let mut inner_state = HighlightState::default();
+                let end = self.tokenize(lang, chars, i + open.len(), &mut inner_state, tokens, true);
+                if end >= chars.len() {
+                    return (chars.len(), false);
+                }
+
+                tokens.push(Token {
+                    text: "}".to_string(),
+                    token_type: TokenType::Punctuation,
+                });
+                i = end + 1;
+                start = i;
+            } else {
+                i += 1;
+            }
+        }
+
+        push_string(tokens, start, chars.len());
+        (chars.len(), false)
+    }
+
+    /// Length of an interpolation prefix (like `f`, optionally with a raw `r`) starting a string at `i`
+    fn interpolation_prefix_len(&self, lang: &Language, chars: &[char], i: usize) -> usize {
+        let Some(interp) = &lang.interpolation else {
+            return 0;
+        };
+
+        (1..=2)
+            .find(|&len| {
+                i + len < chars.len()
+                    && interp.delimiters.contains(&chars[i + len])
+                    && chars[i..i + len]
+                        .iter()
+                        .all(|c| interp.prefixes.contains(c) || *c == 'r' || *c == 'R')
+                    && chars[i..i + len].iter().any(|c| interp.prefixes.contains(c))
+            })
+            .unwrap_or(0)
+    }
+
+    /// Scan a numeric literal starting at `i`, returning the index after it
+    fn scan_number(&self, chars: &[char], mut i: usize) -> usize {
+        let digits_from = |mut i: usize, valid: fn(&char) -> bool| {
+            while i < chars.len() && (valid(&chars[i]) || chars[i] == '_') {
+                i += 1;
+            }
+            i
+        };
+
+        // Radix prefixes only count when followed by a valid digit
+        if chars[i] == '0' {
+            let radix_digits: Option<fn(&char) -> bool> = match chars.get(i + 1) {
+                Some('x') | Some('X') => Some(char::is_ascii_hexdigit),
+                Some('b') | Some('B') => Some(|c| matches!(c, '0' | '1')),
+                Some('o') | Some('O') => Some(|c| matches!(c, '0'..='7')),
+                _ => None,
+            };
+            if let Some(valid) = radix_digits {
+                if chars.get(i + 2).is_some_and(valid) {
+                    return self.scan_number_suffix(chars, digits_from(i + 2, valid));
+                }
+                return i + 1;
+            }
+        }
+
+        i = digits_from(i, char::is_ascii_digit);
+
+        // Fraction: a `.` must be followed by a digit, so `0..5` and `1.max()` stop early
+        if i + 1 < chars.len() && chars[i] == '.' && chars[i + 1].is_ascii_digit() {
+            i = digits_from(i + 1, char::is_ascii_digit);
+        }
+
+        // Exponent
+        if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+            let mut j = i + 1;
+            if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                j += 1;
+            }
+            if j < chars.len() && chars[j].is_ascii_digit() {
+                i = digits_from(j, char::is_ascii_digit);
+            }
+        }
+
+        self.scan_number_suffix(chars, i)
+    }
+
+    /// Consume a known numeric type suffix (like `u8`, `f64`, `L`) at `i`, if present
+    fn scan_number_suffix(&self, chars: &[char], i: usize) -> usize {
+        let end = chars[i..]
+            .iter()
+            .position(|c| !(c.is_ascii_alphanumeric() || *c == '_'))
+            .map_or(chars.len(), |p| i + p);
+        let suffix: String = chars[i..end].iter().collect();
+
+        if NUMBER_SUFFIXES.contains(&suffix.as_str()) {
+            end
+        } else {
+            i
+        }
+    }
+
+    /// For a Rust raw string starting at `i`, the length of its opening
+    /// (`r`/`br`, hashes and quote) and the number of hashes
+    fn raw_string_prefix(&self, chars: &[char], i: usize) -> Option<(usize, usize)> {
+        let mut j = i;
+        if chars.get(j) == Some(&'b') {
+            j += 1;
+        }
+        if chars.get(j) != Some(&'r') {
+            return None;
+        }
+        j += 1;
+
+        let hashes = chars[j..].iter().take_while(|&&c| c == '#').count();
+        j += hashes;
+        (chars.get(j) == Some(&'"')).then_some((j + 1 - i, hashes))
+    }
+
+    /// Push comment text, splitting out TODO-style markers so they stand out
+    fn push_comment(&self, chars: &[char], tokens: &mut Vec<Token>) {
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let mut start = 0;
+        let mut i = 0;
+        while i < chars.len() {
+            let marker = TODO_MARKERS.iter().map(|m| m.chars().collect::<Vec<char>>()).find(|m| {
+                self.matches_at(chars, i, m)
+                    && !chars[..i].last().is_some_and(is_word)
+                    && !chars.get(i + m.len()).is_some_and(is_word)
+            });
+            let Some(marker) = marker else {
+                i += 1;
+                continue;
+            };
+            if start < i {
+                tokens.push(Token {
+                    text: chars[start..i].iter().collect(),
+                    token_type: TokenType::Comment,
+                });
+            }
+            tokens.push(Token {
+                text: marker.iter().collect(),
+                token_type: TokenType::Todo,
+            });
+            i += marker.len();
+            start = i;
+        }
+        if start < chars.len() {
+            tokens.push(Token {
+                text: chars[start..].iter().collect(),
+                token_type: TokenType::Comment,
+            });
+        }
+    }
+
+    /// Whether strings with this delimiter are interpolated without a prefix
+    fn interpolates_delimiter(&self, lang: &Language, delim: char) -> bool {
+        lang.interpolation
+            .as_ref()
+            .is_some_and(|interp| interp.prefixes.is_empty() && interp.delimiters.contains(&delim))
+    }
+
+    /// The interpolation opener for the language, if `interpolated`
+    fn interpolation_open(&self, lang: &Language, interpolated: bool) -> Option<Vec<char>> {
+        lang.interpolation
+            .as_ref()
+            .filter(|_| interpolated)
+            .map(|interp| interp.open.chars().collect())
     }
 
     fn matches_at(&self, chars: &[char], pos: usize, pattern: &[char]) -> bool {
@@ -761,6 +1527,122 @@ mod tests {
         assert!(lang.is_none());
     }
 
+    #[test]
+    fn test_find_language() {
+        assert_eq!(find_language("rust").map(|l| l.name), Some("Rust"));
+        assert_eq!(find_language("C++").map(|l| l.name), Some("C++"));
+        assert_eq!(find_language("sh").map(|l| l.name), Some("Shell"));
+        assert_eq!(find_language("PY").map(|l| l.name), Some("Python"));
+        assert!(find_language("klingon").is_none());
+    }
+
+    #[test]
+    fn test_detect_language_by_filename() {
+        let name = |filename, ext| detect_language_for_file(Some(filename), ext).map(|l| l.name);
+        assert_eq!(name("Makefile", None), Some("Make"));
+        assert_eq!(name("Dockerfile", None), Some("Dockerfile"));
+        // The file name wins over the `.txt` extension
+        assert_eq!(name("CMakeLists.txt", Some("txt")), Some("CMake"));
+        assert_eq!(name("notes.txt", Some("txt")), None);
+        assert_eq!(name("toolchain.cmake", Some("cmake")), Some("CMake"));
+        assert_eq!(name(".gitignore", None), Some("Ignore"));
+        assert_eq!(name(".bashrc", None), Some("Shell"));
+        assert_eq!(name("settings.ini", Some("ini")), Some("INI"));
+        assert_eq!(name("main.rs", Some("rs")), Some("Rust"));
+        assert_eq!(name("LICENSE", None), None);
+    }
+
+    #[test]
+    fn test_detect_language_from_shebang() {
+        let name = |line| detect_language_from_shebang(line).map(|l| l.name);
+        assert_eq!(name("#!/usr/bin/env python3"), Some("Python"));
+        assert_eq!(name("#!/usr/bin/python3.12 -u"), Some("Python"));
+        assert_eq!(name("#!/bin/bash"), Some("Shell"));
+        assert_eq!(name("#! /bin/sh -e"), Some("Shell"));
+        assert_eq!(name("#!/usr/bin/env -S node --harmony"), Some("JavaScript"));
+        assert_eq!(name("#!/usr/bin/env klingon"), None);
+        assert_eq!(name("# /bin/bash"), None);
+
+        // The file name and extension win over the #! line
+        let language = |filename, ext| Highlighter::for_file(filename, ext, Some("#!/bin/sh")).language;
+        assert_eq!(language(Some("deploy"), None).map(|l| l.name), Some("Shell"));
+        assert_eq!(language(Some("tool.py"), Some("py")).map(|l| l.name), Some("Python"));
+        assert!(Highlighter::for_file(Some("notes"), None, Some("hello")).language.is_none());
+    }
+
+    #[test]
+    fn test_detect_more_languages() {
+        let name = |ext| detect_language(Some(ext)).map(|l| l.name);
+        assert_eq!(name("rb"), Some("Ruby"));
+        assert_eq!(name("php"), Some("PHP"));
+        assert_eq!(name("lua"), Some("Lua"));
+        assert_eq!(name("kts"), Some("Kotlin"));
+        assert_eq!(name("swift"), Some("Swift"));
+        assert_eq!(detect_language_for_file(Some("Gemfile"), None).map(|l| l.name), Some("Ruby"));
+        assert_eq!(detect_language_from_shebang("#!/usr/bin/env ruby").map(|l| l.name), Some("Ruby"));
+    }
+
+    #[test]
+    fn test_highlighter_ruby_interpolation() {
+        let highlighter = Highlighter::new(Some("rb"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("puts \"hi #{name}\" # greet", &mut state);
+
+        let find = |text: &str| tokens.iter().find(|t| t.text == text).map(|t| t.token_type);
+        assert_eq!(find("name"), Some(TokenType::Normal));
+        assert_eq!(find("# greet"), Some(TokenType::Comment));
+        assert!(tokens.iter().any(|t| t.token_type == TokenType::String && t.text.starts_with("\"hi")));
+    }
+
+    #[test]
+    fn test_highlighter_todo_markers() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("x(); // TODO: tidy, FIXME(bob) not TODOS", &mut state);
+
+        let todos: Vec<&str> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::Todo)
+            .map(|t| t.text.as_str())
+            .collect();
+        assert_eq!(todos, ["TODO", "FIXME"]);
+        let last = tokens.last().unwrap();
+        assert_eq!((last.text.as_str(), last.token_type), ("(bob) not TODOS", TokenType::Comment));
+
+        // Markers inside block comments, including ones spanning lines
+        highlighter.highlight_line("/* XXX start", &mut state);
+        let tokens = highlighter.highlight_line("HACK */ let TODO = 1;", &mut state);
+        assert_eq!(tokens[0].token_type, TokenType::Todo);
+        assert_eq!(tokens[1].text, " */");
+        assert!(!tokens[2..].iter().any(|t| t.token_type == TokenType::Todo));
+        assert_eq!(TokenType::Todo.style(), Style::fg(ansi::FG_BRIGHT_YELLOW).bold());
+    }
+
+    #[test]
+    fn test_highlighter_makefile_comment() {
+        let highlighter = Highlighter::for_file(Some("Makefile"), None, None);
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("ifdef DEBUG # debug build", &mut state);
+
+        assert_eq!(tokens[0].text, "ifdef");
+        assert_eq!(tokens[0].token_type, TokenType::Keyword);
+        assert_eq!(tokens.last().unwrap().text, "# debug build");
+        assert_eq!(tokens.last().unwrap().token_type, TokenType::Comment);
+    }
+
+    #[test]
+    fn test_highlighter_dockerfile_keywords() {
+        let highlighter = Highlighter::for_file(Some("Dockerfile"), None, None);
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("FROM rust:1 AS build", &mut state);
+
+        let keywords: Vec<&str> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::Keyword)
+            .map(|t| t.text.as_str())
+            .collect();
+
+        assert_eq!(keywords, ["FROM", "AS"]);
+    }
+
     // Highlighter tests
     #[test]
     fn test_highlighter_no_language() {
@@ -931,6 +1813,185 @@ mod tests {
         assert_eq!(attrs[0].text, "#[derive(Debug)]");
     }
 
+    #[test]
+    fn test_highlighter_rust_raw_string() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("let s = r#\"he said \"hi\"\"#;", &mut state);
+
+        let strings: Vec<&Token> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::String)
+            .collect();
+
+        assert_eq!(strings.len(), 1);
+        assert_eq!(strings[0].text, "r#\"he said \"hi\"\"#");
+        assert_eq!(tokens.last().unwrap().text, ";");
+    }
+
+    #[test]
+    fn test_highlighter_rust_raw_string_no_escapes() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("let p = r\"C:\\dir\\\"; x", &mut state);
+
+        assert!(tokens.iter().any(|t| t.text == "r\"C:\\dir\\\"" && t.token_type == TokenType::String));
+        assert!(tokens.iter().any(|t| t.text == "x" && t.token_type == TokenType::Normal));
+    }
+
+    #[test]
+    fn test_highlighter_rust_multiline_raw_string() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+
+        highlighter.highlight_line("let s = br##\"first \"# still", &mut state);
+        assert_eq!(state.in_multiline_string.as_deref(), Some("\"##"));
+
+        let tokens = highlighter.highlight_line("end\\\"##; fn", &mut state);
+        assert_eq!(tokens[0].text, "end\\\"##");
+        assert_eq!(tokens[0].token_type, TokenType::String);
+        assert!(tokens.iter().any(|t| t.text == "fn" && t.token_type == TokenType::Keyword));
+        assert!(state.in_multiline_string.is_none());
+    }
+
+    #[test]
+    fn test_highlighter_rust_lifetime() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("fn f<'a>(x: &'static str) -> char { 'a' }", &mut state);
+
+        let lifetimes: Vec<&str> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::Lifetime)
+            .map(|t| t.text.as_str())
+            .collect();
+        let chars: Vec<&str> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::Char)
+            .map(|t| t.text.as_str())
+            .collect();
+
+        assert_eq!(lifetimes, ["'a", "'static"]);
+        assert_eq!(chars, ["'a'"]);
+    }
+
+    #[test]
+    fn test_highlighter_rust_lifetime_bounds() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("struct S<'a, T: 'a>(Vec<'a>, &'a T); // 'x'", &mut state);
+
+        let lifetimes = tokens.iter().filter(|t| t.token_type == TokenType::Lifetime).count();
+        assert_eq!(lifetimes, 4);
+        assert!(!tokens.iter().any(|t| t.token_type == TokenType::Char));
+        assert_eq!(tokens.last().map(|t| (t.text.as_str(), t.token_type)), Some(("// 'x'", TokenType::Comment)));
+    }
+
+    #[test]
+    fn test_highlighter_rust_unicode_escape_char() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("let c = '\\u{1F600}';", &mut state);
+
+        assert!(tokens.iter().any(|t| t.text == "'\\u{1F600}'" && t.token_type == TokenType::Char));
+    }
+
+    #[test]
+    fn test_highlighter_number_range() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("0..5", &mut state);
+
+        let texts: Vec<(&str, TokenType)> = tokens.iter()
+            .map(|t| (t.text.as_str(), t.token_type))
+            .collect();
+
+        assert_eq!(texts, [
+            ("0", TokenType::Number),
+            (".", TokenType::Punctuation),
+            (".", TokenType::Punctuation),
+            ("5", TokenType::Number),
+        ]);
+    }
+
+    #[test]
+    fn test_highlighter_number_double_dot() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("1.2.3", &mut state);
+
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["1.2", ".", "3"]);
+        assert_eq!(tokens[0].token_type, TokenType::Number);
+        assert_eq!(tokens[2].token_type, TokenType::Number);
+    }
+
+    #[test]
+    fn test_highlighter_number_invalid_hex() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("0xGG", &mut state);
+
+        assert_eq!(tokens[0].text, "0");
+        assert_eq!(tokens[0].token_type, TokenType::Number);
+        assert_eq!(tokens[1].text, "xGG");
+        assert_eq!(tokens[1].token_type, TokenType::Normal);
+    }
+
+    #[test]
+    fn test_highlighter_number_suffixes() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+
+        let tokens = highlighter.highlight_line("3f64 + 0xFFu8 + 0b1012 + 1e10 + 2px", &mut state);
+        let numbers: Vec<&str> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::Number)
+            .map(|t| t.text.as_str())
+            .collect();
+
+        assert_eq!(numbers, ["3f64", "0xFFu8", "0b101", "2", "1e10", "2"]);
+    }
+
+    #[test]
+    fn test_highlighter_number_method_call() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("1.max(x2)", &mut state);
+
+        let texts: Vec<(&str, TokenType)> = tokens.iter()
+            .map(|t| (t.text.as_str(), t.token_type))
+            .collect();
+
+        assert_eq!(texts[0], ("1", TokenType::Number));
+        assert_eq!(texts[2], ("max", TokenType::Function));
+        assert!(texts.contains(&("x2", TokenType::Normal)));
+    }
+
+    #[test]
+    fn test_highlighter_rust_paths() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("std::io::Result<Foo> = Ordering::Less.max(u8::MAX)", &mut state);
+
+        let kind = |text: &str| tokens.iter().find(|t| t.text == text).unwrap().token_type;
+        assert_eq!(kind("std"), TokenType::Namespace);
+        assert_eq!(kind("io"), TokenType::Namespace);
+        assert_eq!(kind("Result"), TokenType::Type);
+        assert_eq!(kind("Foo"), TokenType::Type);
+        assert_eq!(kind("Ordering"), TokenType::Type);
+        assert_eq!(kind("Less"), TokenType::Type);
+        assert_eq!(kind("max"), TokenType::Function);
+        assert_eq!(kind("u8"), TokenType::Type);
+        assert_eq!(kind("MAX"), TokenType::Constant);
+    }
+
+    #[test]
+    fn test_highlighter_paths_language_gated() {
+        let highlighter = Highlighter::new(Some("py"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("MAX_SIZE = Foo", &mut state);
+
+        assert_eq!(tokens[0].token_type, TokenType::Normal);
+        assert_eq!(tokens.last().unwrap().token_type, TokenType::Normal);
+    }
+
     #[test]
     fn test_highlighter_rust_operators() {
         let highlighter = Highlighter::new(Some("rs"));
@@ -971,6 +2032,134 @@ mod tests {
         assert!(keywords.iter().any(|t| t.text == "def"));
     }
 
+    #[test]
+    fn test_highlighter_python_triple_quoted_string() {
+        let highlighter = Highlighter::new(Some("py"));
+        let mut state = HighlightState::default();
+
+        let tokens1 = highlighter.highlight_line("x = \"\"\"start of doc", &mut state);
+        assert_eq!(tokens1.last().unwrap().text, "\"\"\"start of doc");
+        assert_eq!(tokens1.last().unwrap().token_type, TokenType::String);
+        assert_eq!(state.in_multiline_string.as_deref(), Some("\"\"\""));
+
+        // Quotes that don't close the string stay inside it
+        let tokens2 = highlighter.highlight_line("it's \"quoted\" here", &mut state);
+        assert_eq!(tokens2.len(), 1);
+        assert_eq!(tokens2[0].token_type, TokenType::String);
+        assert!(state.in_multiline_string.is_some());
+
+        let tokens3 = highlighter.highlight_line("end\"\"\" + y", &mut state);
+        assert_eq!(tokens3[0].text, "end\"\"\"");
+        assert_eq!(tokens3[0].token_type, TokenType::String);
+        assert!(state.in_multiline_string.is_none());
+        assert!(tokens3.iter().any(|t| t.text == "y" && t.token_type == TokenType::Normal));
+    }
+
+    #[test]
+    fn test_highlighter_python_single_line_triple_quotes() {
+        let highlighter = Highlighter::new(Some("py"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("s = '''a 'b' c'''", &mut state);
+
+        let strings: Vec<&Token> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::String)
+            .collect();
+
+        assert_eq!(strings.len(), 1);
+        assert_eq!(strings[0].text, "'''a 'b' c'''");
+        assert!(state.in_multiline_string.is_none());
+    }
+
+    #[test]
+    fn test_highlighter_python_fstring() {
+        let highlighter = Highlighter::new(Some("py"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("f\"hi {name.upper()} {{x}}\"", &mut state);
+
+        let texts: Vec<(&str, TokenType)> = tokens.iter()
+            .map(|t| (t.text.as_str(), t.token_type))
+            .collect();
+
+        assert_eq!(texts[0], ("f\"hi ", TokenType::String));
+        assert_eq!(texts[1], ("{", TokenType::Punctuation));
+        assert_eq!(texts[2], ("name", TokenType::Normal));
+        assert!(texts.contains(&("upper", TokenType::Function)));
+        assert_eq!(texts.last().unwrap(), &(" {{x}}\"", TokenType::String));
+        assert!(state.in_string.is_none());
+    }
+
+    #[test]
+    fn test_highlighter_python_plain_string_not_interpolated() {
+        let highlighter = Highlighter::new(Some("py"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("if\"{x}\"", &mut state);
+
+        assert_eq!(tokens[0].text, "if");
+        assert_eq!(tokens[0].token_type, TokenType::Keyword);
+        assert_eq!(tokens[1].text, "\"{x}\"");
+        assert_eq!(tokens[1].token_type, TokenType::String);
+    }
+
+    #[test]
+    fn test_highlighter_js_template_literal() {
+        let highlighter = Highlighter::new(Some("js"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("`hello ${user.name} ${typeof x}!`", &mut state);
+
+        let texts: Vec<(&str, TokenType)> = tokens.iter()
+            .map(|t| (t.text.as_str(), t.token_type))
+            .collect();
+
+        assert_eq!(texts[0], ("`hello ", TokenType::String));
+        assert_eq!(texts[1], ("${", TokenType::Punctuation));
+        assert!(texts.contains(&("user", TokenType::Normal)));
+        assert!(texts.contains(&("typeof", TokenType::Keyword)));
+        assert_eq!(texts.last().unwrap(), &("!`", TokenType::String));
+        assert!(state.in_string.is_none());
+    }
+
+    #[test]
+    fn test_highlighter_js_nested_template_literal() {
+        let highlighter = Highlighter::new(Some("ts"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("`a ${ok ? `b ${c}` : {d: 1}.d} e`", &mut state);
+
+        let strings: Vec<&str> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::String)
+            .map(|t| t.text.as_str())
+            .collect();
+
+        assert_eq!(strings, ["`a ", "`b ", "`", " e`"]);
+        assert!(tokens.iter().any(|t| t.text == "c" && t.token_type == TokenType::Normal));
+        assert!(tokens.iter().any(|t| t.text == "1" && t.token_type == TokenType::Number));
+        assert!(state.in_string.is_none());
+    }
+
+    #[test]
+    fn test_highlighter_js_plain_string_not_interpolated() {
+        let highlighter = Highlighter::new(Some("js"));
+        let mut state = HighlightState::default();
+        let tokens = highlighter.highlight_line("'${x}'", &mut state);
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type, TokenType::String);
+    }
+
+    #[test]
+    fn test_highlighter_js_multiline_template_literal() {
+        let highlighter = Highlighter::new(Some("js"));
+        let mut state = HighlightState::default();
+
+        highlighter.highlight_line("const s = `first", &mut state);
+        assert_eq!(state.in_string, Some('`'));
+
+        let tokens = highlighter.highlight_line("${n} last`;", &mut state);
+        assert_eq!(tokens[0].text, "${");
+        assert!(tokens.iter().any(|t| t.text == "n" && t.token_type == TokenType::Normal));
+        assert!(tokens.iter().any(|t| t.text == " last`" && t.token_type == TokenType::String));
+        assert!(state.in_string.is_none());
+    }
+
     #[test]
     fn test_highlighter_json_constants() {
         let highlighter = Highlighter::new(Some("json"));
@@ -1002,6 +2191,57 @@ mod tests {
         assert!(!TokenType::Macro.color().is_empty());
         assert!(!TokenType::Attribute.color().is_empty());
         assert!(!TokenType::Constant.color().is_empty());
+        assert!(!TokenType::Lifetime.color().is_empty());
+        assert!(!TokenType::Bracket(0).color().is_empty());
+        assert!(!TokenType::Namespace.color().is_empty());
+    }
+
+    #[test]
+    fn test_colorize_brackets_nesting() {
+        let highlighter = Highlighter::new(Some("rs"));
+        let mut state = HighlightState::default();
+
+        let mut tokens = highlighter.highlight_line("fn f(a: [u8; 2]) {", &mut state);
+        colorize_brackets(&mut tokens, &mut state);
+        let brackets: Vec<(&str, TokenType)> = tokens.iter()
+            .filter(|t| matches!(t.token_type, TokenType::Bracket(_)))
+            .map(|t| (t.text.as_str(), t.token_type))
+            .collect();
+
+        assert_eq!(brackets, [
+            ("(", TokenType::Bracket(0)),
+            ("[", TokenType::Bracket(1)),
+            ("]", TokenType::Bracket(1)),
+            (")", TokenType::Bracket(0)),
+            ("{", TokenType::Bracket(0)),
+        ]);
+        assert_eq!(state.bracket_depth, 1);
+
+        // Depth carries over to the closing brace on a later line
+        let mut tokens = highlighter.highlight_line("}", &mut state);
+        colorize_brackets(&mut tokens, &mut state);
+        assert_eq!(tokens[0].token_type, TokenType::Bracket(0));
+        assert_eq!(state.bracket_depth, 0);
+
+        // An extra closing bracket stands out and doesn't disturb the depth
+        let mut tokens = highlighter.highlight_line("x) (", &mut state);
+        colorize_brackets(&mut tokens, &mut state);
+        assert_eq!(tokens[1].token_type, TokenType::UnmatchedBracket);
+        assert_eq!(tokens.last().unwrap().token_type, TokenType::Bracket(0));
+        assert_eq!(TokenType::UnmatchedBracket.color(), ansi::FG_BRIGHT_RED);
+    }
+
+    #[test]
+    fn test_token_styles() {
+        let keyword = TokenType::Keyword.style();
+        assert!(keyword.bold && !keyword.italic);
+        assert_eq!(keyword.ansi(), format!("{}{}", ansi::FG_MAGENTA, ansi::BOLD));
+
+        let comment = TokenType::Comment.style();
+        assert!(comment.italic && !comment.bold);
+
+        assert_eq!(TokenType::Normal.style(), Style::fg(ansi::FG_DEFAULT));
+        assert_eq!(TokenType::Normal.style().ansi(), ansi::FG_DEFAULT);
     }
 
     // Language definition tests
@@ -1021,6 +2261,6 @@ mod tests {
 
     #[test]
     fn test_language_count() {
-        assert_eq!(LANGUAGES.len(), 16); // Verify all 16 languages are present
+        assert_eq!(LANGUAGES.len(), 26); // Verify all 26 languages are present
     }
 }
@@ -1,4 +1,6 @@
+use std::cell::RefCell;
 use std::io::{self, Read, Write};
+use std::sync::{Mutex, PoisonError};
 
 #[cfg(unix)]
 use std::os::unix::io::AsRawFd;
@@ -20,6 +22,7 @@ pub mod ansi {
     pub const ITALIC: &str = "\x1b[3m";
     pub const UNDERLINE: &str = "\x1b[4m";
     pub const REVERSE: &str = "\x1b[7m";
+    pub const REVERSE_OFF: &str = "\x1b[27m";
 
     // Foreground colors
     pub const FG_BLACK: &str = "\x1b[30m";
@@ -53,6 +56,10 @@ pub mod ansi {
     pub const BG_WHITE: &str = "\x1b[47m";
     pub const BG_DEFAULT: &str = "\x1b[49m";
 
+    // Bracketed paste
+    pub const PASTE_ENABLE: &str = "\x1b[?2004h";
+    pub const PASTE_DISABLE: &str = "\x1b[?2004l";
+
     pub fn cursor_position(row: u16, col: u16) -> String {
         format!("\x1b[{};{}H", row + 1, col + 1)
     }
@@ -64,6 +71,11 @@ pub mod ansi {
     pub fn bg_rgb(r: u8, g: u8, b: u8) -> String {
         format!("\x1b[48;2;{};{};{}m", r, g, b)
     }
+
+    /// OSC 52 sequence asking the terminal to put `text` on the system clipboard
+    pub fn clipboard_copy(text: &str) -> String {
+        format!("\x1b]52;c;{}\x07", super::base64_encode(text.as_bytes()))
+    }
 }
 
 /// Key events
@@ -81,15 +93,81 @@ pub enum Key {
     Down,
     Left,
     Right,
+    CtrlUp,
+    CtrlDown,
+    CtrlLeft,
+    CtrlRight,
+    ShiftUp,
+    ShiftDown,
+    ShiftLeft,
+    ShiftRight,
     Home,
     End,
     PageUp,
     PageDown,
     Insert,
     F(u8),
+    /// Text delivered by a bracketed paste
+    Paste(String),
     Unknown(Vec<u8>),
 }
 
+/// Standard base64 encoding with padding
+pub fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Length of the first key in `buf`, or `None` if it is an incomplete sequence
+fn key_length(buf: &[u8]) -> Option<usize> {
+    match buf {
+        [] | [27] => None,
+        // CSI: ESC [ params... final byte in 0x40..=0x7E
+        [27, b'[', rest @ ..] => rest
+            .iter()
+            .position(|b| (0x40..=0x7E).contains(b))
+            .map(|i| i + 3),
+        // SS3: ESC O <key>
+        [27, b'O'] => None,
+        [27, b'O', _, ..] => Some(3),
+        // Alt + key
+        [27, _, ..] => Some(2),
+        [lead, ..] if *lead >= 0xC0 => {
+            let len = match lead {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                _ => 4,
+            };
+            (buf.len() >= len).then_some(len)
+        }
+        _ => Some(1),
+    }
+}
+
+/// Markers surrounding bracketed paste content
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Decode bracketed paste content, normalizing line endings to `\n`
+fn paste_text(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
 /// Terminal size
 #[derive(Debug, Clone, Copy)]
 pub struct Size {
@@ -114,26 +192,30 @@ struct Termios {
 
 /// Terminal handler
 pub struct Terminal {
-    #[cfg(unix)]
-    original_termios: Option<Termios>,
-    #[cfg(windows)]
-    original_mode: Option<(u32, u32)>,
+    /// Input read past the end of the last key
+    pending_input: RefCell<Vec<u8>>,
 }
 
+/// Terminal settings from before raw mode. Kept outside `Terminal` so the panic hook
+/// can restore them without access to the editor.
+#[cfg(unix)]
+static ORIGINAL_TERMIOS: Mutex<Option<Termios>> = Mutex::new(None);
+/// Console modes (stdin, stdout) from before raw mode
+#[cfg(windows)]
+static ORIGINAL_MODE: Mutex<Option<(u32, u32)>> = Mutex::new(None);
+
 impl Terminal {
     pub fn new() -> io::Result<Self> {
-        let mut terminal = Terminal {
-            #[cfg(unix)]
-            original_termios: None,
-            #[cfg(windows)]
-            original_mode: None,
+        let terminal = Terminal {
+            pending_input: RefCell::new(Vec::new()),
         };
-        terminal.enable_raw_mode()?;
+        Terminal::enable_raw_mode()?;
+        print!("{}", ansi::PASTE_ENABLE);
         Ok(terminal)
     }
 
     #[cfg(unix)]
-    fn enable_raw_mode(&mut self) -> io::Result<()> {
+    fn enable_raw_mode() -> io::Result<()> {
         use std::mem::MaybeUninit;
 
         // termios flags
@@ -175,7 +257,7 @@ impl Terminal {
         }
 
         let mut termios = unsafe { termios.assume_init() };
-        self.original_termios = Some(termios);
+        *ORIGINAL_TERMIOS.lock().unwrap_or_else(PoisonError::into_inner) = Some(termios);
 
         // Disable canonical mode and echo
         termios.c_lflag &= !(ICANON | ECHO | ISIG | IEXTEN);
@@ -195,7 +277,7 @@ impl Terminal {
     }
 
     #[cfg(windows)]
-    fn enable_raw_mode(&mut self) -> io::Result<()> {
+    fn enable_raw_mode() -> io::Result<()> {
         use std::ptr::null_mut;
 
         const STD_INPUT_HANDLE: u32 = 0xFFFFFFF6;
@@ -229,7 +311,7 @@ impl Terminal {
                 return Err(io::Error::last_os_error());
             }
 
-            self.original_mode = Some((stdin_mode, stdout_mode));
+            *ORIGINAL_MODE.lock().unwrap_or_else(PoisonError::into_inner) = Some((stdin_mode, stdout_mode));
 
             // Enable virtual terminal input
             let new_stdin_mode = ENABLE_VIRTUAL_TERMINAL_INPUT;
@@ -248,14 +330,15 @@ impl Terminal {
     }
 
     #[cfg(unix)]
-    fn disable_raw_mode(&mut self) -> io::Result<()> {
+    fn disable_raw_mode() -> io::Result<()> {
         const TCSAFLUSH: i32 = 2;
 
         unsafe extern "C" {
             fn tcsetattr(fd: i32, action: i32, termios: *const Termios) -> i32;
         }
 
-        if let Some(termios) = self.original_termios.take() {
+        let original = ORIGINAL_TERMIOS.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(termios) = original {
             let fd = io::stdin().as_raw_fd();
             if unsafe { tcsetattr(fd, TCSAFLUSH, &termios) } != 0 {
                 return Err(io::Error::last_os_error());
@@ -265,8 +348,9 @@ impl Terminal {
     }
 
     #[cfg(windows)]
-    fn disable_raw_mode(&mut self) -> io::Result<()> {
-        if let Some((stdin_mode, stdout_mode)) = self.original_mode.take() {
+    fn disable_raw_mode() -> io::Result<()> {
+        let original = ORIGINAL_MODE.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some((stdin_mode, stdout_mode)) = original {
             const STD_INPUT_HANDLE: u32 = 0xFFFFFFF6;
             const STD_OUTPUT_HANDLE: u32 = 0xFFFFFFF5;
 
@@ -287,6 +371,11 @@ impl Terminal {
         Ok(())
     }
 
+    /// Whether the terminal advertises 24-bit color through `COLORTERM`
+    pub fn supports_truecolor() -> bool {
+        matches!(std::env::var("COLORTERM").as_deref(), Ok("truecolor" | "24bit"))
+    }
+
     /// Get terminal size
     pub fn size() -> io::Result<Size> {
         #[cfg(unix)]
@@ -377,22 +466,63 @@ impl Terminal {
         }
     }
 
-    /// Read a key from stdin
+    /// Read a key from stdin. Bytes after the first key are kept for the next call, and
+    /// escape sequences split across reads are completed before parsing.
     pub fn read_key(&self) -> io::Result<Option<Key>> {
-        let mut buf = [0u8; 8];
+        let mut data = std::mem::take(&mut *self.pending_input.borrow_mut());
         let stdin = io::stdin();
         let mut handle = stdin.lock();
 
-        let n = handle.read(&mut buf)?;
-        if n == 0 {
+        if data.is_empty() && !Self::read_more(&mut handle, &mut data)? {
             return Ok(None);
         }
 
-        let key = self.parse_key(&buf[..n]);
-        Ok(Some(key))
+        if data.starts_with(PASTE_START) {
+            // Collect everything up to the end marker as a single paste
+            let mut paste = data.split_off(PASTE_START.len());
+            let end = loop {
+                if let Some(pos) = paste.windows(PASTE_END.len()).position(|w| w == PASTE_END) {
+                    break pos;
+                }
+                if !Self::read_more(&mut handle, &mut paste)? {
+                    break paste.len();
+                }
+            };
+            let rest = paste.split_off((end + PASTE_END.len()).min(paste.len()));
+            paste.truncate(end);
+            *self.pending_input.borrow_mut() = rest;
+            return Ok(Some(Key::Paste(paste_text(&paste))));
+        }
+
+        // Wait briefly for the rest of an incomplete sequence; a lone ESC with
+        // nothing following is the Escape key
+        let len = loop {
+            match key_length(&data) {
+                Some(len) => break len,
+                None if Self::read_more(&mut handle, &mut data)? => {}
+                None => break data.len(),
+            }
+        };
+
+        let rest = data.split_off(len);
+        *self.pending_input.borrow_mut() = rest;
+        Ok(Some(Self::parse_key(&data)))
     }
 
-    fn parse_key(&self, buf: &[u8]) -> Key {
+    /// Append available input to `data`, returning false if none arrived before the
+    /// read timeout
+    fn read_more(handle: &mut io::StdinLock, data: &mut Vec<u8>) -> io::Result<bool> {
+        // Console reads block without a timeout, so only wait for a first chunk
+        if cfg!(windows) && !data.is_empty() {
+            return Ok(false);
+        }
+        let mut buf = [0u8; 64];
+        let n = handle.read(&mut buf)?;
+        data.extend_from_slice(&buf[..n]);
+        Ok(n > 0)
+    }
+
+    fn parse_key(buf: &[u8]) -> Key {
         match buf {
             // Control characters
             [0] => Key::Ctrl(' '),
@@ -430,6 +560,15 @@ impl Terminal {
             [27, 91, 66] => Key::Down,
             [27, 91, 67] => Key::Right,
             [27, 91, 68] => Key::Left,
+            // Modified arrows: ESC [ 1 ; <modifier> <direction>, 2 = Shift, 5 = Ctrl
+            [27, 91, 49, 59, 53, 65] => Key::CtrlUp,
+            [27, 91, 49, 59, 53, 66] => Key::CtrlDown,
+            [27, 91, 49, 59, 53, 67] => Key::CtrlRight,
+            [27, 91, 49, 59, 53, 68] => Key::CtrlLeft,
+            [27, 91, 49, 59, 50, 65] => Key::ShiftUp,
+            [27, 91, 49, 59, 50, 66] => Key::ShiftDown,
+            [27, 91, 49, 59, 50, 67] => Key::ShiftRight,
+            [27, 91, 49, 59, 50, 68] => Key::ShiftLeft,
             [27, 91, 72] => Key::Home,
             [27, 91, 70] => Key::End,
             [27, 91, 49, 126] => Key::Home,
@@ -497,13 +636,55 @@ impl Terminal {
     pub fn flush() -> io::Result<()> {
         io::stdout().flush()
     }
+
+    /// Point stdin at the controlling terminal, after piped input has been read from it,
+    /// so keys can still be read. Must be called before `new`.
+    #[cfg(unix)]
+    pub fn reattach_stdin() -> io::Result<()> {
+        unsafe extern "C" {
+            fn dup2(old: i32, new: i32) -> i32;
+        }
+
+        let tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        if unsafe { dup2(tty.as_raw_fd(), io::stdin().as_raw_fd()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Point stdin at the console, after piped input has been read from it, so keys can
+    /// still be read. Must be called before `new`.
+    #[cfg(windows)]
+    pub fn reattach_stdin() -> io::Result<()> {
+        use std::os::windows::io::IntoRawHandle;
+
+        const STD_INPUT_HANDLE: u32 = 0xFFFFFFF6;
+
+        #[link(name = "kernel32")]
+        unsafe extern "system" {
+            fn SetStdHandle(nStdHandle: u32, hHandle: *mut std::ffi::c_void) -> i32;
+        }
+
+        let console = std::fs::OpenOptions::new().read(true).write(true).open("CONIN$")?;
+        if unsafe { SetStdHandle(STD_INPUT_HANDLE, console.into_raw_handle()) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Leave raw mode and undo the other setup from `new`. Does nothing to the modes
+    /// if raw mode is not active, so it is safe to call more than once.
+    pub fn restore() {
+        print!("{}", ansi::PASTE_DISABLE);
+        Terminal::show_cursor();
+        let _ = Terminal::flush();
+        let _ = Terminal::disable_raw_mode();
+    }
 }
 
 impl Drop for Terminal {
     fn drop(&mut self) {
-        Terminal::show_cursor();
-        let _ = Terminal::flush();
-        let _ = self.disable_raw_mode();
+        Terminal::restore();
     }
 }
 
@@ -674,6 +855,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode("héllo\n".as_bytes()), "aMOpbGxvCg==");
+    }
+
+    #[test]
+    fn test_ansi_clipboard_copy() {
+        assert_eq!(ansi::clipboard_copy("hi"), "\x1b]52;c;aGk=\x07");
+    }
+
+    #[test]
+    fn test_paste_text_normalizes_line_endings() {
+        assert_eq!(paste_text(b"a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!(paste_text("fn ()".as_bytes()), "fn ()");
+    }
+
+    #[test]
+    fn test_parse_modified_arrows() {
+        assert_eq!(Terminal::parse_key(b"\x1b[1;5C"), Key::CtrlRight);
+        assert_eq!(Terminal::parse_key(b"\x1b[1;5D"), Key::CtrlLeft);
+        assert_eq!(Terminal::parse_key(b"\x1b[1;5A"), Key::CtrlUp);
+        assert_eq!(Terminal::parse_key(b"\x1b[1;2B"), Key::ShiftDown);
+        assert_eq!(Terminal::parse_key(b"\x1b[1;2D"), Key::ShiftLeft);
+        assert_eq!(Terminal::parse_key(b"\x1b[C"), Key::Right);
+        assert!(matches!(Terminal::parse_key(b"\x1b[1;9C"), Key::Unknown(_)));
+    }
+
+    #[test]
+    fn test_key_length() {
+        assert_eq!(key_length(b""), None);
+        assert_eq!(key_length(b"\x1b"), None);
+        assert_eq!(key_length(b"\x1b["), None);
+        assert_eq!(key_length(b"\x1b[1;5"), None);
+        assert_eq!(key_length(b"\x1b[Ajk"), Some(3));
+        assert_eq!(key_length(b"\x1b[1;5Cx"), Some(6));
+        assert_eq!(key_length(b"\x1b[3~"), Some(4));
+        assert_eq!(key_length(b"\x1bOP"), Some(3));
+        assert_eq!(key_length(b"\x1bx"), Some(2));
+        assert_eq!(key_length(b"jk"), Some(1));
+        assert_eq!(key_length("é".as_bytes()), Some(2));
+        assert_eq!(key_length(&"世".as_bytes()[..2]), None);
+    }
+
     // Note: Terminal::new(), Terminal::size(), and Terminal::read_key()
     // require actual terminal access and cannot be easily unit tested
     // They are better covered by integration tests
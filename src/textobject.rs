@@ -0,0 +1,326 @@
+use crate::buffer::Buffer;
+
+/// A position in the buffer as (row, col)
+pub type Pos = (usize, usize);
+
+/// A span of buffer text; `start` is inclusive and `end` exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRange {
+    pub start: Pos,
+    pub end: Pos,
+}
+
+/// Find the range of a text object around `cursor`. `object` is the character typed
+/// after `i`/`a` (`w`, `W`, a quote, or a bracket); `around` selects the `a` variant.
+pub fn select(buffer: &Buffer, cursor: Pos, object: char, around: bool) -> Option<TextRange> {
+    match object {
+        'w' => select_word(buffer, cursor, around, false),
+        'W' => select_word(buffer, cursor, around, true),
+        '"' | '\'' | '`' => select_quote(buffer, cursor, object, around),
+        '(' | ')' | 'b' => select_bracket(buffer, cursor, '(', ')', around),
+        '[' | ']' => select_bracket(buffer, cursor, '[', ']', around),
+        '{' | '}' | 'B' => select_bracket(buffer, cursor, '{', '}', around),
+        '<' | '>' => select_bracket(buffer, cursor, '<', '>', around),
+        _ => None,
+    }
+}
+
+/// Position of the bracket matching the one under `cursor`, or the first bracket after
+/// it on the line when not on one (`%`)
+pub fn matching_bracket(buffer: &Buffer, (row, col): Pos) -> Option<Pos> {
+    let (col, &c) = buffer
+        .line(row)?
+        .chars
+        .iter()
+        .enumerate()
+        .skip(col)
+        .find(|(_, c)| "()[]{}".contains(**c))?;
+    let (open, close) = match c {
+        '(' | ')' => ('(', ')'),
+        '[' | ']' => ('[', ']'),
+        _ => ('{', '}'),
+    };
+    let range = select_bracket(buffer, (row, col), open, close, true)?;
+    if c == open {
+        Some((range.end.0, range.end.1 - 1))
+    } else {
+        Some(range.start)
+    }
+}
+
+/// Character class used to split words: 0 whitespace, 1 word, 2 punctuation
+pub fn char_class(c: char, big_word: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big_word || c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn select_word(buffer: &Buffer, (row, col): Pos, around: bool, big_word: bool) -> Option<TextRange> {
+    let chars = &buffer.line(row)?.chars;
+    if col >= chars.len() {
+        return None;
+    }
+
+    let class = char_class(chars[col], big_word);
+    let mut start = col;
+    while start > 0 && char_class(chars[start - 1], big_word) == class {
+        start -= 1;
+    }
+    let mut end = col + 1;
+    while end < chars.len() && char_class(chars[end], big_word) == class {
+        end += 1;
+    }
+
+    if around && class != 0 {
+        // Include trailing whitespace, or leading whitespace if there is none
+        let mut trailing = end;
+        while trailing < chars.len() && chars[trailing].is_whitespace() {
+            trailing += 1;
+        }
+        if trailing > end {
+            end = trailing;
+        } else {
+            while start > 0 && chars[start - 1].is_whitespace() {
+                start -= 1;
+            }
+        }
+    }
+
+    Some(TextRange {
+        start: (row, start),
+        end: (row, end),
+    })
+}
+
+fn select_quote(buffer: &Buffer, (row, col): Pos, quote: char, around: bool) -> Option<TextRange> {
+    let chars = &buffer.line(row)?.chars;
+
+    // Unescaped quote positions, paired up from the start of the line
+    let mut quotes = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == quote {
+            quotes.push(i);
+        }
+        i += 1;
+    }
+
+    // The pair containing the cursor, or else the first pair after it
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(_, close)| col <= close)?;
+
+    let (start, end) = if around {
+        (open, close + 1)
+    } else {
+        (open + 1, close)
+    };
+    Some(TextRange {
+        start: (row, start),
+        end: (row, end),
+    })
+}
+
+fn select_bracket(
+    buffer: &Buffer,
+    (row, col): Pos,
+    open: char,
+    close: char,
+    around: bool,
+) -> Option<TextRange> {
+    let at = |(r, c): Pos| buffer.line(r).and_then(|l| l.chars.get(c).copied());
+
+    // Walk backward to the unmatched opening bracket
+    let mut open_pos = None;
+    let mut depth = 0;
+    let mut pos = Some((row, col));
+    if at((row, col)) == Some(close) {
+        // On a closing bracket: its own pair is the target
+        pos = prev_pos(buffer, (row, col));
+    }
+    while let Some(p) = pos {
+        match at(p) {
+            Some(c) if c == close => depth += 1,
+            Some(c) if c == open && depth == 0 => {
+                open_pos = Some(p);
+                break;
+            }
+            Some(c) if c == open => depth -= 1,
+            _ => {}
+        }
+        pos = prev_pos(buffer, p);
+    }
+    let open_pos = open_pos?;
+
+    // Walk forward to the matching closing bracket
+    let mut depth = 0;
+    let mut pos = next_pos(buffer, open_pos);
+    let close_pos = loop {
+        let p = pos?;
+        match at(p) {
+            Some(c) if c == open => depth += 1,
+            Some(c) if c == close && depth == 0 => break p,
+            Some(c) if c == close => depth -= 1,
+            _ => {}
+        }
+        pos = next_pos(buffer, p);
+    };
+
+    if around {
+        Some(TextRange {
+            start: open_pos,
+            end: (close_pos.0, close_pos.1 + 1),
+        })
+    } else {
+        Some(TextRange {
+            start: (open_pos.0, open_pos.1 + 1),
+            end: close_pos,
+        })
+    }
+}
+
+/// Previous character position, crossing line boundaries
+fn prev_pos(buffer: &Buffer, (row, col): Pos) -> Option<Pos> {
+    if col > 0 {
+        return Some((row, col - 1));
+    }
+    let mut row = row;
+    while row > 0 {
+        row -= 1;
+        let len = buffer.line(row)?.len();
+        if len > 0 {
+            return Some((row, len - 1));
+        }
+    }
+    None
+}
+
+/// Next character position, crossing line boundaries
+fn next_pos(buffer: &Buffer, (row, col): Pos) -> Option<Pos> {
+    if col + 1 < buffer.line(row)?.len() {
+        return Some((row, col + 1));
+    }
+    let mut row = row + 1;
+    while row < buffer.line_count() {
+        if !buffer.line(row)?.is_empty() {
+            return Some((row, 0));
+        }
+        row += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Line;
+
+    fn buffer_from(lines: &[&str]) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.lines = lines.iter().map(|l| Line::from_str(l)).collect();
+        buffer
+    }
+
+    fn selected(buffer: &Buffer, range: TextRange) -> String {
+        buffer
+            .text_in_range(range.start, range.end)
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_select_word() {
+        let buffer = buffer_from(&["let foo_bar = baz.qux;"]);
+
+        let range = select(&buffer, (0, 5), 'w', false).unwrap();
+        assert_eq!(selected(&buffer, range), "foo_bar");
+
+        let range = select(&buffer, (0, 5), 'w', true).unwrap();
+        assert_eq!(selected(&buffer, range), "foo_bar ");
+
+        let range = select(&buffer, (0, 15), 'W', false).unwrap();
+        assert_eq!(selected(&buffer, range), "baz.qux;");
+
+        // Word at the end of the line takes leading whitespace instead
+        let buffer = buffer_from(&["a end"]);
+        let range = select(&buffer, (0, 3), 'w', true).unwrap();
+        assert_eq!(selected(&buffer, range), " end");
+    }
+
+    #[test]
+    fn test_select_quote() {
+        let buffer = buffer_from(&[r#"say("hi \"there\"", "x")"#]);
+
+        let range = select(&buffer, (0, 6), '"', false).unwrap();
+        assert_eq!(selected(&buffer, range), r#"hi \"there\""#);
+
+        let range = select(&buffer, (0, 6), '"', true).unwrap();
+        assert_eq!(selected(&buffer, range), r#""hi \"there\"""#);
+
+        // Cursor before any quote uses the first pair on the line
+        let range = select(&buffer, (0, 0), '"', false).unwrap();
+        assert_eq!(selected(&buffer, range), r#"hi \"there\""#);
+
+        assert!(select(&buffer, (0, 0), '\'', false).is_none());
+    }
+
+    #[test]
+    fn test_select_bracket() {
+        let buffer = buffer_from(&["f(a, (b), c)"]);
+
+        let range = select(&buffer, (0, 3), '(', false).unwrap();
+        assert_eq!(selected(&buffer, range), "a, (b), c");
+
+        let range = select(&buffer, (0, 6), 'b', true).unwrap();
+        assert_eq!(selected(&buffer, range), "(b)");
+
+        // On the closing bracket selects its own pair
+        let range = select(&buffer, (0, 11), ')', false).unwrap();
+        assert_eq!(selected(&buffer, range), "a, (b), c");
+
+        assert!(select(&buffer, (0, 0), '[', false).is_none());
+    }
+
+    #[test]
+    fn test_select_bracket_multi_line() {
+        let buffer = buffer_from(&["fn f() {", "    x();", "}"]);
+
+        let range = select(&buffer, (1, 4), '{', false).unwrap();
+        assert_eq!(range.start, (0, 8));
+        assert_eq!(range.end, (2, 0));
+
+        let range = select(&buffer, (1, 4), 'B', true).unwrap();
+        assert_eq!(selected(&buffer, range), "{\n    x();\n}");
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let buffer = buffer_from(&["fn f(a: [u8; 2]) {", "    x(a[0]);", "}"]);
+
+        assert_eq!(matching_bracket(&buffer, (0, 4)), Some((0, 15)));
+        assert_eq!(matching_bracket(&buffer, (0, 15)), Some((0, 4)));
+        assert_eq!(matching_bracket(&buffer, (0, 8)), Some((0, 14)));
+        assert_eq!(matching_bracket(&buffer, (0, 17)), Some((2, 0)));
+        assert_eq!(matching_bracket(&buffer, (2, 0)), Some((0, 17)));
+
+        // Off a bracket, the next one on the line is used
+        assert_eq!(matching_bracket(&buffer, (1, 0)), Some((1, 10)));
+        assert_eq!(matching_bracket(&buffer, (0, 16)), Some((2, 0)));
+
+        // Unbalanced or missing brackets have no match
+        let buffer = buffer_from(&["(a", "b"]);
+        assert_eq!(matching_bracket(&buffer, (0, 0)), None);
+        assert_eq!(matching_bracket(&buffer, (1, 0)), None);
+    }
+}
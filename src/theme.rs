@@ -0,0 +1,317 @@
+use crate::syntax::{Style, TokenType};
+use crate::terminal::ansi;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Color scheme names accepted by `:colorscheme`, besides `custom` for the theme file
+pub const BUILTIN_THEMES: &[&str] = &["dark", "light"];
+
+/// Config names of the token colors a theme can set
+const TOKEN_NAMES: &[(&str, TokenType)] = &[
+    ("normal", TokenType::Normal),
+    ("keyword", TokenType::Keyword),
+    ("type", TokenType::Type),
+    ("string", TokenType::String),
+    ("char", TokenType::Char),
+    ("number", TokenType::Number),
+    ("comment", TokenType::Comment),
+    ("operator", TokenType::Operator),
+    ("punctuation", TokenType::Punctuation),
+    ("function", TokenType::Function),
+    ("macro", TokenType::Macro),
+    ("attribute", TokenType::Attribute),
+    ("constant", TokenType::Constant),
+    ("lifetime", TokenType::Lifetime),
+    ("namespace", TokenType::Namespace),
+    ("todo", TokenType::Todo),
+];
+
+/// A color from the 16-color palette (as its foreground escape code) or a 24-bit one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Ansi(&'static str),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Parse a `#rrggbb` color
+    pub fn parse(text: &str) -> Option<Color> {
+        let hex = text.strip_prefix('#').filter(|h| h.len() == 6 && h.is_ascii())?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// A color from a `0xrrggbb` value
+    const fn hex(rgb: u32) -> Color {
+        Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+
+    /// Escape sequence that sets this as the foreground color
+    pub fn fg(&self) -> String {
+        match *self {
+            Color::Ansi(code) => code.to_string(),
+            Color::Rgb(r, g, b) => ansi::fg_rgb(r, g, b),
+        }
+    }
+
+    /// Escape sequence that sets this as the background color
+    pub fn bg(&self) -> String {
+        match *self {
+            // Background codes are the foreground ones plus 10
+            Color::Ansi(code) => {
+                let number: u8 = code.trim_start_matches("\x1b[").trim_end_matches('m').parse().unwrap_or(39);
+                format!("\x1b[{}m", number + 10)
+            }
+            Color::Rgb(r, g, b) => ansi::bg_rgb(r, g, b),
+        }
+    }
+}
+
+/// 24-bit colors for the dark theme
+const DARK_RGB: &[(TokenType, u32)] = &[
+    (TokenType::Keyword, 0xc678dd),
+    (TokenType::Type, 0xe5c07b),
+    (TokenType::String, 0x98c379),
+    (TokenType::Char, 0x98c379),
+    (TokenType::Number, 0xd19a66),
+    (TokenType::Comment, 0x7f848e),
+    (TokenType::Operator, 0xe06c75),
+    (TokenType::Function, 0x61afef),
+    (TokenType::Macro, 0x56b6c2),
+    (TokenType::Attribute, 0xd19a66),
+    (TokenType::Constant, 0xd19a66),
+    (TokenType::Lifetime, 0x56b6c2),
+    (TokenType::Namespace, 0x61afef),
+    (TokenType::Todo, 0xe5c07b),
+];
+
+/// 24-bit colors for the light theme
+const LIGHT_RGB: &[(TokenType, u32)] = &[
+    (TokenType::Keyword, 0xa626a4),
+    (TokenType::Type, 0xc18401),
+    (TokenType::String, 0x50a14f),
+    (TokenType::Char, 0x50a14f),
+    (TokenType::Number, 0x986801),
+    (TokenType::Comment, 0xa0a1a7),
+    (TokenType::Operator, 0xe45649),
+    (TokenType::Function, 0x4078f2),
+    (TokenType::Macro, 0x0184bc),
+    (TokenType::Attribute, 0x986801),
+    (TokenType::Constant, 0x986801),
+    (TokenType::Lifetime, 0x0184bc),
+    (TokenType::Namespace, 0x4078f2),
+    (TokenType::Todo, 0xe45649),
+];
+
+/// Colors for syntax tokens and the editor's chrome
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    /// Token colors that replace the defaults from `TokenType::color`
+    colors: HashMap<TokenType, Color>,
+    /// Line numbers in the gutter
+    pub line_number: Color,
+    /// Background of the cursor line (`:set cursorline`)
+    pub cursor_line: Color,
+    /// Background of the column marked by `:set colorcolumn`
+    pub color_column: Color,
+    /// Status line text and background; `None` draws it in reverse video
+    pub status_bar: Option<(Color, Color)>,
+}
+
+impl Theme {
+    /// The default colors, for dark terminal backgrounds
+    pub fn dark() -> Self {
+        Theme {
+            name: "dark".to_string(),
+            colors: HashMap::new(),
+            line_number: Color::Ansi(ansi::FG_BRIGHT_BLACK),
+            cursor_line: Color::Ansi(ansi::FG_BLACK),
+            color_column: Color::Ansi(ansi::FG_RED),
+            status_bar: None,
+        }
+    }
+
+    /// Darker colors that stay readable on light terminal backgrounds
+    pub fn light() -> Self {
+        let colors = [
+            (TokenType::Type, ansi::FG_BLUE),
+            (TokenType::Number, ansi::FG_RED),
+            (TokenType::Constant, ansi::FG_RED),
+            (TokenType::Attribute, ansi::FG_BLUE),
+            (TokenType::Macro, ansi::FG_MAGENTA),
+            (TokenType::Lifetime, ansi::FG_CYAN),
+            (TokenType::Namespace, ansi::FG_BLUE),
+            (TokenType::Todo, ansi::FG_RED),
+        ];
+        Theme {
+            name: "light".to_string(),
+            colors: colors.into_iter().map(|(token, code)| (token, Color::Ansi(code))).collect(),
+            line_number: Color::Ansi(ansi::FG_BRIGHT_BLACK),
+            cursor_line: Color::Ansi(ansi::FG_WHITE),
+            color_column: Color::Ansi(ansi::FG_BRIGHT_RED),
+            status_bar: None,
+        }
+    }
+
+    /// A built-in theme by name, using 24-bit colors if the terminal supports them
+    pub fn named(name: &str, truecolor: bool) -> Option<Self> {
+        let (mut theme, rgb, [line_number, cursor_line, color_column]) = match name {
+            "dark" => (Theme::dark(), DARK_RGB, [0x5c6370, 0x2c313c, 0x3e2a2e]),
+            "light" => (Theme::light(), LIGHT_RGB, [0x9d9d9f, 0xf0f0f0, 0xf6dada]),
+            _ => return None,
+        };
+        if truecolor {
+            theme.colors = rgb.iter().map(|&(token, rgb)| (token, Color::hex(rgb))).collect();
+            theme.line_number = Color::hex(line_number);
+            theme.cursor_line = Color::hex(cursor_line);
+            theme.color_column = Color::hex(color_column);
+        }
+        Some(theme)
+    }
+
+    /// Read a theme of `key = #rrggbb` lines, starting from the dark theme's colors.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut theme = Theme::dark();
+        theme.name = "custom".to_string();
+        let (mut status_fg, mut status_bg) = (None, None);
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (line.starts_with('#') && !line.contains('=')) {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected key = #rrggbb", number + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let Some(color) = Color::parse(value) else {
+                return Err(format!("line {}: invalid color: {}", number + 1, value));
+            };
+            match key {
+                "line_number" => theme.line_number = color,
+                "cursor_line" => theme.cursor_line = color,
+                "color_column" => theme.color_column = color,
+                "status_fg" => status_fg = Some(color),
+                "status_bg" => status_bg = Some(color),
+                _ => match TOKEN_NAMES.iter().find(|(name, _)| *name == key) {
+                    Some(&(_, token)) => {
+                        theme.colors.insert(token, color);
+                    }
+                    None => return Err(format!("line {}: unknown color name: {}", number + 1, key)),
+                },
+            }
+        }
+
+        if status_fg.is_some() || status_bg.is_some() {
+            theme.status_bar = Some((
+                status_fg.unwrap_or(Color::Ansi(ansi::FG_DEFAULT)),
+                status_bg.unwrap_or(Color::Ansi(ansi::FG_DEFAULT)),
+            ));
+        }
+        Ok(theme)
+    }
+
+    /// The theme file (`$XDG_CONFIG_HOME/zedit/theme`, `~/.config/zedit/theme`, or
+    /// `%APPDATA%\zedit\theme` on Windows)
+    pub fn user_file() -> Option<PathBuf> {
+        let dir = if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+            PathBuf::from(dir)
+        } else if cfg!(windows) {
+            PathBuf::from(env::var_os("APPDATA")?)
+        } else {
+            PathBuf::from(env::var_os("HOME")?).join(".config")
+        };
+        Some(dir.join("zedit").join("theme"))
+    }
+
+    /// Load the theme file, or `None` if there isn't one
+    pub fn load_user() -> Result<Option<Self>, String> {
+        let Some(file) = Theme::user_file() else {
+            return Ok(None);
+        };
+        match fs::read_to_string(&file) {
+            Ok(text) => Theme::parse(&text).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("{}: {}", file.display(), e)),
+        }
+    }
+
+    /// Escape sequence for drawing a token: its color plus its text attributes
+    pub fn style(&self, token: TokenType) -> String {
+        let style = token.style();
+        match self.colors.get(&token) {
+            Some(color) => color.fg() + &Style { fg: "", ..style }.ansi(),
+            None => style.ansi(),
+        }
+    }
+
+    /// Escape sequence that starts the status line
+    pub fn status_bar(&self) -> String {
+        match self.status_bar {
+            Some((fg, bg)) => fg.fg() + &bg.bg(),
+            None => ansi::REVERSE.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_parse_and_escapes() {
+        assert_eq!(Color::parse("#ff8000"), Some(Color::Rgb(255, 128, 0)));
+        assert_eq!(Color::parse("ff8000"), None);
+        assert_eq!(Color::parse("#ff80"), None);
+        assert_eq!(Color::parse("#gg0000"), None);
+        assert_eq!(Color::Rgb(1, 2, 3).fg(), ansi::fg_rgb(1, 2, 3));
+        assert_eq!(Color::Ansi(ansi::FG_BLUE).bg(), ansi::BG_BLUE);
+        assert_eq!(Color::Ansi(ansi::FG_DEFAULT).bg(), ansi::BG_DEFAULT);
+        assert_eq!(Color::Ansi(ansi::FG_BRIGHT_BLACK).bg(), "\x1b[100m");
+    }
+
+    #[test]
+    fn test_builtin_themes() {
+        let dark = Theme::dark();
+        assert_eq!(dark.style(TokenType::Keyword), TokenType::Keyword.style().ansi());
+        assert_eq!(dark.status_bar(), ansi::REVERSE);
+
+        let light = Theme::named("light", false).unwrap();
+        assert_eq!(light.style(TokenType::Number), ansi::FG_RED);
+        assert_eq!(light.style(TokenType::Keyword), dark.style(TokenType::Keyword));
+        assert!(Theme::named("solarized", false).is_none());
+    }
+
+    #[test]
+    fn test_truecolor_themes() {
+        let dark = Theme::named("dark", true).unwrap();
+        assert_eq!(dark.style(TokenType::String), ansi::fg_rgb(0x98, 0xc3, 0x79));
+        assert_eq!(dark.style(TokenType::Keyword), format!("{}{}", ansi::fg_rgb(0xc6, 0x78, 0xdd), ansi::BOLD));
+        assert_eq!(dark.line_number, Color::Rgb(0x5c, 0x63, 0x70));
+        // Plain text keeps the terminal's own foreground
+        assert_eq!(dark.style(TokenType::Normal), ansi::FG_DEFAULT);
+
+        let light = Theme::named("light", true).unwrap();
+        assert_eq!(light.name, "light");
+        assert_eq!(light.style(TokenType::Function), ansi::fg_rgb(0x40, 0x78, 0xf2));
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = Theme::parse("# my colors\nkeyword = #ff0000\n\ncomment=#808080\nstatus_bg = #000080\n").unwrap();
+        assert_eq!(theme.name, "custom");
+        assert_eq!(theme.style(TokenType::Keyword), format!("{}{}", ansi::fg_rgb(255, 0, 0), ansi::BOLD));
+        assert_eq!(theme.style(TokenType::Comment), format!("{}{}", ansi::fg_rgb(128, 128, 128), ansi::ITALIC));
+        assert_eq!(theme.style(TokenType::String), TokenType::String.style().ansi());
+        assert_eq!(theme.status_bar(), format!("{}{}", ansi::FG_DEFAULT, ansi::bg_rgb(0, 0, 128)));
+
+        assert_eq!(Theme::parse("keyword #ff0000").unwrap_err(), "line 1: expected key = #rrggbb");
+        assert_eq!(Theme::parse("\nkeyword = red").unwrap_err(), "line 2: invalid color: red");
+        assert_eq!(Theme::parse("keywords = #ff0000").unwrap_err(), "line 1: unknown color name: keywords");
+    }
+}
@@ -0,0 +1,215 @@
+use crate::buffer::Line;
+use crate::textobject::Pos;
+
+/// One undoable change: the `old` lines at `start` were replaced by `new`
+struct Change {
+    id: u64,
+    start: usize,
+    old: Vec<Line>,
+    new: Vec<Line>,
+    cursor_before: Pos,
+    cursor_after: Pos,
+}
+
+/// Undo and redo stacks for a buffer. Changes are found by comparing the lines with a
+/// copy taken at the previous commit, so edits only need to say that they happened
+/// and everything between two commits becomes a single undo step.
+pub struct UndoHistory {
+    /// The lines as of the last commit; `None` until the first one
+    base: Option<Vec<Line>>,
+    undo_stack: Vec<Change>,
+    redo_stack: Vec<Change>,
+    next_id: u64,
+    /// Id of the newest change when the buffer was saved (0 for none), or `None` if
+    /// that state can no longer be reached
+    save_point: Option<u64>,
+}
+
+impl UndoHistory {
+    pub fn new() -> Self {
+        UndoHistory {
+            base: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            next_id: 0,
+            save_point: Some(0),
+        }
+    }
+
+    fn top_id(&self) -> u64 {
+        self.undo_stack.last().map_or(0, |change| change.id)
+    }
+
+    /// Record any difference between `lines` and the last commit as one change,
+    /// returning its first row and how many lines it removed and inserted there
+    pub fn commit(&mut self, lines: &[Line], cursor_before: Pos, cursor_after: Pos) -> Option<(usize, usize, usize)> {
+        let Some(base) = &mut self.base else {
+            self.base = Some(lines.to_vec());
+            return None;
+        };
+
+        let prefix = base.iter().zip(lines).take_while(|(a, b)| a == b).count();
+        if prefix == base.len() && prefix == lines.len() {
+            return None;
+        }
+        let suffix = base
+            .iter()
+            .rev()
+            .zip(lines.iter().rev())
+            .take(base.len().min(lines.len()) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let new = lines[prefix..lines.len() - suffix].to_vec();
+        let old: Vec<Line> = base
+            .splice(prefix..base.len() - suffix, new.iter().cloned())
+            .collect();
+
+        // A new change discards the redo stack, and with it possibly the saved state
+        if self.redo_stack.iter().any(|change| Some(change.id) == self.save_point) {
+            self.save_point = None;
+        }
+        self.redo_stack.clear();
+
+        let span = (prefix, old.len(), new.len());
+        self.next_id += 1;
+        self.undo_stack.push(Change {
+            id: self.next_id,
+            start: prefix,
+            old,
+            new,
+            cursor_before,
+            cursor_after,
+        });
+        Some(span)
+    }
+
+    /// Revert the newest change, returning the cursor position from before it
+    pub fn undo(&mut self, lines: &mut Vec<Line>) -> Option<Pos> {
+        let change = self.undo_stack.pop()?;
+        let range = change.start..change.start + change.new.len();
+        lines.splice(range.clone(), change.old.iter().cloned());
+        if let Some(base) = &mut self.base {
+            base.splice(range, change.old.iter().cloned());
+        }
+        let cursor = change.cursor_before;
+        self.redo_stack.push(change);
+        Some(cursor)
+    }
+
+    /// Reapply the most recently undone change, returning the cursor position after it
+    pub fn redo(&mut self, lines: &mut Vec<Line>) -> Option<Pos> {
+        let change = self.redo_stack.pop()?;
+        let range = change.start..change.start + change.old.len();
+        lines.splice(range.clone(), change.new.iter().cloned());
+        if let Some(base) = &mut self.base {
+            base.splice(range, change.new.iter().cloned());
+        }
+        let cursor = change.cursor_after;
+        self.undo_stack.push(change);
+        Some(cursor)
+    }
+
+    /// Remember that `lines` were just saved
+    pub fn mark_saved(&mut self, lines: &[Line]) {
+        let uncommitted = self.base.as_deref().is_some_and(|base| base != lines);
+        // Uncommitted edits become the next change when they are committed
+        self.save_point = Some(if uncommitted { self.next_id + 1 } else { self.top_id() });
+    }
+
+    /// Whether the lines match what was last saved
+    pub fn at_save_point(&self) -> bool {
+        self.save_point == Some(self.top_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<Line> {
+        text.iter().map(|l| Line::from_str(l)).collect()
+    }
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_undo_redo_round_trip() {
+        let mut history = UndoHistory::new();
+        let mut buf = lines(&["a", "b", "c"]);
+        history.commit(&buf, (0, 0), (0, 0));
+
+        buf[1] = Line::from_str("B");
+        buf.insert(2, Line::from_str("new"));
+        history.commit(&buf, (1, 0), (2, 2));
+        buf.remove(0);
+        history.commit(&buf, (0, 0), (0, 0));
+
+        assert_eq!(history.undo(&mut buf), Some((0, 0)));
+        assert_eq!(text(&buf), ["a", "B", "new", "c"]);
+        assert_eq!(history.undo(&mut buf), Some((1, 0)));
+        assert_eq!(text(&buf), ["a", "b", "c"]);
+        assert_eq!(history.undo(&mut buf), None);
+
+        assert_eq!(history.redo(&mut buf), Some((2, 2)));
+        assert_eq!(text(&buf), ["a", "B", "new", "c"]);
+
+        // A new change clears what could be redone
+        buf.push(Line::from_str("end"));
+        history.commit(&buf, (0, 0), (4, 0));
+        assert_eq!(history.redo(&mut buf), None);
+        history.undo(&mut buf);
+        assert_eq!(text(&buf), ["a", "B", "new", "c"]);
+    }
+
+    #[test]
+    fn test_commit_without_changes_is_ignored() {
+        let mut history = UndoHistory::new();
+        let mut buf = lines(&["x"]);
+        history.commit(&buf, (0, 0), (0, 0));
+        assert_eq!(history.commit(&buf, (0, 0), (0, 0)), None);
+        assert_eq!(history.undo(&mut buf), None);
+
+        // Repeated identical lines still produce a minimal change
+        let mut buf = lines(&["x", "x"]);
+        history.commit(&buf, (0, 0), (0, 0));
+        buf.push(Line::from_str("x"));
+        assert_eq!(history.commit(&buf, (0, 0), (0, 0)), Some((2, 0, 1)));
+        history.undo(&mut buf);
+        assert_eq!(text(&buf), ["x", "x"]);
+    }
+
+    #[test]
+    fn test_save_point() {
+        let mut history = UndoHistory::new();
+        let mut buf = lines(&["one"]);
+        history.commit(&buf, (0, 0), (0, 0));
+        assert!(history.at_save_point());
+
+        buf[0] = Line::from_str("two");
+        history.commit(&buf, (0, 0), (0, 0));
+        assert!(!history.at_save_point());
+        history.mark_saved(&buf);
+        assert!(history.at_save_point());
+
+        history.undo(&mut buf);
+        assert!(!history.at_save_point());
+        history.redo(&mut buf);
+        assert!(history.at_save_point());
+
+        // Saving before the edits are committed counts once they are
+        buf[0] = Line::from_str("three");
+        history.mark_saved(&buf);
+        history.commit(&buf, (0, 0), (0, 0));
+        assert!(history.at_save_point());
+
+        // Once the saved state is discarded from redo it can't be reached again
+        history.undo(&mut buf);
+        buf[0] = Line::from_str("four");
+        history.commit(&buf, (0, 0), (0, 0));
+        history.undo(&mut buf);
+        assert!(!history.at_save_point());
+    }
+}